@@ -0,0 +1,159 @@
+// A mock of the Solana PubSub websocket API for trying out the live ingestion locally
+//
+//   cargo run --example mock_ws
+//   LIVE_INGESTION=true WS_ENDPOINT_DEVNET=ws://127.0.0.1:8900 cargo run
+//
+// It accepts the logsSubscribe and accountSubscribe requests of the backend and sends
+// notifications to every open subscription on the commands read from stdin:
+//   log <signature>   a logsNotification of the signature, which should be a real one
+//                     of the watched address so the backend can fetch its transaction
+//   account           an accountNotification
+//   drop              close every connection to try out the reconnect and catch up
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use futures::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+#[derive(Debug, Clone)]
+enum Command {
+    Log(String),
+    Account,
+    Drop,
+}
+
+static SUBSCRIPTION_ID: AtomicU64 = AtomicU64::new(1);
+static SLOT: AtomicU64 = AtomicU64::new(1);
+
+#[tokio::main]
+async fn main() {
+    let addr = std::env::var("MOCK_WS_ADDR").unwrap_or_else(|_| "127.0.0.1:8900".to_string());
+    let listener = TcpListener::bind(&addr)
+        .await
+        .expect("Failed to bind the mock websocket server");
+    println!("Mock websocket server listening on ws://{addr}");
+
+    let (commands, _) = broadcast::channel(16);
+
+    // Read the commands from stdin and hand them to every connection
+    let sender = commands.clone();
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let mut parts = line.split_whitespace();
+            let command = match (parts.next(), parts.next()) {
+                (Some("log"), Some(signature)) => Command::Log(signature.to_string()),
+                (Some("account"), _) => Command::Account,
+                (Some("drop"), _) => Command::Drop,
+                _ => {
+                    println!("Unknown command, use: log <signature> | account | drop");
+                    continue;
+                }
+            };
+            let _ = sender.send(command);
+        }
+    });
+
+    loop {
+        let Ok((stream, peer)) = listener.accept().await else {
+            continue;
+        };
+        println!("Connection from {peer}");
+        tokio::spawn(serve(stream, commands.subscribe()));
+    }
+}
+
+async fn serve(stream: TcpStream, mut commands: broadcast::Receiver<Command>) {
+    let Ok(mut ws) = accept_async(stream).await else {
+        return;
+    };
+    let mut logs_subscriptions: Vec<u64> = vec![];
+    let mut account_subscriptions: Vec<u64> = vec![];
+
+    loop {
+        tokio::select! {
+            message = ws.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Ping(data))) => {
+                        let _ = ws.send(Message::Pong(data)).await;
+                        continue;
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+                let Ok(request) = serde_json::from_str::<Value>(&text) else {
+                    continue;
+                };
+
+                let id = request["id"].clone();
+                let result = match request["method"].as_str() {
+                    Some("logsSubscribe") => {
+                        let subscription = SUBSCRIPTION_ID.fetch_add(1, Ordering::Relaxed);
+                        logs_subscriptions.push(subscription);
+                        json!(subscription)
+                    }
+                    Some("accountSubscribe") => {
+                        let subscription = SUBSCRIPTION_ID.fetch_add(1, Ordering::Relaxed);
+                        account_subscriptions.push(subscription);
+                        json!(subscription)
+                    }
+                    Some(method) if method.ends_with("Unsubscribe") => json!(true),
+                    _ => {
+                        let error = json!({"code": -32601, "message": "Method not found"});
+                        let response = json!({"jsonrpc": "2.0", "error": error, "id": id});
+                        let _ = ws.send(Message::Text(response.to_string().into())).await;
+                        continue;
+                    }
+                };
+                let response = json!({"jsonrpc": "2.0", "result": result, "id": id});
+                let _ = ws.send(Message::Text(response.to_string().into())).await;
+            }
+            command = commands.recv() => {
+                let Ok(command) = command else {
+                    continue;
+                };
+                let slot = SLOT.fetch_add(1, Ordering::Relaxed);
+                let (method, subscriptions, value) = match command {
+                    Command::Log(signature) => (
+                        "logsNotification",
+                        &logs_subscriptions,
+                        json!({"signature": signature, "err": null, "logs": []}),
+                    ),
+                    Command::Account => (
+                        "accountNotification",
+                        &account_subscriptions,
+                        json!({
+                            "lamports": 0,
+                            "data": ["", "base64"],
+                            "owner": "11111111111111111111111111111111",
+                            "executable": false,
+                            "rentEpoch": 0,
+                            "space": 0
+                        }),
+                    ),
+                    Command::Drop => {
+                        let _ = ws.close(None).await;
+                        break;
+                    }
+                };
+
+                for subscription in subscriptions {
+                    let notification = json!({
+                        "jsonrpc": "2.0",
+                        "method": method,
+                        "params": {
+                            "result": {"context": {"slot": slot}, "value": value},
+                            "subscription": subscription
+                        }
+                    });
+                    let _ = ws.send(Message::Text(notification.to_string().into())).await;
+                }
+            }
+        }
+    }
+    println!("Connection closed");
+}
@@ -0,0 +1,128 @@
+-- The _id of the Mongo documents is kept as the id of every row,
+-- e.g. "devnet:<address>" or "devnet:<address>:<signature>"
+
+CREATE TABLE accounts (
+    id TEXT PRIMARY KEY,
+    cluster TEXT NOT NULL,
+    address TEXT NOT NULL,
+    status TEXT NOT NULL,
+    lamports BIGINT NOT NULL,
+    owner TEXT NOT NULL,
+    executable BOOLEAN NOT NULL,
+    data_length BIGINT NOT NULL,
+    rent_epoch BIGINT NOT NULL,
+    indexed_at TIMESTAMPTZ NOT NULL,
+    last_updated_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE address_indexing_states (
+    id TEXT PRIMARY KEY,
+    cluster TEXT NOT NULL,
+    address TEXT NOT NULL,
+    state TEXT NOT NULL,
+    checkpoint JSONB,
+    bounds JSONB NOT NULL,
+    last_error JSONB,
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX address_indexing_states_cluster_state ON address_indexing_states (cluster, state);
+
+CREATE TABLE transaction_signatures (
+    id TEXT PRIMARY KEY,
+    cluster TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    account_address TEXT NOT NULL,
+    slot BIGINT NOT NULL,
+    block_time BIGINT,
+    confirmation_status TEXT NOT NULL,
+    indexed_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX transaction_signatures_address_slot
+    ON transaction_signatures (cluster, account_address, slot DESC);
+CREATE INDEX transaction_signatures_signature ON transaction_signatures (cluster, signature);
+CREATE INDEX transaction_signatures_unfinalized
+    ON transaction_signatures (cluster, indexed_at)
+    WHERE confirmation_status <> 'finalized';
+
+-- A transaction body is stored once per signature however many addresses share it
+CREATE TABLE transactions (
+    id TEXT PRIMARY KEY,
+    cluster TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    slot BIGINT NOT NULL,
+    block_time BIGINT,
+    version TEXT,
+    account_keys TEXT[] NOT NULL,
+    loaded_addresses JSONB NOT NULL,
+    transaction JSONB NOT NULL,
+    indexed_at TIMESTAMPTZ NOT NULL,
+    UNIQUE (cluster, signature)
+);
+
+CREATE TABLE address_transactions (
+    id TEXT PRIMARY KEY,
+    cluster TEXT NOT NULL,
+    account_address TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    slot BIGINT NOT NULL,
+    block_time BIGINT,
+    indexed_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX address_transactions_address_slot
+    ON address_transactions (cluster, account_address, slot DESC);
+CREATE INDEX address_transactions_signature ON address_transactions (cluster, signature);
+
+CREATE TABLE indexing_jobs (
+    id TEXT PRIMARY KEY,
+    cluster TEXT NOT NULL,
+    address TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    priority INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    enqueued_at TIMESTAMPTZ NOT NULL,
+    started_at TIMESTAMPTZ,
+    bounds JSONB
+);
+
+CREATE INDEX indexing_jobs_queue ON indexing_jobs (status, priority DESC, enqueued_at);
+
+CREATE TABLE dead_letters (
+    id TEXT PRIMARY KEY,
+    cluster TEXT NOT NULL,
+    account_address TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    status TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    error JSONB NOT NULL,
+    next_attempt_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX dead_letters_due ON dead_letters (cluster, status, next_attempt_at);
+CREATE INDEX dead_letters_address ON dead_letters (cluster, account_address);
+CREATE INDEX dead_letters_signature ON dead_letters (cluster, signature);
+
+CREATE TABLE refresh_schedules (
+    id TEXT PRIMARY KEY,
+    cluster TEXT NOT NULL,
+    address TEXT NOT NULL,
+    interval_secs BIGINT NOT NULL,
+    last_viewed_at TIMESTAMPTZ NOT NULL,
+    next_refresh_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX refresh_schedules_due ON refresh_schedules (next_refresh_at);
+
+CREATE TABLE watched_addresses (
+    id TEXT PRIMARY KEY,
+    cluster TEXT NOT NULL,
+    address TEXT NOT NULL,
+    watched_at TIMESTAMPTZ NOT NULL
+);
@@ -1,26 +1,104 @@
 use dashmap::DashMap;
-use std::sync::{Arc, atomic::AtomicBool};
-use tokio::sync::{RwLock, broadcast};
+use std::collections::HashMap;
+use std::sync::{
+    Arc, Mutex, PoisonError,
+    atomic::{AtomicBool, AtomicU64, AtomicUsize},
+};
+use tokio::sync::{Notify, RwLock, broadcast};
+use tokio_util::sync::CancellationToken;
 
-use mongodb::Database;
-use solana_client::nonblocking::rpc_client::RpcClient;
 use tracing::{error, warn};
 
-use crate::message::SyncStatus;
+use crate::{
+    cluster::Cluster, config::Config, db::Storage, error::AppError, live::AddressWatcher,
+    message::SyncStatus, rpc::SolanaApi,
+};
 
-// A global AddressSession for each address whenever the account indexing or syncing tasks are running.
+// A global AddressSession for each address (of a cluster) whenever the account indexing or syncing tasks are running.
 // Here the sender is of the broadcast channel which is used for subscribing
 // all the receiver or clients to this channel specific to the address to receive real-time updates.
 // started is an AtomicBool that is a thread-safe boolean variable to prevent data race
 // in case of multiple concurrent requests try to index or refresh the same address.
+// cancel is the token the running job checks between its RPC calls, it is cancelled
+// by an admin cancel/pause or once the last subscriber of a pausable job disconnects.
 #[derive(Debug)]
 pub struct AddressSession {
     pub sender: broadcast::Sender<SyncStatus>,
     pub started: AtomicBool,
     pub past_events: RwLock<Vec<SyncStatus>>,
+    pub cancel: CancellationToken,
+    // Why the token was cancelled, so the job knows which state to leave the address in
+    pub stop_request: Mutex<Option<StopRequest>>,
+    // Number of SSE clients currently following the job
+    pub subscribers: AtomicUsize,
+    // Number of transactions the job skipped and recorded as dead letters
+    pub skipped: AtomicU64,
+}
+
+// How a running job was asked to stop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopRequest {
+    // Stop the job for good, it is only resumed by indexing or refreshing the address again
+    Cancel,
+    // Stop the job until it is resumed
+    Pause,
+}
+
+impl std::fmt::Display for StopRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopRequest::Cancel => f.write_str("cancelled"),
+            StopRequest::Pause => f.write_str("paused"),
+        }
+    }
+}
+
+impl Default for AddressSession {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(10);
+        AddressSession {
+            sender,
+            started: AtomicBool::new(false),
+            past_events: RwLock::new(Vec::new()),
+            cancel: CancellationToken::new(),
+            stop_request: Mutex::new(None),
+            subscribers: AtomicUsize::new(0),
+            skipped: AtomicU64::new(0),
+        }
+    }
 }
 
 impl AddressSession {
+    // Ask the job to stop at its next check
+    // A cancel always wins over a pause that was requested before it
+    pub fn request_stop(&self, request: StopRequest) {
+        {
+            let mut stop_request = self
+                .stop_request
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            if *stop_request != Some(StopRequest::Cancel) {
+                *stop_request = Some(request);
+            }
+        }
+        self.cancel.cancel();
+    }
+
+    // Fail with the Stopped error once the job was asked to stop
+    // Called between the RPC calls of the job so it stops at a consistent point
+    pub fn check_stop(&self) -> Result<(), AppError> {
+        if !self.cancel.is_cancelled() {
+            return Ok(());
+        }
+
+        let request = self
+            .stop_request
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .unwrap_or(StopRequest::Cancel);
+        Err(AppError::Stopped(request))
+    }
+
     // Store the copy of events in the past_events before sending it to the channel
     // for streaming all the events to the late subscribers
     pub async fn emit_event(&self, event: SyncStatus) {
@@ -30,7 +108,29 @@ impl AddressSession {
             let mut events = self.past_events.write().await;
             events.push(event.clone());
         }
-        // Sending the events to the channel and logging on error
+        self.send_event(event);
+    }
+
+    // Same as emit_event for the queue position of the job, but only the latest position
+    // is kept in the past_events since a new one is reported on every enqueue and dequeue
+    pub async fn emit_queue_position(&self, position: String) {
+        let event = SyncStatus::Queued(position);
+        {
+            let mut events = self.past_events.write().await;
+            match events
+                .iter_mut()
+                .rev()
+                .find(|past| matches!(past, SyncStatus::Queued(_)))
+            {
+                Some(past) => *past = event.clone(),
+                None => events.push(event.clone()),
+            }
+        }
+        self.send_event(event);
+    }
+
+    // Sending the events to the channel and logging on error
+    fn send_event(&self, event: SyncStatus) {
         if let Err(err) = self.sender.send(event) {
             error!(
                 "Error occcured while sending event to channel: {}",
@@ -42,49 +142,73 @@ impl AddressSession {
 
 #[derive(Clone)]
 pub struct AppState {
-    // The standard mongodb database
-    pub db: Database,
-    // The Solana Json-Rpc Client wrapped inside an Arc to be shared across threads
-    pub rpc: Arc<RpcClient>,
-    // A dashmap that stores the address(String) as its key and an AddressSession (wrapped inside
-    // an Arc for sharing across threads)
+    // The storage backend (MongoDB or in memory) behind the storage traits
+    pub db: Arc<dyn Storage>,
+    // The rate limited Solana Json-Rpc Client of every enabled cluster
+    // wrapped inside an Arc to be shared across threads
+    pub rpc: Arc<HashMap<Cluster, Arc<dyn SolanaApi>>>,
+    // The indexer tunables loaded from the env
+    pub config: Arc<Config>,
+    // A dashmap that stores the cluster and address as its key and an AddressSession (wrapped
+    // inside an Arc for sharing across threads)
     // Why dashmap and not hashmap? Well, dashmap has built-in fine-grained locks for its sharded
     // map regions that allows multiple threads to write to different keys concurrently.
     // If we use hashmap and locks that is a coarse-grained locking which is harder and slower to
     // manage across threads while dashmap is built for high-performance and multithreaded systems.
-    pub session: Arc<DashMap<String, Arc<AddressSession>>>,
+    pub session: Arc<DashMap<(Cluster, String), Arc<AddressSession>>>,
+    // Wakes up an idle worker of the job queue whenever a job is enqueued
+    pub job_notify: Arc<Notify>,
+    // The live ingestion of every watched address
+    pub watchers: Arc<DashMap<(Cluster, String), Arc<AddressWatcher>>>,
 }
 
 impl AppState {
-    pub fn new(db: Database, rpc: Arc<RpcClient>) -> Self {
+    pub fn new(
+        db: Arc<dyn Storage>,
+        rpc: HashMap<Cluster, Arc<dyn SolanaApi>>,
+        config: Arc<Config>,
+    ) -> Self {
         AppState {
             db,
-            rpc,
+            rpc: Arc::new(rpc),
+            config,
             session: Arc::new(DashMap::new()),
+            job_notify: Arc::new(Notify::new()),
+            watchers: Arc::new(DashMap::new()),
         }
     }
 
     // Session creation or retrieval when indexing or refreshing an address
     // It involves adding the address into the DashMap along with the broadcast channel sender and
     // started atomic bool for handling multiple such requests
-    pub fn get_or_create_session(&self, address: &str) -> Arc<AddressSession> {
+    pub fn get_or_create_session(&self, cluster: Cluster, address: &str) -> Arc<AddressSession> {
         warn!("Session data: {:?}", self.session);
         self.session
-            .entry(address.to_string())
-            .or_insert_with(|| {
-                let (sender, _) = broadcast::channel(10);
-                Arc::new(AddressSession {
-                    sender,
-                    started: AtomicBool::new(false),
-                    past_events: RwLock::new(Vec::new()),
-                })
-            })
+            .entry((cluster, address.to_string()))
+            .or_insert_with(|| Arc::new(AddressSession::default()))
             .clone()
     }
 
+    // The session of the address when it has a job queued or running
+    pub fn find_session(&self, cluster: Cluster, address: &str) -> Option<Arc<AddressSession>> {
+        self.session
+            .get(&(cluster, address.to_string()))
+            .map(|session| session.clone())
+    }
+
     // Once the indexing or refreshing is done
     // making sure to remove the address from the DashMap
-    pub fn remove_session(&self, address: &str) -> bool {
-        self.session.remove(address).is_some()
+    pub fn remove_session(&self, cluster: Cluster, address: &str) -> bool {
+        self.session
+            .remove(&(cluster, address.to_string()))
+            .is_some()
+    }
+
+    // The RPC client of the cluster, which has to be enabled in this deployment
+    pub fn rpc_for(&self, cluster: Cluster) -> Result<&dyn SolanaApi, AppError> {
+        self.rpc
+            .get(&cluster)
+            .map(|rpc| rpc.as_ref())
+            .ok_or_else(|| AppError::BadRequest(format!("Cluster {cluster} is not enabled")))
     }
 }
@@ -0,0 +1,116 @@
+use axum::{
+    extract::{Request, State},
+    http::header::AUTHORIZATION,
+    middleware::Next,
+    response::Response,
+};
+
+use std::sync::Arc;
+
+use crate::{config::Config, error::AppError};
+
+// Middleware of the admin routes
+// A request is let through only when it carries the ADMIN_TOKEN as its bearer token,
+// the admin routes stay closed altogether while no token is configured
+pub async fn require_admin_token(
+    State(config): State<Arc<Config>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(admin_token) = config.admin_token.as_deref() else {
+        return Err(AppError::Unauthorized(
+            "Admin endpoints are disabled".to_string(),
+        ));
+    };
+
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match token {
+        Some(token) if tokens_match(token.trim(), admin_token) => Ok(next.run(request).await),
+        _ => Err(AppError::Unauthorized("Invalid admin token".to_string())),
+    }
+}
+
+// Compare the whole token every time so the response time doesn't tell
+// how much of a guessed token is right
+fn tokens_match(token: &str, expected: &str) -> bool {
+    token.len() == expected.len()
+        && token
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        Router,
+        body::Body,
+        http::{Request, StatusCode},
+        middleware,
+        routing::post,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn admin_router(admin_token: Option<&str>) -> Router {
+        let config = Config {
+            admin_token: admin_token.map(str::to_string),
+            ..Config::from_env()
+        };
+
+        Router::new()
+            .route("/cancel", post(|| async { "cancelled" }))
+            .route_layer(middleware::from_fn_with_state(
+                Arc::new(config),
+                require_admin_token,
+            ))
+    }
+
+    async fn send(router: Router, authorization: Option<&str>) -> StatusCode {
+        let mut request = Request::post("/cancel");
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+
+        router
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn admin_routes_accept_the_admin_token() {
+        let status = send(admin_router(Some("secret")), Some("Bearer secret")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn admin_routes_reject_a_missing_or_wrong_token() {
+        assert_eq!(
+            send(admin_router(Some("secret")), None).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            send(admin_router(Some("secret")), Some("Bearer secreT")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            send(admin_router(Some("secret")), Some("secret")).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn admin_routes_are_closed_without_a_configured_token() {
+        let status = send(admin_router(None), Some("Bearer ")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,139 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+// Solana Mainnet RPC URL
+const MAIN_NET: &str = "https://api.mainnet-beta.solana.com";
+// Solana Testnet RPC URL
+const TEST_NET: &str = "https://api.testnet.solana.com";
+// Solana Devnet RPC URL
+const DEV_NET: &str = "https://api.devnet.solana.com";
+// Local test validator RPC URL
+const LOCAL_NET: &str = "http://127.0.0.1:8899";
+
+// The Solana cluster an address is indexed from
+// Every stored document is scoped by its cluster so the same address
+// on different clusters never collides
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Cluster {
+    Mainnet,
+    Testnet,
+    Devnet,
+    Localnet,
+}
+
+impl Cluster {
+    pub const ALL: [Cluster; 4] = [
+        Cluster::Mainnet,
+        Cluster::Testnet,
+        Cluster::Devnet,
+        Cluster::Localnet,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Cluster::Mainnet => "mainnet",
+            Cluster::Testnet => "testnet",
+            Cluster::Devnet => "devnet",
+            Cluster::Localnet => "localnet",
+        }
+    }
+
+    // The public RPC URL used when no endpoints are configured for the cluster
+    pub fn default_url(&self) -> &'static str {
+        match self {
+            Cluster::Mainnet => MAIN_NET,
+            Cluster::Testnet => TEST_NET,
+            Cluster::Devnet => DEV_NET,
+            Cluster::Localnet => LOCAL_NET,
+        }
+    }
+}
+
+// The websocket URL of the PubSub API next to an RPC URL
+// Only the scheme changes, along with the port of the local test validator
+// that serves the PubSub API on the port after the RPC one
+pub fn websocket_url(rpc_url: &str) -> String {
+    let Ok(mut url) = Url::parse(rpc_url) else {
+        return rpc_url.to_string();
+    };
+
+    let scheme = match url.scheme() {
+        "https" => "wss",
+        "http" => "ws",
+        _ => return rpc_url.to_string(),
+    };
+    // Switching between these schemes of a URL with a host can't fail
+    let _ = url.set_scheme(scheme);
+    if url.port() == Some(8899) {
+        let _ = url.set_port(Some(8900));
+    }
+
+    url.to_string()
+}
+
+impl fmt::Display for Cluster {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Cluster {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "mainnet" | "mainnet-beta" => Ok(Cluster::Mainnet),
+            "testnet" => Ok(Cluster::Testnet),
+            "devnet" => Ok(Cluster::Devnet),
+            "localnet" | "localhost" => Ok(Cluster::Localnet),
+            other => Err(format!("Unknown cluster '{other}'")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn websocket_url_switches_the_scheme() {
+        assert_eq!(
+            websocket_url("https://api.devnet.solana.com"),
+            "wss://api.devnet.solana.com/"
+        );
+        assert_eq!(
+            websocket_url("http://rpc.internal:8080/solana"),
+            "ws://rpc.internal:8080/solana"
+        );
+    }
+
+    #[test]
+    fn websocket_url_moves_the_local_validator_port() {
+        assert_eq!(websocket_url(LOCAL_NET), "ws://127.0.0.1:8900/");
+    }
+
+    #[test]
+    fn websocket_url_keeps_the_rest_of_the_url() {
+        // Only the port of the host is the RPC port, not digits elsewhere in the URL
+        assert_eq!(
+            websocket_url("https://rpc.example.com/v1/?api-key=ab:8899cd"),
+            "wss://rpc.example.com/v1/?api-key=ab:8899cd"
+        );
+        assert_eq!(
+            websocket_url("https://rpc.example.com:18899"),
+            "wss://rpc.example.com:18899/"
+        );
+    }
+
+    #[test]
+    fn websocket_url_leaves_other_urls_alone() {
+        assert_eq!(
+            websocket_url("wss://api.devnet.solana.com"),
+            "wss://api.devnet.solana.com"
+        );
+        assert_eq!(websocket_url("not a url"), "not a url");
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Deserialize;
+use solana_commitment_config::{CommitmentConfig, CommitmentLevel};
+
+use crate::{
+    cluster::{Cluster, websocket_url},
+    models::IndexingBounds,
+};
+
+// An RPC endpoint of a cluster's pool as given in the RPC_ENDPOINTS_<CLUSTER> env variable
+// (a JSON array) e.g. RPC_ENDPOINTS_DEVNET=[{"url": "https://api.devnet.solana.com", "weight": 2}]
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcEndpointConfig {
+    pub url: String,
+    #[serde(default)]
+    pub auth_header: Option<RpcAuthHeader>,
+    // Share of the requests routed to this endpoint relative to the others
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    // Overrides the RPC_REQUESTS_PER_SECOND for this endpoint
+    #[serde(default)]
+    pub requests_per_second: Option<f64>,
+}
+
+impl RpcEndpointConfig {
+    pub fn from_url(url: &str) -> Self {
+        RpcEndpointConfig {
+            url: url.to_string(),
+            auth_header: None,
+            weight: default_weight(),
+            requests_per_second: None,
+        }
+    }
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+// Header sent with every request to an RPC provider that requires an API key
+#[derive(Clone, Deserialize)]
+pub struct RpcAuthHeader {
+    pub name: String,
+    pub value: String,
+}
+
+// Keep the API key out of the logs
+impl std::fmt::Debug for RpcAuthHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RpcAuthHeader")
+            .field("name", &self.name)
+            .field("value", &"<redacted>")
+            .finish()
+    }
+}
+
+// What the startup recovery does with the runs that a crash or redeploy interrupted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecoveryPolicy {
+    // Queue the run again so it resumes from its checkpoint
+    Requeue,
+    // Mark the run as failed and leave resuming it to the clients
+    Fail,
+}
+
+impl FromStr for RecoveryPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "requeue" => Ok(RecoveryPolicy::Requeue),
+            "fail" => Ok(RecoveryPolicy::Fail),
+            other => Err(format!("Unknown recovery policy '{other}'")),
+        }
+    }
+}
+
+// Where the indexer stores the accounts, their history and its own bookkeeping
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageBackend {
+    Mongo,
+    // Typed tables in a PostgreSQL database, see migrations/postgres
+    Postgres,
+    // A single local SQLite file embedded in the process, no external service needed
+    Sqlite,
+    // Kept in the process only, everything is lost on restart
+    Memory,
+}
+
+impl FromStr for StorageBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "mongo" | "mongodb" => Ok(StorageBackend::Mongo),
+            "postgres" | "postgresql" => Ok(StorageBackend::Postgres),
+            "sqlite" => Ok(StorageBackend::Sqlite),
+            "memory" => Ok(StorageBackend::Memory),
+            other => Err(format!("Unknown storage backend '{other}'")),
+        }
+    }
+}
+
+// What happens to a job once the last SSE client following it disconnects
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisconnectPolicy {
+    // Keep running the job without anyone watching
+    Continue,
+    // Pause the job until it is indexed/refreshed again or resumed by an admin
+    Pause,
+}
+
+impl FromStr for DisconnectPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "continue" => Ok(DisconnectPolicy::Continue),
+            "pause" => Ok(DisconnectPolicy::Pause),
+            other => Err(format!("Unknown disconnect policy '{other}'")),
+        }
+    }
+}
+
+// Tunables of the indexer that are read from the env
+// Every one of them is optional and falls back to a sensible default
+#[derive(Debug, Clone)]
+pub struct Config {
+    // Cluster used by the routes that don't name one
+    pub default_cluster: Cluster,
+    // Clusters that can be indexed from this deployment
+    pub clusters: Vec<Cluster>,
+    // Number of get_transaction RPC calls in flight at once while indexing
+    pub fetch_concurrency: usize,
+    // Number of fetched transactions that are flushed to the DB together
+    pub insert_batch_size: usize,
+    // Requests per second allowed by the token bucket of the RPC endpoint
+    pub rpc_requests_per_second: f64,
+    // Number of requests that can be sent at once after the endpoint was idle
+    pub rpc_burst: u32,
+    // Number of times a transient RPC error is retried before giving up
+    pub rpc_max_retries: u32,
+    // Backoff delay of the first retry, doubled for every following one
+    pub rpc_retry_base_delay: Duration,
+    // Upper limit of the backoff delay
+    pub rpc_retry_max_delay: Duration,
+    // RPC endpoints of the pool of every enabled cluster
+    pub rpc_endpoints: HashMap<Cluster, Vec<RpcEndpointConfig>>,
+    // Number of consecutive failures after which an endpoint is ejected from the pool
+    pub rpc_eject_after_failures: u32,
+    // How long an ejected endpoint gets no requests
+    pub rpc_eject_duration: Duration,
+    // Number of slots an endpoint can be behind the others before it is ejected
+    pub rpc_max_slot_lag: u64,
+    // Interval of the slot health checks of the endpoints
+    pub rpc_health_check_interval: Duration,
+    // What to do with the runs that were interrupted by the last shutdown
+    pub recovery_policy: RecoveryPolicy,
+    // Number of workers running the queued indexing jobs at once
+    pub indexing_workers: usize,
+    // What to do with a job once nobody follows it anymore
+    pub disconnect_policy: DisconnectPolicy,
+    // Bearer token of the admin endpoints, they are disabled while it is not set
+    pub admin_token: Option<String>,
+    // Number of days of history indexed when a request sets no bounds, 0 for the full history
+    pub default_index_days: u64,
+    // Commitment of the signatures and transactions fetched while indexing (confirmed or finalized)
+    pub rpc_commitment: CommitmentLevel,
+    // Interval of the checks that promote the confirmed signatures to finalized
+    pub finality_check_interval: Duration,
+    // How long a confirmed signature can be unknown to the RPC before it counts as dropped on a fork
+    pub fork_grace_period: Duration,
+    // Interval of the retries of the transactions that could not be fetched while indexing
+    pub dead_letter_retry_interval: Duration,
+    // Number of failed fetches after which a transaction is no longer retried
+    pub dead_letter_max_attempts: i32,
+    // Delay of the first retry of a transaction, doubled for every following one
+    pub dead_letter_retry_base_delay: Duration,
+    // Interval of the scheduled integrity repairs of the idle addresses, 0 (the default) turns them off
+    // since every repair pages through the whole stored history of the address again
+    pub integrity_check_interval: Duration,
+    // Interval of the checks for the addresses whose automatic refresh is due
+    pub auto_refresh_check_interval: Duration,
+    // Number of automatic refreshes queued or running at once, 0 to turn them off
+    pub auto_refresh_concurrency: usize,
+    // Refresh interval of the most viewed addresses
+    pub auto_refresh_min_interval: Duration,
+    // Refresh interval of the addresses that are hardly ever viewed
+    pub auto_refresh_max_interval: Duration,
+    // Whether the watched addresses ingest their new transactions live over websocket subscriptions
+    pub live_ingestion: bool,
+    // Websocket endpoint of every enabled cluster used by the live ingestion
+    pub ws_endpoints: HashMap<Cluster, String>,
+    // Delay of the first reconnect of a dropped subscription, doubled for every following one
+    pub live_reconnect_delay: Duration,
+    // Backend that the indexer stores its data in
+    pub storage_backend: StorageBackend,
+    // Whether the pending data migrations of the stored documents are applied on startup
+    pub migrate_on_startup: bool,
+    // The cluster that the documents stored before the cluster scoping belong to,
+    // the indexer only ran on devnet back then
+    pub legacy_cluster: Cluster,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let default_cluster = env_or("DEFAULT_CLUSTER", Cluster::Devnet);
+        let mut clusters = env_list_or("CLUSTERS", Cluster::ALL.to_vec());
+        if !clusters.contains(&default_cluster) {
+            clusters.push(default_cluster);
+        }
+
+        // Every cluster uses its RPC_ENDPOINTS_<CLUSTER> or its public RPC URL,
+        // the plain RPC_ENDPOINTS is also accepted for the default cluster
+        let rpc_endpoints = clusters
+            .iter()
+            .map(|&cluster| {
+                let key = format!("RPC_ENDPOINTS_{}", cluster.as_str().to_uppercase());
+                let mut endpoints = env_json_or(&key, Vec::new());
+                if endpoints.is_empty() && cluster == default_cluster {
+                    endpoints = env_json_or("RPC_ENDPOINTS", Vec::new());
+                }
+                if endpoints.is_empty() {
+                    endpoints = vec![RpcEndpointConfig::from_url(cluster.default_url())];
+                }
+                (cluster, endpoints)
+            })
+            .collect::<HashMap<Cluster, Vec<RpcEndpointConfig>>>();
+
+        // Every cluster uses its WS_ENDPOINT_<CLUSTER> or the websocket URL
+        // of its first RPC endpoint
+        let ws_endpoints = rpc_endpoints
+            .iter()
+            .map(|(&cluster, endpoints)| {
+                let key = format!("WS_ENDPOINT_{}", cluster.as_str().to_uppercase());
+                let url = std::env::var(&key).unwrap_or_else(|_| websocket_url(&endpoints[0].url));
+                (cluster, url)
+            })
+            .collect();
+
+        // The signatures of an address are only served from confirmed blocks and up
+        let rpc_commitment = env_or("RPC_COMMITMENT", CommitmentLevel::Finalized);
+        assert!(
+            rpc_commitment != CommitmentLevel::Processed,
+            "RPC_COMMITMENT env variable has to be confirmed or finalized"
+        );
+
+        let auto_refresh_min_interval =
+            Duration::from_secs(env_or("AUTO_REFRESH_MIN_INTERVAL_SECS", 300).max(1));
+        let auto_refresh_max_interval =
+            Duration::from_secs(env_or("AUTO_REFRESH_MAX_INTERVAL_SECS", 24 * 60 * 60))
+                .max(auto_refresh_min_interval);
+
+        Config {
+            default_cluster,
+            clusters,
+            fetch_concurrency: env_or("FETCH_CONCURRENCY", 8).max(1),
+            insert_batch_size: env_or("INSERT_BATCH_SIZE", 100).max(1),
+            rpc_requests_per_second: env_or("RPC_REQUESTS_PER_SECOND", 10.0_f64).max(0.1),
+            rpc_burst: env_or("RPC_BURST", 10).max(1),
+            rpc_max_retries: env_or("RPC_MAX_RETRIES", 5),
+            rpc_retry_base_delay: Duration::from_millis(env_or("RPC_RETRY_BASE_DELAY_MS", 500)),
+            rpc_retry_max_delay: Duration::from_millis(env_or("RPC_RETRY_MAX_DELAY_MS", 30_000)),
+            rpc_endpoints,
+            rpc_eject_after_failures: env_or("RPC_EJECT_AFTER_FAILURES", 3),
+            rpc_eject_duration: Duration::from_secs(env_or("RPC_EJECT_DURATION_SECS", 60)),
+            rpc_max_slot_lag: env_or("RPC_MAX_SLOT_LAG", 50),
+            rpc_health_check_interval: Duration::from_secs(env_or(
+                "RPC_HEALTH_CHECK_INTERVAL_SECS",
+                30,
+            )),
+            recovery_policy: env_or("RECOVERY_POLICY", RecoveryPolicy::Requeue),
+            indexing_workers: env_or("INDEXING_WORKERS", 4).max(1),
+            disconnect_policy: env_or("DISCONNECT_POLICY", DisconnectPolicy::Continue),
+            admin_token: std::env::var("ADMIN_TOKEN")
+                .ok()
+                .map(|token| token.trim().to_string())
+                .filter(|token| !token.is_empty()),
+            default_index_days: env_or("DEFAULT_INDEX_DAYS", 0),
+            rpc_commitment,
+            finality_check_interval: Duration::from_secs(env_or(
+                "FINALITY_CHECK_INTERVAL_SECS",
+                60,
+            )),
+            fork_grace_period: Duration::from_secs(env_or("FORK_GRACE_PERIOD_SECS", 300)),
+            dead_letter_retry_interval: Duration::from_secs(env_or(
+                "DEAD_LETTER_RETRY_INTERVAL_SECS",
+                60,
+            )),
+            dead_letter_max_attempts: env_or("DEAD_LETTER_MAX_ATTEMPTS", 5).max(1),
+            dead_letter_retry_base_delay: Duration::from_secs(env_or(
+                "DEAD_LETTER_RETRY_BASE_DELAY_SECS",
+                60,
+            )),
+            integrity_check_interval: Duration::from_secs(env_or(
+                "INTEGRITY_CHECK_INTERVAL_SECS",
+                0,
+            )),
+            auto_refresh_check_interval: Duration::from_secs(env_or(
+                "AUTO_REFRESH_CHECK_INTERVAL_SECS",
+                60,
+            )),
+            auto_refresh_concurrency: env_or("AUTO_REFRESH_CONCURRENCY", 2),
+            auto_refresh_min_interval,
+            auto_refresh_max_interval,
+            live_ingestion: env_or("LIVE_INGESTION", false),
+            ws_endpoints,
+            live_reconnect_delay: Duration::from_millis(env_or("LIVE_RECONNECT_DELAY_MS", 1000)),
+            storage_backend: env_or("STORAGE_BACKEND", StorageBackend::Mongo),
+            migrate_on_startup: env_or("MIGRATE_ON_STARTUP", true),
+            legacy_cluster: env_or("LEGACY_CLUSTER", Cluster::Devnet),
+        }
+    }
+
+    // Commitment sent with the RPC calls of the indexing runs
+    pub fn commitment(&self) -> CommitmentConfig {
+        CommitmentConfig {
+            commitment: self.rpc_commitment,
+        }
+    }
+
+    // Bounds of the history indexed for a request that doesn't set any
+    pub fn default_bounds(&self) -> IndexingBounds {
+        if self.default_index_days == 0 {
+            return IndexingBounds::default();
+        }
+
+        let since = chrono::Utc::now() - chrono::Duration::days(self.default_index_days as i64);
+        IndexingBounds {
+            since: Some(since.timestamp()),
+            ..Default::default()
+        }
+    }
+}
+
+// Parse the env variable into the required type or use the default when it is not set
+fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    match std::env::var(key) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .unwrap_or_else(|_| panic!("{key} env variable is invalid")),
+        Err(_) => default,
+    }
+}
+
+// Parse the JSON env variable or use the default when it is not set
+fn env_json_or<T: serde::de::DeserializeOwned>(key: &str, default: T) -> T {
+    match std::env::var(key) {
+        Ok(value) => serde_json::from_str(&value)
+            .unwrap_or_else(|err| panic!("{key} env variable is invalid: {err}")),
+        Err(_) => default,
+    }
+}
+
+// Parse the comma separated env variable or use the default when it is not set
+fn env_list_or<T: FromStr>(key: &str, default: Vec<T>) -> Vec<T> {
+    match std::env::var(key) {
+        Ok(value) => value
+            .split(',')
+            .filter(|item| !item.trim().is_empty())
+            .map(|item| {
+                item.trim()
+                    .parse()
+                    .unwrap_or_else(|_| panic!("{key} env variable is invalid"))
+            })
+            .collect(),
+        Err(_) => default,
+    }
+}
@@ -1,17 +1,364 @@
-use mongodb::{Client, Database};
+use std::collections::HashSet;
+use std::sync::Arc;
 
+use async_trait::async_trait;
+use tracing::{error, info};
+
+use crate::cluster::Cluster;
+use crate::config::{Config, StorageBackend};
 use crate::error::AppError;
+use crate::models::{
+    Account, AddressIndexingState, DeadLetter, DeadLetterStatus, IndexerStats, IndexingBounds,
+    IndexingCheckpoint, IndexingError, IndexingJob, RefreshSchedule, Timestamp, Transaction,
+    TransactionSignature, UnfinalizedSignature, UpdateAccount, UpdateAddressIndexingState,
+    UpsertCounts, WatchedAddress,
+};
+
+pub mod memory;
+pub mod mongo;
+pub mod postgres;
+mod sql;
+pub mod sqlite;
+#[cfg(test)]
+mod storage_tests;
+
+// The stored account data of the indexed addresses
+#[async_trait]
+pub trait AccountStore: Send + Sync {
+    async fn get_account(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Option<Account>, AppError>;
+
+    async fn insert_account(&self, account: &Account) -> Result<(), AppError>;
+
+    // Overwrite the account data of the address and return the updated account
+    async fn update_account(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        account: UpdateAccount,
+    ) -> Result<Account, AppError>;
+
+    async fn get_indexer_stats(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<IndexerStats, AppError>;
+
+    async fn check_account_exists(&self, cluster: Cluster, address: &str) -> bool {
+        match self.get_account(cluster, address).await {
+            Ok(acc) => match acc {
+                Some(account) => {
+                    info!("Account Found: {account:?}");
+                    true
+                }
+                None => {
+                    info!("Account Not Found");
+                    false
+                }
+            },
+            Err(e) => {
+                error!("Error occurred while finding account: {e:?}");
+                false
+            }
+        }
+    }
+}
+
+// Where every address is in its lifecycle along with the progress of its runs
+#[async_trait]
+pub trait IndexingStateStore: Send + Sync {
+    async fn get_address_indexing_state(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<AddressIndexingState, AppError>;
+
+    // Insert the record unless the address already has one and tell whether it was inserted
+    // An existing record is never overwritten, it has to move through a legal transition
+    async fn insert_address_indexing_state(
+        &self,
+        record: AddressIndexingState,
+    ) -> Result<bool, AppError>;
+
+    // Move the address to the next state of its lifecycle
+    // An illegal transition is rejected even when two runs race for the same address
+    async fn update_address_indexing_state(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        update: UpdateAddressIndexingState,
+    ) -> Result<(), AppError>;
+
+    // The addresses that are queued or in the middle of a run
+    async fn get_unfinished_address_states(&self) -> Result<Vec<AddressIndexingState>, AppError>;
+
+    // The addresses of the cluster that have no run going on
+    async fn get_idle_addresses(&self, cluster: Cluster) -> Result<Vec<String>, AppError>;
+
+    async fn save_indexing_checkpoint(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        checkpoint: &IndexingCheckpoint,
+        updated_at: Timestamp,
+    ) -> Result<(), AppError>;
+
+    // Store how deep the history of the address is indexed
+    async fn save_indexing_bounds(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        bounds: &IndexingBounds,
+        updated_at: Timestamp,
+    ) -> Result<(), AppError>;
+}
+
+// The transaction signatures of the indexed addresses, stored once for every address
+#[async_trait]
+pub trait SignatureStore: Send + Sync {
+    async fn upsert_transaction_signatures(
+        &self,
+        signatures: &[TransactionSignature],
+    ) -> Result<UpsertCounts, AppError>;
+
+    // A page of the signatures of the address, newest first
+    async fn get_transaction_signatures(
+        &self,
+        cluster: Cluster,
+        address: String,
+        skip: u64,
+        limit: i64,
+    ) -> Result<Vec<TransactionSignature>, AppError>;
+
+    // The latest stored signature of the address, a sync continues from it
+    async fn get_latest_signature(
+        &self,
+        cluster: Cluster,
+        address: String,
+    ) -> Result<Option<String>, AppError>;
+
+    // The oldest stored signature of the address, where a deeper history continues from
+    async fn get_oldest_signature(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Option<String>, AppError>;
+
+    // The given signatures that are stored for the address
+    async fn get_stored_signatures(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        signatures: &[String],
+    ) -> Result<HashSet<String>, AppError>;
+
+    async fn get_signatures_count(&self, cluster: Cluster, address: &str) -> Result<u64, AppError>;
+
+    // The stored signatures of the cluster that still have to be finalized, the oldest first
+    async fn get_unfinalized_signatures(
+        &self,
+        cluster: Cluster,
+        limit: i64,
+    ) -> Result<Vec<UnfinalizedSignature>, AppError>;
+
+    // Mark the signatures as finalized for every address that they belong to
+    // along with the slot they were finalized in
+    async fn finalize_signatures(
+        &self,
+        cluster: Cluster,
+        finalized: &[(String, i64)],
+    ) -> Result<(), AppError>;
+
+    // Delete the signatures that were dropped on a fork along with their transactions
+    // and dead letters, for every address that they belonged to
+    async fn remove_signatures(
+        &self,
+        cluster: Cluster,
+        signatures: &[String],
+    ) -> Result<(), AppError>;
+}
+
+// The transaction bodies, stored once per signature and linked to every address they belong to
+#[async_trait]
+pub trait TransactionStore: Send + Sync {
+    // A transaction counts as inserted when it is newly linked to the address
+    // and as updated when its stored body has changed
+    async fn upsert_transactions(
+        &self,
+        address: &str,
+        txns: &[Transaction],
+    ) -> Result<UpsertCounts, AppError>;
+
+    // A page of the transactions of the address, newest first
+    async fn get_transactions(
+        &self,
+        cluster: Cluster,
+        address: String,
+        skip: u64,
+        limit: i64,
+    ) -> Result<Vec<Transaction>, AppError>;
 
-pub mod accounts;
-pub mod transactions;
+    // The transaction of the signature when it belongs to the address
+    async fn get_transaction(
+        &self,
+        cluster: Cluster,
+        address: String,
+        signature: String,
+    ) -> Result<Option<Transaction>, AppError>;
 
-pub async fn init() -> Result<Database, AppError> {
-    // Get the Mongo URI and DB name from the env
-    let uri = std::env::var("MONGO_URI").expect("MONGO_URI env variable is mising");
-    let db = std::env::var("MONGO_DB").expect("MONGO_DB env variable is mising");
+    async fn get_transactions_count(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<u64, AppError>;
+
+    // The stored signatures of the address without a stored transaction, newest first
+    // The ones that are already retried as dead letters are left out
+    async fn get_signatures_without_transactions(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Vec<String>, AppError>;
+}
+
+// The persistent queue of the indexing jobs
+#[async_trait]
+pub trait JobStore: Send + Sync {
+    // Add the job to the queue unless the address already has one
+    // A queued job of the address is bumped to the priority of the new one when that is higher
+    async fn enqueue_job(&self, job: &IndexingJob) -> Result<IndexingJob, AppError>;
+
+    // Take the pending job with the highest priority, the oldest one first among equals
+    async fn claim_next_job(&self) -> Result<Option<IndexingJob>, AppError>;
+
+    // The pending jobs in the order they will be picked up
+    async fn get_pending_jobs(&self) -> Result<Vec<IndexingJob>, AppError>;
+
+    async fn delete_job(&self, id: &str) -> Result<(), AppError>;
+
+    // Drop the job from the queue when it hasn't been picked up by a worker yet
+    async fn delete_pending_job(&self, id: &str) -> Result<bool, AppError>;
+
+    // Put the jobs that were running when the backend stopped back in the queue
+    async fn reset_running_jobs(&self) -> Result<u64, AppError>;
+}
+
+// The signatures whose transaction could not be fetched, retried in the background
+#[async_trait]
+pub trait DeadLetterStore: Send + Sync {
+    // A signature that is already recorded counts one more failed attempt
+    // and keeps its retry schedule
+    async fn record_dead_letter(&self, dead_letter: &DeadLetter) -> Result<(), AppError>;
+
+    // The pending dead letters of the cluster whose next retry is due, the most overdue first
+    async fn get_due_dead_letters(
+        &self,
+        cluster: Cluster,
+        limit: i64,
+    ) -> Result<Vec<DeadLetter>, AppError>;
+
+    // Count another failed retry of the dead letter along with its next schedule or final status
+    async fn update_dead_letter(
+        &self,
+        id: &str,
+        status: DeadLetterStatus,
+        error: &IndexingError,
+        next_attempt_at: Timestamp,
+    ) -> Result<(), AppError>;
+
+    async fn delete_dead_letter(&self, id: &str) -> Result<(), AppError>;
+}
+
+// When the indexed addresses are refreshed on their own
+#[async_trait]
+pub trait ScheduleStore: Send + Sync {
+    async fn get_refresh_schedule(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Option<RefreshSchedule>, AppError>;
+
+    async fn save_refresh_schedule(&self, schedule: &RefreshSchedule) -> Result<(), AppError>;
+
+    // The schedules of the given clusters whose refresh is due, the most overdue first
+    async fn get_due_refresh_schedules(
+        &self,
+        clusters: &[Cluster],
+        limit: i64,
+    ) -> Result<Vec<RefreshSchedule>, AppError>;
+
+    async fn reschedule_refresh(
+        &self,
+        id: &str,
+        next_refresh_at: Timestamp,
+    ) -> Result<(), AppError>;
+}
+
+// The addresses that are ingested live
+#[async_trait]
+pub trait WatchStore: Send + Sync {
+    async fn save_watched_address(&self, watch: &WatchedAddress) -> Result<(), AppError>;
+
+    // Stop watching the address, returns whether it was watched
+    async fn delete_watched_address(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<bool, AppError>;
+
+    // The watched addresses of the given clusters
+    async fn get_watched_addresses(
+        &self,
+        clusters: &[Cluster],
+    ) -> Result<Vec<WatchedAddress>, AppError>;
+}
+
+// Everything the indexer stores, implemented by every storage backend
+pub trait Storage:
+    AccountStore
+    + IndexingStateStore
+    + SignatureStore
+    + TransactionStore
+    + JobStore
+    + DeadLetterStore
+    + ScheduleStore
+    + WatchStore
+{
+}
+
+impl<T> Storage for T where
+    T: AccountStore
+        + IndexingStateStore
+        + SignatureStore
+        + TransactionStore
+        + JobStore
+        + DeadLetterStore
+        + ScheduleStore
+        + WatchStore
+{
+}
 
-    // Setup the Mongo Database
-    let db = Client::with_uri_str(uri).await?.database(&db);
+// Setup the storage backend selected in the config
+pub async fn init(config: &Config) -> Result<Arc<dyn Storage>, AppError> {
+    let storage: Arc<dyn Storage> = match config.storage_backend {
+        StorageBackend::Mongo => {
+            let storage = mongo::MongoStorage::init().await?;
+            if config.migrate_on_startup {
+                storage.migrate(config.legacy_cluster, false).await?;
+            } else {
+                storage.check_schema_version().await?;
+            }
+            storage.sync_indexes().await?;
+            Arc::new(storage)
+        }
+        StorageBackend::Postgres => Arc::new(postgres::PostgresStorage::init().await?),
+        StorageBackend::Sqlite => Arc::new(sqlite::SqliteStorage::init().await?),
+        StorageBackend::Memory => Arc::new(memory::MemoryStorage::default()),
+    };
+    info!(backend = ?config.storage_backend, "Storage ready");
 
-    Ok(db)
+    Ok(storage)
 }
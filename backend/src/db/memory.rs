@@ -0,0 +1,880 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use async_trait::async_trait;
+
+use crate::cluster::Cluster;
+use crate::db::{
+    AccountStore, DeadLetterStore, IndexingStateStore, JobStore, ScheduleStore, SignatureStore,
+    TransactionStore, WatchStore,
+};
+use crate::error::AppError;
+use crate::models::{
+    Account, AddressIndexingState, AddressTransaction, DeadLetter, DeadLetterStatus, FINALIZED,
+    IndexerStats, IndexingBounds, IndexingCheckpoint, IndexingError, IndexingJob, IndexingState,
+    JobStatus, RefreshSchedule, Timestamp, Transaction, TransactionSignature, UnfinalizedSignature,
+    UpdateAccount, UpdateAddressIndexingState, UpsertCounts, Upserted, WatchedAddress, document_id,
+    membership_id,
+};
+
+// The records of every kind keyed by their _id, like the collections of the Mongo storage
+#[derive(Debug, Default)]
+struct Records {
+    accounts: HashMap<String, Account>,
+    indexing_states: HashMap<String, AddressIndexingState>,
+    signatures: HashMap<String, TransactionSignature>,
+    transactions: HashMap<String, Transaction>,
+    memberships: HashMap<String, AddressTransaction>,
+    jobs: HashMap<String, IndexingJob>,
+    dead_letters: HashMap<String, DeadLetter>,
+    schedules: HashMap<String, RefreshSchedule>,
+    watches: HashMap<String, WatchedAddress>,
+}
+
+// The storage kept in the memory of the process
+// Nothing survives a restart, it runs the indexer without a database e.g. in tests and demos
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    records: Mutex<Records>,
+}
+
+impl MemoryStorage {
+    // The lock is never held across an await, so a poisoned one only means a panic mid-write
+    fn records(&self) -> MutexGuard<'_, Records> {
+        self.records.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// Skip and limit a sorted list the way a Mongo find does, a limit of 0 means no limit
+fn page<T>(items: Vec<T>, skip: u64, limit: i64) -> Vec<T> {
+    let items = items.into_iter().skip(skip as usize);
+    match limit.unsigned_abs() {
+        0 => items.collect(),
+        limit => items.take(limit as usize).collect(),
+    }
+}
+
+#[async_trait]
+impl AccountStore for MemoryStorage {
+    async fn get_account(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Option<Account>, AppError> {
+        Ok(self
+            .records()
+            .accounts
+            .get(&document_id(cluster, address))
+            .cloned())
+    }
+
+    async fn insert_account(&self, account: &Account) -> Result<(), AppError> {
+        let mut records = self.records();
+        if records.accounts.contains_key(&account.id) {
+            return Err(AppError::Database(format!(
+                "Account {} already exists",
+                account.id
+            )));
+        }
+        records.accounts.insert(account.id.clone(), account.clone());
+
+        Ok(())
+    }
+
+    async fn update_account(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        account: UpdateAccount,
+    ) -> Result<Account, AppError> {
+        let mut records = self.records();
+        let stored = records
+            .accounts
+            .get_mut(&document_id(cluster, address))
+            .ok_or_else(|| AppError::NotFound("Account Not Found".into()))?;
+
+        stored.status = account.status;
+        stored.lamports = account.lamports;
+        stored.owner = account.owner;
+        stored.executable = account.executable;
+        stored.data_length = account.data_length;
+        stored.rent_epoch = account.rent_epoch;
+        stored.last_updated_at = account.last_updated_at;
+
+        Ok(stored.clone())
+    }
+
+    async fn get_indexer_stats(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<IndexerStats, AppError> {
+        let records = self.records();
+        if !records
+            .accounts
+            .contains_key(&document_id(cluster, address))
+        {
+            return Err(AppError::NotFound("Account Not Found".into()));
+        }
+
+        let of_address = |sign: &&TransactionSignature| {
+            sign.cluster == cluster && sign.account_address == address
+        };
+        let finalized = |id: &str| {
+            records
+                .signatures
+                .get(id)
+                .is_some_and(|sign| sign.confirmation_status == FINALIZED)
+        };
+        let dead_letters = |status: DeadLetterStatus| {
+            records
+                .dead_letters
+                .values()
+                .filter(|letter| {
+                    letter.cluster == cluster
+                        && letter.account_address == address
+                        && letter.status == status
+                })
+                .count() as i64
+        };
+
+        Ok(IndexerStats {
+            account_exists: true,
+            signatures: records
+                .signatures
+                .values()
+                .filter(of_address)
+                .filter(|sign| sign.confirmation_status == FINALIZED)
+                .count() as i64,
+            transactions: records
+                .memberships
+                .values()
+                .filter(|membership| {
+                    membership.cluster == cluster
+                        && membership.account_address == address
+                        && finalized(&membership.id)
+                })
+                .count() as i64,
+            unfinalized_signatures: records
+                .signatures
+                .values()
+                .filter(of_address)
+                .filter(|sign| sign.confirmation_status != FINALIZED)
+                .count() as i64,
+            pending_dead_letters: dead_letters(DeadLetterStatus::Pending),
+            failed_dead_letters: dead_letters(DeadLetterStatus::Failed),
+        })
+    }
+}
+
+#[async_trait]
+impl IndexingStateStore for MemoryStorage {
+    async fn get_address_indexing_state(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<AddressIndexingState, AppError> {
+        self.records()
+            .indexing_states
+            .get(&document_id(cluster, address))
+            .cloned()
+            .ok_or_else(|| AppError::NotFound("Address not found".to_string()))
+    }
+
+    async fn insert_address_indexing_state(
+        &self,
+        record: AddressIndexingState,
+    ) -> Result<bool, AppError> {
+        let mut records = self.records();
+        if records.indexing_states.contains_key(&record.id) {
+            return Ok(false);
+        }
+        records.indexing_states.insert(record.id.clone(), record);
+
+        Ok(true)
+    }
+
+    async fn update_address_indexing_state(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        update: UpdateAddressIndexingState,
+    ) -> Result<(), AppError> {
+        let mut records = self.records();
+        let record = records
+            .indexing_states
+            .get_mut(&document_id(cluster, address))
+            .ok_or_else(|| AppError::NotFound("Address not found".to_string()))?;
+        if !record.state.can_transition_to(update.state) {
+            return Err(AppError::BadRequest(format!(
+                "Address can't move from {} to {}",
+                record.state, update.state
+            )));
+        }
+
+        if update.state.is_running() {
+            record.started_at = Some(update.updated_at);
+            record.finished_at = None;
+        } else if update.state != IndexingState::Queued {
+            record.finished_at = Some(update.updated_at);
+        }
+        record.state = update.state;
+        record.checkpoint = update.checkpoint;
+        record.last_error = update.last_error;
+        record.updated_at = update.updated_at;
+
+        Ok(())
+    }
+
+    async fn get_unfinished_address_states(&self) -> Result<Vec<AddressIndexingState>, AppError> {
+        Ok(self
+            .records()
+            .indexing_states
+            .values()
+            .filter(|record| {
+                matches!(
+                    record.state,
+                    IndexingState::Queued | IndexingState::Indexing | IndexingState::Syncing
+                )
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn get_idle_addresses(&self, cluster: Cluster) -> Result<Vec<String>, AppError> {
+        Ok(self
+            .records()
+            .indexing_states
+            .values()
+            .filter(|record| record.cluster == cluster && record.state == IndexingState::Idle)
+            .map(|record| record.address.clone())
+            .collect())
+    }
+
+    async fn save_indexing_checkpoint(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        checkpoint: &IndexingCheckpoint,
+        updated_at: Timestamp,
+    ) -> Result<(), AppError> {
+        let mut records = self.records();
+        let record = records
+            .indexing_states
+            .get_mut(&document_id(cluster, address))
+            .ok_or_else(|| AppError::NotFound("Address Not Found".into()))?;
+        record.checkpoint = Some(checkpoint.clone());
+        record.updated_at = updated_at;
+
+        Ok(())
+    }
+
+    async fn save_indexing_bounds(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        bounds: &IndexingBounds,
+        updated_at: Timestamp,
+    ) -> Result<(), AppError> {
+        let mut records = self.records();
+        let record = records
+            .indexing_states
+            .get_mut(&document_id(cluster, address))
+            .ok_or_else(|| AppError::NotFound("Address Not Found".into()))?;
+        record.bounds = bounds.clone();
+        record.updated_at = updated_at;
+
+        Ok(())
+    }
+}
+
+impl Records {
+    // The stored signatures of the address in no particular order
+    fn signatures_of<'a>(
+        &'a self,
+        cluster: Cluster,
+        address: &'a str,
+    ) -> impl Iterator<Item = &'a TransactionSignature> {
+        self.signatures
+            .values()
+            .filter(move |sign| sign.cluster == cluster && sign.account_address == address)
+    }
+
+    // The transactions linked to the address in no particular order
+    fn memberships_of<'a>(
+        &'a self,
+        cluster: Cluster,
+        address: &'a str,
+    ) -> impl Iterator<Item = &'a AddressTransaction> {
+        self.memberships.values().filter(move |membership| {
+            membership.cluster == cluster && membership.account_address == address
+        })
+    }
+}
+
+#[async_trait]
+impl SignatureStore for MemoryStorage {
+    async fn upsert_transaction_signatures(
+        &self,
+        signatures: &[TransactionSignature],
+    ) -> Result<UpsertCounts, AppError> {
+        let mut records = self.records();
+        let mut counts = UpsertCounts::default();
+        for signature in signatures {
+            match records.signatures.get_mut(&signature.id) {
+                Some(stored)
+                    if stored.slot == signature.slot
+                        && stored.block_time == signature.block_time
+                        && stored.confirmation_status == signature.confirmation_status =>
+                {
+                    counts.skipped += 1;
+                }
+                Some(stored) => {
+                    stored.slot = signature.slot;
+                    stored.block_time = signature.block_time;
+                    stored.confirmation_status = signature.confirmation_status.clone();
+                    counts.updated += 1;
+                }
+                None => {
+                    records
+                        .signatures
+                        .insert(signature.id.clone(), signature.clone());
+                    counts.inserted += 1;
+                }
+            }
+        }
+
+        Ok(counts)
+    }
+
+    async fn get_transaction_signatures(
+        &self,
+        cluster: Cluster,
+        address: String,
+        skip: u64,
+        limit: i64,
+    ) -> Result<Vec<TransactionSignature>, AppError> {
+        let records = self.records();
+        let mut signatures: Vec<TransactionSignature> =
+            records.signatures_of(cluster, &address).cloned().collect();
+        signatures.sort_by_key(|record| Reverse(record.slot));
+
+        Ok(page(signatures, skip, limit))
+    }
+
+    async fn get_latest_signature(
+        &self,
+        cluster: Cluster,
+        address: String,
+    ) -> Result<Option<String>, AppError> {
+        Ok(self
+            .records()
+            .signatures_of(cluster, &address)
+            .max_by_key(|sign| sign.slot)
+            .map(|sign| sign.signature.clone()))
+    }
+
+    async fn get_oldest_signature(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Option<String>, AppError> {
+        Ok(self
+            .records()
+            .signatures_of(cluster, address)
+            .min_by_key(|sign| sign.slot)
+            .map(|sign| sign.signature.clone()))
+    }
+
+    async fn get_stored_signatures(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        signatures: &[String],
+    ) -> Result<HashSet<String>, AppError> {
+        let records = self.records();
+
+        Ok(signatures
+            .iter()
+            .filter(|signature| {
+                records
+                    .signatures
+                    .contains_key(&membership_id(cluster, address, signature))
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn get_signatures_count(&self, cluster: Cluster, address: &str) -> Result<u64, AppError> {
+        Ok(self.records().signatures_of(cluster, address).count() as u64)
+    }
+
+    async fn get_unfinalized_signatures(
+        &self,
+        cluster: Cluster,
+        limit: i64,
+    ) -> Result<Vec<UnfinalizedSignature>, AppError> {
+        let records = self.records();
+        let mut signatures: Vec<UnfinalizedSignature> = records
+            .signatures
+            .values()
+            .filter(|sign| sign.cluster == cluster && sign.confirmation_status != FINALIZED)
+            .map(|sign| UnfinalizedSignature {
+                signature: sign.signature.clone(),
+                indexed_at: sign.indexed_at,
+            })
+            .collect();
+        signatures.sort_by_key(|sign| sign.indexed_at);
+
+        Ok(page(signatures, 0, limit))
+    }
+
+    async fn finalize_signatures(
+        &self,
+        cluster: Cluster,
+        finalized: &[(String, i64)],
+    ) -> Result<(), AppError> {
+        let mut records = self.records();
+        let slots: HashMap<&str, i64> = finalized
+            .iter()
+            .map(|(signature, slot)| (signature.as_str(), *slot))
+            .collect();
+
+        for sign in records.signatures.values_mut() {
+            if sign.cluster == cluster
+                && let Some(&slot) = slots.get(sign.signature.as_str())
+            {
+                sign.confirmation_status = FINALIZED.to_string();
+                sign.slot = slot;
+            }
+        }
+        for membership in records.memberships.values_mut() {
+            if membership.cluster == cluster
+                && let Some(&slot) = slots.get(membership.signature.as_str())
+            {
+                membership.slot = slot;
+            }
+        }
+        for (signature, slot) in finalized {
+            if let Some(txn) = records
+                .transactions
+                .get_mut(&document_id(cluster, signature))
+            {
+                txn.slot = *slot;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn remove_signatures(
+        &self,
+        cluster: Cluster,
+        signatures: &[String],
+    ) -> Result<(), AppError> {
+        let mut records = self.records();
+        let dropped: HashSet<&str> = signatures.iter().map(String::as_str).collect();
+
+        records.signatures.retain(|_, sign| {
+            sign.cluster != cluster || !dropped.contains(sign.signature.as_str())
+        });
+        records.memberships.retain(|_, membership| {
+            membership.cluster != cluster || !dropped.contains(membership.signature.as_str())
+        });
+        records.dead_letters.retain(|_, letter| {
+            letter.cluster != cluster || !dropped.contains(letter.signature.as_str())
+        });
+        for signature in signatures {
+            records
+                .transactions
+                .remove(&document_id(cluster, signature));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TransactionStore for MemoryStorage {
+    async fn upsert_transactions(
+        &self,
+        address: &str,
+        txns: &[Transaction],
+    ) -> Result<UpsertCounts, AppError> {
+        let mut records = self.records();
+        let mut counts = UpsertCounts::default();
+        for txn in txns {
+            let body = match records.transactions.get_mut(&txn.id) {
+                Some(stored)
+                    if stored.slot == txn.slot
+                        && stored.block_time == txn.block_time
+                        && stored.version == txn.version
+                        && stored.account_keys == txn.account_keys
+                        && stored.loaded_addresses == txn.loaded_addresses
+                        && stored.transaction == txn.transaction =>
+                {
+                    Upserted::Skipped
+                }
+                Some(stored) => {
+                    stored.slot = txn.slot;
+                    stored.block_time = txn.block_time;
+                    stored.version = txn.version.clone();
+                    stored.account_keys = txn.account_keys.clone();
+                    stored.loaded_addresses = txn.loaded_addresses.clone();
+                    stored.transaction = txn.transaction.clone();
+                    Upserted::Updated
+                }
+                None => {
+                    records.transactions.insert(txn.id.clone(), txn.clone());
+                    Upserted::Inserted
+                }
+            };
+
+            let id = membership_id(txn.cluster, address, &txn.signature);
+            let link = match records.memberships.get_mut(&id) {
+                Some(membership)
+                    if membership.slot == txn.slot && membership.block_time == txn.block_time =>
+                {
+                    Upserted::Skipped
+                }
+                Some(membership) => {
+                    membership.slot = txn.slot;
+                    membership.block_time = txn.block_time;
+                    Upserted::Updated
+                }
+                None => {
+                    let membership = AddressTransaction {
+                        id: id.clone(),
+                        cluster: txn.cluster,
+                        account_address: address.to_string(),
+                        signature: txn.signature.clone(),
+                        slot: txn.slot,
+                        block_time: txn.block_time,
+                        indexed_at: txn.indexed_at,
+                    };
+                    records.memberships.insert(id, membership);
+                    Upserted::Inserted
+                }
+            };
+            counts.add_transaction(body, link);
+        }
+
+        Ok(counts)
+    }
+
+    async fn get_transactions(
+        &self,
+        cluster: Cluster,
+        address: String,
+        skip: u64,
+        limit: i64,
+    ) -> Result<Vec<Transaction>, AppError> {
+        let records = self.records();
+        let mut memberships: Vec<&AddressTransaction> =
+            records.memberships_of(cluster, &address).collect();
+        memberships.sort_by_key(|record| Reverse(record.slot));
+
+        Ok(page(memberships, skip, limit)
+            .into_iter()
+            .filter_map(|membership| {
+                records
+                    .transactions
+                    .get(&document_id(cluster, &membership.signature))
+                    .cloned()
+            })
+            .collect())
+    }
+
+    async fn get_transaction(
+        &self,
+        cluster: Cluster,
+        address: String,
+        signature: String,
+    ) -> Result<Option<Transaction>, AppError> {
+        let records = self.records();
+        // The transaction has to belong to the address
+        if !records
+            .memberships
+            .contains_key(&membership_id(cluster, &address, &signature))
+        {
+            return Ok(None);
+        }
+
+        Ok(records
+            .transactions
+            .get(&document_id(cluster, &signature))
+            .cloned())
+    }
+
+    async fn get_transactions_count(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<u64, AppError> {
+        Ok(self.records().memberships_of(cluster, address).count() as u64)
+    }
+
+    async fn get_signatures_without_transactions(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Vec<String>, AppError> {
+        let records = self.records();
+        // A membership and a dead letter share their _id with the signature of the address
+        let mut signatures: Vec<&TransactionSignature> = records
+            .signatures_of(cluster, address)
+            .filter(|sign| {
+                !records.memberships.contains_key(&sign.id)
+                    && !records.dead_letters.contains_key(&sign.id)
+            })
+            .collect();
+        signatures.sort_by_key(|record| Reverse(record.slot));
+
+        Ok(signatures
+            .into_iter()
+            .map(|sign| sign.signature.clone())
+            .collect())
+    }
+}
+
+impl Records {
+    // The pending jobs in the order they will be picked up
+    fn pending_jobs(&self) -> Vec<&IndexingJob> {
+        let mut jobs: Vec<&IndexingJob> = self
+            .jobs
+            .values()
+            .filter(|job| job.status == JobStatus::Pending)
+            .collect();
+        jobs.sort_by(|a, b| {
+            b.priority
+                .cmp(&a.priority)
+                .then(a.enqueued_at.cmp(&b.enqueued_at))
+        });
+        jobs
+    }
+}
+
+#[async_trait]
+impl JobStore for MemoryStorage {
+    async fn enqueue_job(&self, job: &IndexingJob) -> Result<IndexingJob, AppError> {
+        let mut records = self.records();
+        let stored = records
+            .jobs
+            .entry(job.id.clone())
+            .or_insert_with(|| job.clone());
+        stored.priority = stored.priority.max(job.priority);
+
+        Ok(stored.clone())
+    }
+
+    async fn claim_next_job(&self) -> Result<Option<IndexingJob>, AppError> {
+        let mut records = self.records();
+        let Some(id) = records.pending_jobs().first().map(|job| job.id.clone()) else {
+            return Ok(None);
+        };
+
+        let job = records.jobs.get_mut(&id).map(|job| {
+            job.status = JobStatus::Running;
+            job.started_at = Some(Timestamp::now());
+            job.clone()
+        });
+
+        Ok(job)
+    }
+
+    async fn get_pending_jobs(&self) -> Result<Vec<IndexingJob>, AppError> {
+        Ok(self.records().pending_jobs().into_iter().cloned().collect())
+    }
+
+    async fn delete_job(&self, id: &str) -> Result<(), AppError> {
+        self.records().jobs.remove(id);
+
+        Ok(())
+    }
+
+    async fn delete_pending_job(&self, id: &str) -> Result<bool, AppError> {
+        let mut records = self.records();
+        if records
+            .jobs
+            .get(id)
+            .is_some_and(|job| job.status == JobStatus::Pending)
+        {
+            records.jobs.remove(id);
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
+    async fn reset_running_jobs(&self) -> Result<u64, AppError> {
+        let mut reset = 0;
+        for job in self.records().jobs.values_mut() {
+            if job.status == JobStatus::Running {
+                job.status = JobStatus::Pending;
+                job.started_at = None;
+                reset += 1;
+            }
+        }
+
+        Ok(reset)
+    }
+}
+
+#[async_trait]
+impl DeadLetterStore for MemoryStorage {
+    async fn record_dead_letter(&self, dead_letter: &DeadLetter) -> Result<(), AppError> {
+        let mut records = self.records();
+        match records.dead_letters.get_mut(&dead_letter.id) {
+            Some(stored) => {
+                stored.attempts += dead_letter.attempts;
+                stored.error = dead_letter.error.clone();
+                stored.updated_at = dead_letter.updated_at;
+            }
+            None => {
+                records
+                    .dead_letters
+                    .insert(dead_letter.id.clone(), dead_letter.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_due_dead_letters(
+        &self,
+        cluster: Cluster,
+        limit: i64,
+    ) -> Result<Vec<DeadLetter>, AppError> {
+        let now = Timestamp::now();
+        let mut dead_letters: Vec<DeadLetter> = self
+            .records()
+            .dead_letters
+            .values()
+            .filter(|letter| {
+                letter.cluster == cluster
+                    && letter.status == DeadLetterStatus::Pending
+                    && letter.next_attempt_at <= now
+            })
+            .cloned()
+            .collect();
+        dead_letters.sort_by_key(|letter| letter.next_attempt_at);
+
+        Ok(page(dead_letters, 0, limit))
+    }
+
+    async fn update_dead_letter(
+        &self,
+        id: &str,
+        status: DeadLetterStatus,
+        error: &IndexingError,
+        next_attempt_at: Timestamp,
+    ) -> Result<(), AppError> {
+        if let Some(letter) = self.records().dead_letters.get_mut(id) {
+            letter.attempts += 1;
+            letter.status = status;
+            letter.error = error.clone();
+            letter.next_attempt_at = next_attempt_at;
+            letter.updated_at = Timestamp::now();
+        }
+
+        Ok(())
+    }
+
+    async fn delete_dead_letter(&self, id: &str) -> Result<(), AppError> {
+        self.records().dead_letters.remove(id);
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ScheduleStore for MemoryStorage {
+    async fn get_refresh_schedule(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Option<RefreshSchedule>, AppError> {
+        Ok(self
+            .records()
+            .schedules
+            .get(&document_id(cluster, address))
+            .cloned())
+    }
+
+    async fn save_refresh_schedule(&self, schedule: &RefreshSchedule) -> Result<(), AppError> {
+        self.records()
+            .schedules
+            .insert(schedule.id.clone(), schedule.clone());
+
+        Ok(())
+    }
+
+    async fn get_due_refresh_schedules(
+        &self,
+        clusters: &[Cluster],
+        limit: i64,
+    ) -> Result<Vec<RefreshSchedule>, AppError> {
+        let now = Timestamp::now();
+        let mut schedules: Vec<RefreshSchedule> = self
+            .records()
+            .schedules
+            .values()
+            .filter(|schedule| {
+                clusters.contains(&schedule.cluster) && schedule.next_refresh_at <= now
+            })
+            .cloned()
+            .collect();
+        schedules.sort_by_key(|schedule| schedule.next_refresh_at);
+
+        Ok(page(schedules, 0, limit))
+    }
+
+    async fn reschedule_refresh(
+        &self,
+        id: &str,
+        next_refresh_at: Timestamp,
+    ) -> Result<(), AppError> {
+        if let Some(schedule) = self.records().schedules.get_mut(id) {
+            schedule.next_refresh_at = next_refresh_at;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl WatchStore for MemoryStorage {
+    async fn save_watched_address(&self, watch: &WatchedAddress) -> Result<(), AppError> {
+        self.records()
+            .watches
+            .insert(watch.id.clone(), watch.clone());
+
+        Ok(())
+    }
+
+    async fn delete_watched_address(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<bool, AppError> {
+        Ok(self
+            .records()
+            .watches
+            .remove(&document_id(cluster, address))
+            .is_some())
+    }
+
+    async fn get_watched_addresses(
+        &self,
+        clusters: &[Cluster],
+    ) -> Result<Vec<WatchedAddress>, AppError> {
+        Ok(self
+            .records()
+            .watches
+            .values()
+            .filter(|watch| clusters.contains(&watch.cluster))
+            .cloned()
+            .collect())
+    }
+}
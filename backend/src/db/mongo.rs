@@ -0,0 +1,393 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use mongodb::{
+    Client, Database,
+    bson::{Bson, DateTime as BsonDateTime},
+};
+
+use crate::cluster::Cluster;
+use crate::db::{
+    AccountStore, DeadLetterStore, IndexingStateStore, JobStore, ScheduleStore, SignatureStore,
+    TransactionStore, WatchStore,
+};
+use crate::error::AppError;
+use crate::models::{
+    Account, AddressIndexingState, DeadLetter, DeadLetterStatus, IndexerStats, IndexingBounds,
+    IndexingCheckpoint, IndexingError, IndexingJob, RefreshSchedule, Timestamp, Transaction,
+    TransactionSignature, UnfinalizedSignature, UpdateAccount, UpdateAddressIndexingState,
+    UpsertCounts, WatchedAddress,
+};
+
+mod accounts;
+mod dead_letters;
+pub mod indexes;
+mod jobs;
+pub mod migrations;
+mod schedules;
+mod transactions;
+mod watches;
+
+// Timestamps are stored as BSON datetimes so they can be compared and sorted in queries
+impl From<Timestamp> for Bson {
+    fn from(time: Timestamp) -> Self {
+        Bson::DateTime(BsonDateTime::from_millis(time.timestamp_millis()))
+    }
+}
+
+// The storage backed by a MongoDB database, one collection per kind of record
+#[derive(Debug, Clone)]
+pub struct MongoStorage {
+    db: Database,
+}
+
+impl MongoStorage {
+    pub async fn init() -> Result<Self, AppError> {
+        // Get the Mongo URI and DB name from the env
+        let uri = std::env::var("MONGO_URI").expect("MONGO_URI env variable is mising");
+        let db = std::env::var("MONGO_DB").expect("MONGO_DB env variable is mising");
+
+        // Setup the Mongo Database
+        let db = Client::with_uri_str(uri).await?.database(&db);
+
+        Ok(MongoStorage { db })
+    }
+
+    // Make sure the indexes that the queries rely on exist
+    // Kept apart from init so a dry run of the migrations leaves the database untouched
+    pub async fn sync_indexes(&self) -> Result<(), AppError> {
+        indexes::sync_indexes(&self.db).await?;
+        Ok(())
+    }
+
+    // Bring the stored documents up to the latest schema version
+    // The documents stored before the cluster scoping are moved to the legacy cluster
+    pub async fn migrate(
+        &self,
+        legacy_cluster: Cluster,
+        dry_run: bool,
+    ) -> Result<migrations::MigrationReport, AppError> {
+        migrations::run_migrations(&self.db, legacy_cluster, dry_run).await
+    }
+
+    // Fail when the stored documents are newer than this build
+    pub async fn check_schema_version(&self) -> Result<(), AppError> {
+        migrations::check_schema_version(&self.db).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AccountStore for MongoStorage {
+    async fn get_account(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Option<Account>, AppError> {
+        accounts::get_account(&self.db, cluster, address).await
+    }
+
+    async fn insert_account(&self, account: &Account) -> Result<(), AppError> {
+        accounts::insert_account(&self.db, account).await
+    }
+
+    async fn update_account(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        account: UpdateAccount,
+    ) -> Result<Account, AppError> {
+        accounts::update_account(&self.db, cluster, address, account).await
+    }
+
+    async fn get_indexer_stats(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<IndexerStats, AppError> {
+        accounts::get_indexer_stats(&self.db, cluster, address).await
+    }
+}
+
+#[async_trait]
+impl IndexingStateStore for MongoStorage {
+    async fn get_address_indexing_state(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<AddressIndexingState, AppError> {
+        accounts::get_address_indexing_state(&self.db, cluster, address).await
+    }
+
+    async fn insert_address_indexing_state(
+        &self,
+        record: AddressIndexingState,
+    ) -> Result<bool, AppError> {
+        accounts::insert_address_indexing_state(&self.db, record).await
+    }
+
+    async fn update_address_indexing_state(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        update: UpdateAddressIndexingState,
+    ) -> Result<(), AppError> {
+        accounts::update_address_indexing_state(&self.db, cluster, address, update).await
+    }
+
+    async fn get_unfinished_address_states(&self) -> Result<Vec<AddressIndexingState>, AppError> {
+        accounts::get_unfinished_address_states(&self.db).await
+    }
+
+    async fn get_idle_addresses(&self, cluster: Cluster) -> Result<Vec<String>, AppError> {
+        accounts::get_idle_addresses(&self.db, cluster).await
+    }
+
+    async fn save_indexing_checkpoint(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        checkpoint: &IndexingCheckpoint,
+        updated_at: Timestamp,
+    ) -> Result<(), AppError> {
+        accounts::save_indexing_checkpoint(&self.db, cluster, address, checkpoint, updated_at).await
+    }
+
+    async fn save_indexing_bounds(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        bounds: &IndexingBounds,
+        updated_at: Timestamp,
+    ) -> Result<(), AppError> {
+        accounts::save_indexing_bounds(&self.db, cluster, address, bounds, updated_at).await
+    }
+}
+
+#[async_trait]
+impl SignatureStore for MongoStorage {
+    async fn upsert_transaction_signatures(
+        &self,
+        signatures: &[TransactionSignature],
+    ) -> Result<UpsertCounts, AppError> {
+        transactions::upsert_transaction_signatures(&self.db, signatures).await
+    }
+
+    async fn get_transaction_signatures(
+        &self,
+        cluster: Cluster,
+        address: String,
+        skip: u64,
+        limit: i64,
+    ) -> Result<Vec<TransactionSignature>, AppError> {
+        transactions::get_transaction_signatures(&self.db, cluster, address, skip, limit).await
+    }
+
+    async fn get_latest_signature(
+        &self,
+        cluster: Cluster,
+        address: String,
+    ) -> Result<Option<String>, AppError> {
+        transactions::get_latest_signature(&self.db, cluster, address).await
+    }
+
+    async fn get_oldest_signature(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Option<String>, AppError> {
+        transactions::get_oldest_signature(&self.db, cluster, address).await
+    }
+
+    async fn get_stored_signatures(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        signatures: &[String],
+    ) -> Result<HashSet<String>, AppError> {
+        transactions::get_stored_signatures(&self.db, cluster, address, signatures).await
+    }
+
+    async fn get_signatures_count(&self, cluster: Cluster, address: &str) -> Result<u64, AppError> {
+        transactions::get_signatures_count(&self.db, cluster, address).await
+    }
+
+    async fn get_unfinalized_signatures(
+        &self,
+        cluster: Cluster,
+        limit: i64,
+    ) -> Result<Vec<UnfinalizedSignature>, AppError> {
+        transactions::get_unfinalized_signatures(&self.db, cluster, limit).await
+    }
+
+    async fn finalize_signatures(
+        &self,
+        cluster: Cluster,
+        finalized: &[(String, i64)],
+    ) -> Result<(), AppError> {
+        transactions::finalize_signatures(&self.db, cluster, finalized).await
+    }
+
+    async fn remove_signatures(
+        &self,
+        cluster: Cluster,
+        signatures: &[String],
+    ) -> Result<(), AppError> {
+        transactions::remove_signatures(&self.db, cluster, signatures).await
+    }
+}
+
+#[async_trait]
+impl TransactionStore for MongoStorage {
+    async fn upsert_transactions(
+        &self,
+        address: &str,
+        txns: &[Transaction],
+    ) -> Result<UpsertCounts, AppError> {
+        transactions::upsert_transactions(&self.db, address, txns).await
+    }
+
+    async fn get_transactions(
+        &self,
+        cluster: Cluster,
+        address: String,
+        skip: u64,
+        limit: i64,
+    ) -> Result<Vec<Transaction>, AppError> {
+        transactions::get_transactions(&self.db, cluster, address, skip, limit).await
+    }
+
+    async fn get_transaction(
+        &self,
+        cluster: Cluster,
+        address: String,
+        signature: String,
+    ) -> Result<Option<Transaction>, AppError> {
+        transactions::get_transaction(&self.db, cluster, address, signature).await
+    }
+
+    async fn get_transactions_count(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<u64, AppError> {
+        transactions::get_transactions_count(&self.db, cluster, address).await
+    }
+
+    async fn get_signatures_without_transactions(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Vec<String>, AppError> {
+        transactions::get_signatures_without_transactions(&self.db, cluster, address).await
+    }
+}
+
+#[async_trait]
+impl JobStore for MongoStorage {
+    async fn enqueue_job(&self, job: &IndexingJob) -> Result<IndexingJob, AppError> {
+        jobs::enqueue_job(&self.db, job).await
+    }
+
+    async fn claim_next_job(&self) -> Result<Option<IndexingJob>, AppError> {
+        jobs::claim_next_job(&self.db).await
+    }
+
+    async fn get_pending_jobs(&self) -> Result<Vec<IndexingJob>, AppError> {
+        jobs::get_pending_jobs(&self.db).await
+    }
+
+    async fn delete_job(&self, id: &str) -> Result<(), AppError> {
+        jobs::delete_job(&self.db, id).await
+    }
+
+    async fn delete_pending_job(&self, id: &str) -> Result<bool, AppError> {
+        jobs::delete_pending_job(&self.db, id).await
+    }
+
+    async fn reset_running_jobs(&self) -> Result<u64, AppError> {
+        jobs::reset_running_jobs(&self.db).await
+    }
+}
+
+#[async_trait]
+impl DeadLetterStore for MongoStorage {
+    async fn record_dead_letter(&self, dead_letter: &DeadLetter) -> Result<(), AppError> {
+        dead_letters::record_dead_letter(&self.db, dead_letter).await
+    }
+
+    async fn get_due_dead_letters(
+        &self,
+        cluster: Cluster,
+        limit: i64,
+    ) -> Result<Vec<DeadLetter>, AppError> {
+        dead_letters::get_due_dead_letters(&self.db, cluster, limit).await
+    }
+
+    async fn update_dead_letter(
+        &self,
+        id: &str,
+        status: DeadLetterStatus,
+        error: &IndexingError,
+        next_attempt_at: Timestamp,
+    ) -> Result<(), AppError> {
+        dead_letters::update_dead_letter(&self.db, id, status, error, next_attempt_at).await
+    }
+
+    async fn delete_dead_letter(&self, id: &str) -> Result<(), AppError> {
+        dead_letters::delete_dead_letter(&self.db, id).await
+    }
+}
+
+#[async_trait]
+impl ScheduleStore for MongoStorage {
+    async fn get_refresh_schedule(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Option<RefreshSchedule>, AppError> {
+        schedules::get_refresh_schedule(&self.db, cluster, address).await
+    }
+
+    async fn save_refresh_schedule(&self, schedule: &RefreshSchedule) -> Result<(), AppError> {
+        schedules::save_refresh_schedule(&self.db, schedule).await
+    }
+
+    async fn get_due_refresh_schedules(
+        &self,
+        clusters: &[Cluster],
+        limit: i64,
+    ) -> Result<Vec<RefreshSchedule>, AppError> {
+        schedules::get_due_refresh_schedules(&self.db, clusters, limit).await
+    }
+
+    async fn reschedule_refresh(
+        &self,
+        id: &str,
+        next_refresh_at: Timestamp,
+    ) -> Result<(), AppError> {
+        schedules::reschedule_refresh(&self.db, id, next_refresh_at).await
+    }
+}
+
+#[async_trait]
+impl WatchStore for MongoStorage {
+    async fn save_watched_address(&self, watch: &WatchedAddress) -> Result<(), AppError> {
+        watches::save_watched_address(&self.db, watch).await
+    }
+
+    async fn delete_watched_address(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<bool, AppError> {
+        watches::delete_watched_address(&self.db, cluster, address).await
+    }
+
+    async fn get_watched_addresses(
+        &self,
+        clusters: &[Cluster],
+    ) -> Result<Vec<WatchedAddress>, AppError> {
+        watches::get_watched_addresses(&self.db, clusters).await
+    }
+}
@@ -0,0 +1,428 @@
+use futures::stream::TryStreamExt;
+use mongodb::{
+    Database,
+    bson::{Bson, Document, doc, from_document, to_bson, to_document},
+    options::{FindOneAndUpdateOptions, ReturnDocument, UpdateOptions},
+};
+use tracing::info;
+
+use crate::cluster::Cluster;
+use crate::db::mongo::dead_letters::DEAD_LETTER_COLLECTION;
+use crate::error::AppError;
+use crate::models::{
+    Account, AddressIndexingState, DeadLetterStatus, FINALIZED, IndexerStats, IndexingBounds,
+    IndexingCheckpoint, IndexingState, Timestamp, UpdateAccount, UpdateAddressIndexingState,
+    document_id,
+};
+
+pub const ADDRESS_INDEXING_STATE: &str = "address_indexing_state";
+pub const ACCOUNTS: &str = "accounts";
+
+pub async fn get_address_indexing_state(
+    db: &Database,
+    cluster: Cluster,
+    address: &str,
+) -> Result<AddressIndexingState, AppError> {
+    db.collection::<AddressIndexingState>(ADDRESS_INDEXING_STATE)
+        .find_one(doc! {"_id": document_id(cluster, address)})
+        .await?
+        .ok_or_else(|| AppError::NotFound("Address not found".to_string()))
+}
+
+// Insert the record only when the address has none yet
+// $setOnInsert leaves an existing record alone, so its state can't be overwritten
+// without going through the transition check of update_address_indexing_state
+pub async fn insert_address_indexing_state(
+    db: &Database,
+    record: AddressIndexingState,
+) -> Result<bool, AppError> {
+    let mut fields = to_document(&record)?;
+    fields.remove("_id");
+    let options = UpdateOptions::builder().upsert(true).build();
+
+    let result = db
+        .collection::<AddressIndexingState>(ADDRESS_INDEXING_STATE)
+        .update_one(doc! {"_id": &record.id}, doc! {"$setOnInsert": fields})
+        .with_options(options)
+        .await?;
+
+    Ok(result.upserted_id.is_some())
+}
+
+// Move the address to the next state of its lifecycle
+// The update only matches when the address is in a state that can move to the next one,
+// so an illegal transition is rejected even when two runs race for the same address
+pub async fn update_address_indexing_state(
+    db: &Database,
+    cluster: Cluster,
+    address: &str,
+    update: UpdateAddressIndexingState,
+) -> Result<(), AppError> {
+    let mut set = doc! {
+        "state": to_bson(&update.state)?,
+        "checkpoint": to_bson(&update.checkpoint)?,
+        "last_error": to_bson(&update.last_error)?,
+        "updated_at": update.updated_at,
+    };
+    if update.state.is_running() {
+        set.insert("started_at", update.updated_at);
+        set.insert("finished_at", Bson::Null);
+    } else if update.state != IndexingState::Queued {
+        set.insert("finished_at", update.updated_at);
+    }
+
+    let sources = IndexingState::sources(update.state)
+        .iter()
+        .map(to_bson)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let updated = db
+        .collection::<AddressIndexingState>(ADDRESS_INDEXING_STATE)
+        .find_one_and_update(
+            doc! {"_id": document_id(cluster, address), "state": {"$in": sources}},
+            doc! {"$set": set},
+        )
+        .await?;
+
+    match updated {
+        Some(record) => {
+            info!(?record);
+            Ok(())
+        }
+        None => {
+            // Tell an illegal transition apart from a missing address
+            let current = get_address_indexing_state(db, cluster, address).await?;
+            Err(AppError::BadRequest(format!(
+                "Address can't move from {} to {}",
+                current.state, update.state
+            )))
+        }
+    }
+}
+
+// The addresses that are queued or in the middle of a run
+pub async fn get_unfinished_address_states(
+    db: &Database,
+) -> Result<Vec<AddressIndexingState>, AppError> {
+    let states = [
+        IndexingState::Queued,
+        IndexingState::Indexing,
+        IndexingState::Syncing,
+    ]
+    .iter()
+    .map(to_bson)
+    .collect::<Result<Vec<_>, _>>()?;
+
+    let records = db
+        .collection::<AddressIndexingState>(ADDRESS_INDEXING_STATE)
+        .find(doc! {"state": {"$in": states}})
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(records)
+}
+
+// The addresses of the cluster that have no run going on
+pub async fn get_idle_addresses(db: &Database, cluster: Cluster) -> Result<Vec<String>, AppError> {
+    let records: Vec<AddressIndexingState> = db
+        .collection::<AddressIndexingState>(ADDRESS_INDEXING_STATE)
+        .find(doc! {
+            "cluster": cluster.as_str(),
+            "state": to_bson(&IndexingState::Idle)?,
+        })
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(records.into_iter().map(|record| record.address).collect())
+}
+
+pub async fn save_indexing_checkpoint(
+    db: &Database,
+    cluster: Cluster,
+    address: &str,
+    checkpoint: &IndexingCheckpoint,
+    updated_at: Timestamp,
+) -> Result<(), AppError> {
+    let updated = db
+        .collection::<AddressIndexingState>(ADDRESS_INDEXING_STATE)
+        .update_one(
+            doc! {"_id": document_id(cluster, address)},
+            doc! {
+                "$set": {
+                    "checkpoint": to_bson(checkpoint)?,
+                    "updated_at": updated_at,
+                }
+            },
+        )
+        .await?;
+
+    if updated.matched_count == 0 {
+        return Err(AppError::NotFound("Address Not Found".into()));
+    }
+
+    Ok(())
+}
+
+// Store how deep the history of the address is indexed
+pub async fn save_indexing_bounds(
+    db: &Database,
+    cluster: Cluster,
+    address: &str,
+    bounds: &IndexingBounds,
+    updated_at: Timestamp,
+) -> Result<(), AppError> {
+    let updated = db
+        .collection::<AddressIndexingState>(ADDRESS_INDEXING_STATE)
+        .update_one(
+            doc! {"_id": document_id(cluster, address)},
+            doc! {
+                "$set": {
+                    "bounds": to_bson(bounds)?,
+                    "updated_at": updated_at,
+                }
+            },
+        )
+        .await?;
+
+    if updated.matched_count == 0 {
+        return Err(AppError::NotFound("Address Not Found".into()));
+    }
+
+    Ok(())
+}
+
+pub async fn get_account(
+    db: &Database,
+    cluster: Cluster,
+    address: &str,
+) -> Result<Option<Account>, AppError> {
+    let account = db
+        .collection::<Account>(ACCOUNTS)
+        .find_one(doc! {"_id": document_id(cluster, address)})
+        .await?;
+
+    Ok(account)
+}
+
+pub async fn insert_account(db: &Database, account: &Account) -> Result<(), AppError> {
+    let inserted = db
+        .collection::<Account>(ACCOUNTS)
+        .insert_one(account)
+        .await?;
+    info!(?inserted);
+
+    Ok(())
+}
+
+pub async fn update_account(
+    db: &Database,
+    cluster: Cluster,
+    address: &str,
+    account: UpdateAccount,
+) -> Result<Account, AppError> {
+    // option to return the document after the update
+    let options = FindOneAndUpdateOptions::builder()
+        .return_document(ReturnDocument::After)
+        .build();
+
+    // Find the account in DB
+    // If it exists update it and return the updated document
+    // Else return Account not found error
+    db.collection::<Account>(ACCOUNTS)
+        .find_one_and_update(
+            doc! {"_id": document_id(cluster, address)},
+            doc! {"$set": {
+                "status": to_bson(&account.status)?,
+                "lamports": account.lamports,
+                "owner": account.owner,
+                "executable": account.executable,
+                "data_length": account.data_length,
+                "rent_epoch": account.rent_epoch,
+                "last_updated_at": account.last_updated_at,
+            }},
+        )
+        .with_options(options)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Account Not Found".into()))
+}
+
+// Count of the dead letters with the status among the ones grouped by status
+fn dead_letter_count(status: DeadLetterStatus) -> Result<Document, AppError> {
+    Ok(doc! {
+        "$sum": {
+            "$map": {
+                "input": {
+                    "$filter": {
+                        "input": "$dead_letters",
+                        "cond": {"$eq": ["$$this._id", to_bson(&status)?]}
+                    }
+                },
+                "in": "$$this.count"
+            }
+        }
+    })
+}
+
+pub async fn get_indexer_stats(
+    db: &Database,
+    cluster: Cluster,
+    address: &str,
+) -> Result<IndexerStats, AppError> {
+    let docs: Vec<Document> = db
+        .collection::<Document>(ACCOUNTS)
+        .aggregate([
+            doc! {
+                "$match": {
+                    "_id": document_id(cluster, address)
+                }
+            },
+            doc! {
+                "$lookup": {
+                    "from": "transaction_signatures",
+                    "localField": "address",
+                    "foreignField": "account_address",
+                    "as": "signatures",
+                    "pipeline": [
+                        {
+                            "$match": {
+                                "cluster": cluster.as_str(),
+                                "confirmation_status": FINALIZED
+                            }
+                        },
+                        {
+                            "$count": "count"
+                        }
+                    ]
+                }
+            },
+            doc! {
+                "$lookup": {
+                    "from": "transaction_signatures",
+                    "localField": "address",
+                    "foreignField": "account_address",
+                    "as": "unfinalized_signatures",
+                    "pipeline": [
+                        {
+                            "$match": {
+                                "cluster": cluster.as_str(),
+                                "confirmation_status": {"$ne": FINALIZED}
+                            }
+                        },
+                        {
+                            "$count": "count"
+                        }
+                    ]
+                }
+            },
+            doc! {
+                "$lookup": {
+                    "from": "address_transactions",
+                    "localField": "address",
+                    "foreignField": "account_address",
+                    "as": "transactions",
+                    "pipeline": [
+                        {
+                            "$match": {
+                                "cluster": cluster.as_str()
+                            }
+                        },
+                        // A membership shares its _id with the signature of the address
+                        {
+                            "$lookup": {
+                                "from": "transaction_signatures",
+                                "localField": "_id",
+                                "foreignField": "_id",
+                                "as": "finalized",
+                                "pipeline": [
+                                    {
+                                        "$match": {
+                                            "confirmation_status": FINALIZED
+                                        }
+                                    }
+                                ]
+                            }
+                        },
+                        {
+                            "$match": {
+                                "finalized": {"$ne": []}
+                            }
+                        },
+                        {
+                            "$count": "count"
+                        }
+                    ]
+                }
+            },
+            doc! {
+                "$lookup": {
+                    "from": DEAD_LETTER_COLLECTION,
+                    "localField": "address",
+                    "foreignField": "account_address",
+                    "as": "dead_letters",
+                    "pipeline": [
+                        {
+                            "$match": {
+                                "cluster": cluster.as_str()
+                            }
+                        },
+                        {
+                            "$group": {
+                                "_id": "$status",
+                                "count": {"$sum": 1}
+                            }
+                        }
+                    ]
+                }
+            },
+            doc! {
+                "$addFields": {
+                    "account_exists": true
+                }
+            },
+            doc! {
+                "$project": {
+                    "_id": 0,
+                    "account_exists": 1,
+                    "signatures": {
+                        "$ifNull": [
+                            {
+                                "$first": "$signatures.count"
+                            },
+                            0
+                        ]
+                    },
+                    "transactions": {
+                        "$ifNull": [
+                            {
+                                "$first": "$transactions.count"
+                            },
+                            0
+                        ]
+                    },
+                    "unfinalized_signatures": {
+                        "$ifNull": [
+                            {
+                                "$first": "$unfinalized_signatures.count"
+                            },
+                            0
+                        ]
+                    },
+                    "pending_dead_letters": dead_letter_count(DeadLetterStatus::Pending)?,
+                    "failed_dead_letters": dead_letter_count(DeadLetterStatus::Failed)?
+                }
+            },
+        ])
+        .await?
+        .try_collect()
+        .await?;
+
+    if let Some(doc) = docs.into_iter().next() {
+        let decoded: IndexerStats = from_document(doc)?;
+        info!(?decoded);
+        Ok(decoded)
+    } else {
+        Err(AppError::NotFound("Account Not Found".into()))
+    }
+}
@@ -0,0 +1,104 @@
+use futures::stream::TryStreamExt;
+use mongodb::{
+    Database,
+    bson::{doc, to_bson},
+    options::{FindOptions, UpdateOptions},
+};
+
+use crate::cluster::Cluster;
+use crate::error::AppError;
+use crate::models::{DeadLetter, DeadLetterStatus, IndexingError, Timestamp};
+
+pub const DEAD_LETTER_COLLECTION: &str = "dead_letters";
+
+// Record a signature whose transaction could not be fetched
+// A signature that is already recorded e.g. by an interrupted run of the same batch
+// counts one more failed attempt and keeps its retry schedule
+pub async fn record_dead_letter(db: &Database, dead_letter: &DeadLetter) -> Result<(), AppError> {
+    let options = UpdateOptions::builder().upsert(true).build();
+
+    db.collection::<DeadLetter>(DEAD_LETTER_COLLECTION)
+        .update_one(
+            doc! {"_id": &dead_letter.id},
+            doc! {
+                "$inc": {"attempts": dead_letter.attempts},
+                "$set": {
+                    "error": to_bson(&dead_letter.error)?,
+                    "updated_at": dead_letter.updated_at,
+                },
+                "$setOnInsert": {
+                    "cluster": to_bson(&dead_letter.cluster)?,
+                    "account_address": &dead_letter.account_address,
+                    "signature": &dead_letter.signature,
+                    "status": to_bson(&dead_letter.status)?,
+                    "next_attempt_at": dead_letter.next_attempt_at,
+                    "created_at": dead_letter.created_at,
+                },
+            },
+        )
+        .with_options(options)
+        .await?;
+
+    Ok(())
+}
+
+// The pending dead letters of the cluster whose next retry is due, the most overdue first
+pub async fn get_due_dead_letters(
+    db: &Database,
+    cluster: Cluster,
+    limit: i64,
+) -> Result<Vec<DeadLetter>, AppError> {
+    let options = FindOptions::builder()
+        .sort(doc! {"next_attempt_at": 1})
+        .limit(limit)
+        .build();
+
+    let dead_letters = db
+        .collection::<DeadLetter>(DEAD_LETTER_COLLECTION)
+        .find(doc! {
+            "cluster": cluster.as_str(),
+            "status": to_bson(&DeadLetterStatus::Pending)?,
+            "next_attempt_at": {"$lte": Timestamp::now()},
+        })
+        .with_options(options)
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(dead_letters)
+}
+
+// Count another failed retry of the dead letter along with its next schedule or final status
+pub async fn update_dead_letter(
+    db: &Database,
+    id: &str,
+    status: DeadLetterStatus,
+    error: &IndexingError,
+    next_attempt_at: Timestamp,
+) -> Result<(), AppError> {
+    db.collection::<DeadLetter>(DEAD_LETTER_COLLECTION)
+        .update_one(
+            doc! {"_id": id},
+            doc! {
+                "$inc": {"attempts": 1},
+                "$set": {
+                    "status": to_bson(&status)?,
+                    "error": to_bson(error)?,
+                    "next_attempt_at": next_attempt_at,
+                    "updated_at": Timestamp::now(),
+                },
+            },
+        )
+        .await?;
+
+    Ok(())
+}
+
+// Drop the dead letter once its transaction is stored
+pub async fn delete_dead_letter(db: &Database, id: &str) -> Result<(), AppError> {
+    db.collection::<DeadLetter>(DEAD_LETTER_COLLECTION)
+        .delete_one(doc! {"_id": id})
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use std::collections::HashSet;
+
+use futures::stream::TryStreamExt;
+use mongodb::{
+    Database, IndexModel,
+    bson::{Document, doc},
+    options::IndexOptions,
+};
+use tracing::{info, warn};
+
+use crate::db::mongo::accounts::ADDRESS_INDEXING_STATE;
+use crate::db::mongo::dead_letters::DEAD_LETTER_COLLECTION;
+use crate::db::mongo::jobs::INDEXING_JOBS;
+use crate::db::mongo::schedules::REFRESH_SCHEDULES;
+use crate::db::mongo::transactions::{
+    ADDRESS_TRANSACTION_COLLECTION, SIGNATURE_COLLECTION, TRANSACTION_COLLECTION,
+};
+use crate::db::mongo::watches::WATCHED_ADDRESSES;
+use crate::error::AppError;
+
+// An index that the queries on a collection rely on
+struct IndexSpec {
+    collection: &'static str,
+    name: &'static str,
+    keys: Document,
+}
+
+impl IndexSpec {
+    fn new(collection: &'static str, name: &'static str, keys: Document) -> Self {
+        IndexSpec {
+            collection,
+            name,
+            keys,
+        }
+    }
+}
+
+// Every index the storage expects, the lookups by _id are served by the default index
+// The address comes first in the indexes of the signatures, memberships and dead letters
+// so that the $lookup stages of the indexer stats, which join on it, can use them too
+fn index_specs() -> Vec<IndexSpec> {
+    vec![
+        // The unfinished runs on startup and the idle addresses of a cluster
+        IndexSpec::new(
+            ADDRESS_INDEXING_STATE,
+            "state_cluster",
+            doc! {"state": 1, "cluster": 1},
+        ),
+        // The pages, counts and latest/oldest signature of an address
+        IndexSpec::new(
+            SIGNATURE_COLLECTION,
+            "account_address_cluster_slot",
+            doc! {"account_address": 1, "cluster": 1, "slot": -1},
+        ),
+        // Finalizing and removing a signature for every address it belongs to
+        IndexSpec::new(
+            SIGNATURE_COLLECTION,
+            "cluster_signature",
+            doc! {"cluster": 1, "signature": 1},
+        ),
+        // The signatures of a cluster that still have to be finalized, the oldest first
+        IndexSpec::new(
+            SIGNATURE_COLLECTION,
+            "cluster_confirmation_status_indexed_at",
+            doc! {"cluster": 1, "confirmation_status": 1, "indexed_at": 1},
+        ),
+        IndexSpec::new(
+            TRANSACTION_COLLECTION,
+            "cluster_signature",
+            doc! {"cluster": 1, "signature": 1},
+        ),
+        // The pages and counts of the transactions of an address
+        IndexSpec::new(
+            ADDRESS_TRANSACTION_COLLECTION,
+            "account_address_cluster_slot",
+            doc! {"account_address": 1, "cluster": 1, "slot": -1},
+        ),
+        IndexSpec::new(
+            ADDRESS_TRANSACTION_COLLECTION,
+            "cluster_signature",
+            doc! {"cluster": 1, "signature": 1},
+        ),
+        // The queue in the order the jobs are picked up
+        IndexSpec::new(
+            INDEXING_JOBS,
+            "status_priority_enqueued_at",
+            doc! {"status": 1, "priority": -1, "enqueued_at": 1},
+        ),
+        // The due retries, the most overdue first
+        IndexSpec::new(
+            DEAD_LETTER_COLLECTION,
+            "cluster_status_next_attempt_at",
+            doc! {"cluster": 1, "status": 1, "next_attempt_at": 1},
+        ),
+        IndexSpec::new(
+            DEAD_LETTER_COLLECTION,
+            "account_address_cluster_status",
+            doc! {"account_address": 1, "cluster": 1, "status": 1},
+        ),
+        IndexSpec::new(
+            DEAD_LETTER_COLLECTION,
+            "cluster_signature",
+            doc! {"cluster": 1, "signature": 1},
+        ),
+        // The due refreshes, the most overdue first
+        IndexSpec::new(
+            REFRESH_SCHEDULES,
+            "next_refresh_at",
+            doc! {"next_refresh_at": 1},
+        ),
+        IndexSpec::new(WATCHED_ADDRESSES, "cluster", doc! {"cluster": 1}),
+    ]
+}
+
+// What applying the index definitions found, as "<collection>.<index name>"
+#[derive(Debug, Default)]
+pub struct IndexReport {
+    // The indexes that were missing and have been created
+    pub created: Vec<String>,
+    // The indexes that exist but aren't defined, left in place for an operator to look into
+    pub extra: Vec<String>,
+}
+
+// Create the missing indexes and report the ones that aren't defined
+// An index is matched by its keys so one created by hand under another name isn't created twice
+pub async fn sync_indexes(db: &Database) -> Result<IndexReport, AppError> {
+    let specs = index_specs();
+    let existing_collections: HashSet<String> =
+        db.list_collection_names().await?.into_iter().collect();
+
+    let mut collections: Vec<&str> = specs.iter().map(|spec| spec.collection).collect();
+    collections.dedup();
+
+    let mut report = IndexReport::default();
+    for collection in collections {
+        let coll = db.collection::<Document>(collection);
+
+        // Listing the indexes of a collection that doesn't exist yet fails
+        let existing: Vec<IndexModel> = if existing_collections.contains(collection) {
+            coll.list_indexes().await?.try_collect().await?
+        } else {
+            Vec::new()
+        };
+
+        let defined: Vec<&IndexSpec> = specs
+            .iter()
+            .filter(|spec| spec.collection == collection)
+            .collect();
+
+        let missing: Vec<IndexModel> = defined
+            .iter()
+            .filter(|spec| !existing.iter().any(|index| index.keys == spec.keys))
+            .map(|spec| {
+                report.created.push(format!("{collection}.{}", spec.name));
+                IndexModel::builder()
+                    .keys(spec.keys.clone())
+                    .options(IndexOptions::builder().name(spec.name.to_string()).build())
+                    .build()
+            })
+            .collect();
+        if !missing.is_empty() {
+            coll.create_indexes(missing).await?;
+        }
+
+        for index in &existing {
+            let name = index
+                .options
+                .as_ref()
+                .and_then(|options| options.name.clone())
+                .unwrap_or_default();
+            if name != "_id_" && !defined.iter().any(|spec| spec.keys == index.keys) {
+                report.extra.push(format!("{collection}.{name}"));
+            }
+        }
+    }
+
+    if report.created.is_empty() {
+        info!("MongoDB indexes are up to date");
+    } else {
+        info!(created = ?report.created, "Created the missing MongoDB indexes");
+    }
+    if !report.extra.is_empty() {
+        warn!(extra = ?report.extra, "Found MongoDB indexes that aren't defined");
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,114 @@
+use futures::stream::TryStreamExt;
+use mongodb::{
+    Database,
+    bson::{doc, to_bson},
+    options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument},
+};
+
+use crate::error::AppError;
+use crate::models::{IndexingJob, JobStatus, Timestamp};
+
+pub const INDEXING_JOBS: &str = "indexing_jobs";
+
+// Add the job to the queue unless the address already has one
+// A queued job of the address is bumped to the priority of the new one when that is higher
+// but keeps its own bounds
+pub async fn enqueue_job(db: &Database, job: &IndexingJob) -> Result<IndexingJob, AppError> {
+    let options = FindOneAndUpdateOptions::builder()
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .build();
+
+    db.collection::<IndexingJob>(INDEXING_JOBS)
+        .find_one_and_update(
+            doc! {"_id": &job.id},
+            doc! {
+                "$max": {"priority": job.priority},
+                "$setOnInsert": {
+                    "cluster": to_bson(&job.cluster)?,
+                    "address": &job.address,
+                    "kind": to_bson(&job.kind)?,
+                    "status": to_bson(&job.status)?,
+                    "enqueued_at": job.enqueued_at,
+                    "started_at": to_bson(&job.started_at)?,
+                    "bounds": to_bson(&job.bounds)?,
+                },
+            },
+        )
+        .with_options(options)
+        .await?
+        .ok_or_else(|| AppError::Database("Job was not enqueued".into()))
+}
+
+// Take the pending job with the highest priority, the oldest one first among equals
+pub async fn claim_next_job(db: &Database) -> Result<Option<IndexingJob>, AppError> {
+    let options = FindOneAndUpdateOptions::builder()
+        .sort(doc! {"priority": -1, "enqueued_at": 1})
+        .return_document(ReturnDocument::After)
+        .build();
+
+    let job = db
+        .collection::<IndexingJob>(INDEXING_JOBS)
+        .find_one_and_update(
+            doc! {"status": to_bson(&JobStatus::Pending)?},
+            doc! {"$set": {
+                "status": to_bson(&JobStatus::Running)?,
+                "started_at": Timestamp::now(),
+            }},
+        )
+        .with_options(options)
+        .await?;
+
+    Ok(job)
+}
+
+// The pending jobs in the order they will be picked up
+pub async fn get_pending_jobs(db: &Database) -> Result<Vec<IndexingJob>, AppError> {
+    let options = FindOptions::builder()
+        .sort(doc! {"priority": -1, "enqueued_at": 1})
+        .build();
+
+    let jobs = db
+        .collection::<IndexingJob>(INDEXING_JOBS)
+        .find(doc! {"status": to_bson(&JobStatus::Pending)?})
+        .with_options(options)
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(jobs)
+}
+
+pub async fn delete_job(db: &Database, id: &str) -> Result<(), AppError> {
+    db.collection::<IndexingJob>(INDEXING_JOBS)
+        .delete_one(doc! {"_id": id})
+        .await?;
+
+    Ok(())
+}
+
+// Drop the job from the queue when it hasn't been picked up by a worker yet
+pub async fn delete_pending_job(db: &Database, id: &str) -> Result<bool, AppError> {
+    let deleted = db
+        .collection::<IndexingJob>(INDEXING_JOBS)
+        .delete_one(doc! {"_id": id, "status": to_bson(&JobStatus::Pending)?})
+        .await?;
+
+    Ok(deleted.deleted_count > 0)
+}
+
+// Put the jobs that were running when the backend stopped back in the queue
+pub async fn reset_running_jobs(db: &Database) -> Result<u64, AppError> {
+    let updated = db
+        .collection::<IndexingJob>(INDEXING_JOBS)
+        .update_many(
+            doc! {"status": to_bson(&JobStatus::Running)?},
+            doc! {"$set": {
+                "status": to_bson(&JobStatus::Pending)?,
+                "started_at": null,
+            }},
+        )
+        .await?;
+
+    Ok(updated.modified_count)
+}
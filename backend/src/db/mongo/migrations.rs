@@ -0,0 +1,483 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use futures::{future::BoxFuture, stream::TryStreamExt};
+use mongodb::{
+    Database,
+    bson::{Document, doc},
+    options::{ReplaceOptions, UpdateOptions},
+};
+use tracing::info;
+
+use crate::cluster::Cluster;
+use crate::db::mongo::accounts::{ACCOUNTS, ADDRESS_INDEXING_STATE};
+use crate::db::mongo::transactions::{
+    ADDRESS_TRANSACTION_COLLECTION, SIGNATURE_COLLECTION, TRANSACTION_COLLECTION,
+};
+use crate::error::AppError;
+use crate::models::{AddressTransaction, Timestamp, document_id, membership_id};
+
+pub const METADATA_COLLECTION: &str = "metadata";
+// The metadata document holding the schema version of the stored documents
+const SCHEMA_ID: &str = "schema";
+
+// What a migration works with
+struct MigrationContext {
+    db: Database,
+    // The cluster that the documents stored before the cluster scoping belong to
+    legacy_cluster: Cluster,
+    // Only count the documents that would change
+    dry_run: bool,
+}
+
+// A data migration that brings the stored documents to its version
+// Every migration only matches the documents it hasn't migrated yet, so an interrupted one
+// picks up where it stopped when it is run again
+struct Migration<C = MigrationContext> {
+    version: i32,
+    name: &'static str,
+    // Returns the number of documents that were (or would be) changed
+    run: for<'a> fn(&'a C) -> BoxFuture<'a, Result<u64, AppError>>,
+}
+
+// Every migration in the order it is applied, a new one takes the next version
+const MIGRATIONS: [Migration; 2] = [
+    Migration {
+        version: 1,
+        name: "scope_documents_to_cluster",
+        run: scope_documents_to_cluster,
+    },
+    Migration {
+        version: 2,
+        name: "link_transactions_to_addresses",
+        run: link_transactions_to_addresses,
+    },
+];
+
+// A migration that was applied, or would be applied in a dry run
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppliedMigration {
+    pub version: i32,
+    pub name: &'static str,
+    pub documents: u64,
+}
+
+#[derive(Debug)]
+pub struct MigrationReport {
+    pub dry_run: bool,
+    // The schema version the stored documents were at
+    pub from_version: i32,
+    // The schema version the stored documents are at now, or would be after a dry run
+    pub to_version: i32,
+    pub migrations: Vec<AppliedMigration>,
+}
+
+// Where the schema version of the stored documents is kept
+// along with the history of the migrations that brought them there
+#[async_trait]
+trait SchemaHistory: Sync {
+    // The schema version of the stored documents, 0 before any migration was applied
+    async fn schema_version(&self) -> Result<i32, AppError>;
+
+    // Move the schema to the version of the migration and keep it in the history
+    async fn record_migration(&self, migration: &AppliedMigration) -> Result<(), AppError>;
+}
+
+#[async_trait]
+impl SchemaHistory for Database {
+    async fn schema_version(&self) -> Result<i32, AppError> {
+        let version = self
+            .collection::<Document>(METADATA_COLLECTION)
+            .find_one(doc! {"_id": SCHEMA_ID})
+            .await?
+            .and_then(|schema| schema.get_i32("version").ok())
+            .unwrap_or(0);
+
+        Ok(version)
+    }
+
+    async fn record_migration(&self, migration: &AppliedMigration) -> Result<(), AppError> {
+        let options = UpdateOptions::builder().upsert(true).build();
+        let now = Timestamp::now();
+
+        self.collection::<Document>(METADATA_COLLECTION)
+            .update_one(
+                doc! {"_id": SCHEMA_ID},
+                doc! {
+                    "$set": {"version": migration.version, "updated_at": now},
+                    "$push": {"applied": {
+                        "version": migration.version,
+                        "name": migration.name,
+                        "documents": migration.documents as i64,
+                        "applied_at": now,
+                    }},
+                },
+            )
+            .with_options(options)
+            .await?;
+
+        Ok(())
+    }
+}
+
+// The schema version of the stored documents
+// A newer build migrated the documents to a shape this one can't read,
+// so it refuses to run on them instead of corrupting them
+pub async fn check_schema_version(db: &Database) -> Result<i32, AppError> {
+    checked_version(db, &MIGRATIONS).await
+}
+
+async fn checked_version<C>(
+    history: &impl SchemaHistory,
+    migrations: &[Migration<C>],
+) -> Result<i32, AppError> {
+    let version = history.schema_version().await?;
+    let latest = migrations.last().map_or(0, |migration| migration.version);
+    if version > latest {
+        return Err(AppError::Internal(format!(
+            "The stored documents are at schema version {version} \
+            but this build only knows up to version {latest}"
+        )));
+    }
+
+    Ok(version)
+}
+
+// Apply the pending migrations in order, recording the version after every one of them
+pub async fn run_migrations(
+    db: &Database,
+    legacy_cluster: Cluster,
+    dry_run: bool,
+) -> Result<MigrationReport, AppError> {
+    let ctx = MigrationContext {
+        db: db.clone(),
+        legacy_cluster,
+        dry_run,
+    };
+    apply_migrations(db, &ctx, &MIGRATIONS, dry_run).await
+}
+
+async fn apply_migrations<C: Sync>(
+    history: &impl SchemaHistory,
+    ctx: &C,
+    migrations: &[Migration<C>],
+    dry_run: bool,
+) -> Result<MigrationReport, AppError> {
+    let from_version = checked_version(history, migrations).await?;
+
+    let mut report = MigrationReport {
+        dry_run,
+        from_version,
+        to_version: from_version,
+        migrations: Vec::new(),
+    };
+    for migration in migrations
+        .iter()
+        .filter(|migration| migration.version > from_version)
+    {
+        let applied = AppliedMigration {
+            version: migration.version,
+            name: migration.name,
+            documents: (migration.run)(ctx).await?,
+        };
+        if dry_run {
+            info!(
+                version = applied.version,
+                migration = applied.name,
+                documents = applied.documents,
+                "Would apply the migration"
+            );
+        } else {
+            history.record_migration(&applied).await?;
+            info!(
+                version = applied.version,
+                migration = applied.name,
+                documents = applied.documents,
+                "Applied the migration"
+            );
+        }
+
+        report.to_version = applied.version;
+        report.migrations.push(applied);
+    }
+
+    if report.migrations.is_empty() {
+        info!(version = from_version, "Stored documents are up to date");
+    }
+
+    Ok(report)
+}
+
+// Before the cluster scoping the documents were keyed by the bare address or signature
+// and had no cluster, they are moved to the _id of their cluster
+fn scope_documents_to_cluster<'a>(
+    ctx: &'a MigrationContext,
+) -> BoxFuture<'a, Result<u64, AppError>> {
+    Box::pin(async move {
+        let mut documents = 0;
+        documents += rekey_legacy_documents(ctx, ACCOUNTS, "address", false).await?;
+        documents += rekey_legacy_documents(ctx, ADDRESS_INDEXING_STATE, "address", false).await?;
+        documents += rekey_legacy_documents(ctx, SIGNATURE_COLLECTION, "signature", true).await?;
+        documents +=
+            rekey_legacy_documents(ctx, TRANSACTION_COLLECTION, "signature", false).await?;
+
+        Ok(documents)
+    })
+}
+
+// Replace every legacy document of the collection with one under its scoped _id
+// The legacy _id is kept in the given field, and the signatures of an address
+// are also scoped by that address
+async fn rekey_legacy_documents(
+    ctx: &MigrationContext,
+    collection: &str,
+    id_field: &str,
+    scoped_by_address: bool,
+) -> Result<u64, AppError> {
+    let coll = ctx.db.collection::<Document>(collection);
+    let legacy = doc! {"cluster": {"$exists": false}};
+    if ctx.dry_run {
+        return Ok(coll.count_documents(legacy).await?);
+    }
+
+    let options = ReplaceOptions::builder().upsert(true).build();
+    let mut cursor = coll.find(legacy).await?;
+    let mut migrated = 0;
+    while let Some(mut document) = cursor.try_next().await? {
+        let legacy_id = document.get_str("_id")?.to_string();
+        let id = if scoped_by_address {
+            membership_id(
+                ctx.legacy_cluster,
+                document.get_str("account_address")?,
+                &legacy_id,
+            )
+        } else {
+            document_id(ctx.legacy_cluster, &legacy_id)
+        };
+
+        document.insert("_id", &id);
+        document.insert(id_field, &legacy_id);
+        document.insert("cluster", ctx.legacy_cluster.as_str());
+
+        // The scoped document is written before the legacy one is dropped
+        // so an interrupted run never loses a document
+        coll.replace_one(doc! {"_id": &id}, &document)
+            .with_options(options.clone())
+            .await?;
+        coll.delete_one(doc! {"_id": &legacy_id}).await?;
+        migrated += 1;
+    }
+
+    Ok(migrated)
+}
+
+// The transactions used to be stored per address, now a body is shared by every address
+// and linked to each of them, the address of a legacy body becomes its first link
+fn link_transactions_to_addresses<'a>(
+    ctx: &'a MigrationContext,
+) -> BoxFuture<'a, Result<u64, AppError>> {
+    Box::pin(async move {
+        let transactions = ctx.db.collection::<Document>(TRANSACTION_COLLECTION);
+        let legacy = doc! {"account_address": {"$exists": true}};
+        if ctx.dry_run {
+            return Ok(transactions.count_documents(legacy).await?);
+        }
+
+        let memberships = ctx
+            .db
+            .collection::<AddressTransaction>(ADDRESS_TRANSACTION_COLLECTION);
+        let options = ReplaceOptions::builder().upsert(true).build();
+        let mut cursor = transactions.find(legacy).await?;
+        let mut migrated = 0;
+        while let Some(document) = cursor.try_next().await? {
+            let cluster = match document.get_str("cluster") {
+                Ok(cluster) => Cluster::from_str(cluster).map_err(AppError::Database)?,
+                Err(_) => ctx.legacy_cluster,
+            };
+            let address = document.get_str("account_address")?;
+            let signature = document.get_str("signature")?;
+
+            let membership = AddressTransaction {
+                id: membership_id(cluster, address, signature),
+                cluster,
+                account_address: address.to_string(),
+                signature: signature.to_string(),
+                slot: document.get_i64("slot")?,
+                block_time: document.get_i64("block_time").ok(),
+                indexed_at: Timestamp::from_millis(
+                    document.get_datetime("indexed_at")?.timestamp_millis(),
+                ),
+            };
+            memberships
+                .replace_one(doc! {"_id": &membership.id}, &membership)
+                .with_options(options.clone())
+                .await?;
+
+            // The address is dropped from the body only once its link is stored
+            transactions
+                .update_one(
+                    doc! {"_id": document.get("_id").cloned()},
+                    doc! {"$unset": {"account_address": ""}},
+                )
+                .await?;
+            migrated += 1;
+        }
+
+        Ok(migrated)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    // The schema history kept in memory instead of the metadata collection
+    #[derive(Default)]
+    struct History {
+        version: Mutex<i32>,
+        applied: Mutex<Vec<AppliedMigration>>,
+    }
+
+    impl History {
+        fn at(version: i32) -> Self {
+            History {
+                version: Mutex::new(version),
+                ..Default::default()
+            }
+        }
+
+        fn version(&self) -> i32 {
+            *self.version.lock().unwrap()
+        }
+
+        fn applied(&self) -> Vec<i32> {
+            let applied = self.applied.lock().unwrap();
+            applied.iter().map(|migration| migration.version).collect()
+        }
+    }
+
+    #[async_trait]
+    impl SchemaHistory for History {
+        async fn schema_version(&self) -> Result<i32, AppError> {
+            Ok(self.version())
+        }
+
+        async fn record_migration(&self, migration: &AppliedMigration) -> Result<(), AppError> {
+            *self.version.lock().unwrap() = migration.version;
+            self.applied.lock().unwrap().push(migration.clone());
+            Ok(())
+        }
+    }
+
+    // The versions of the migrations that ran, in the order they ran
+    type Ran = Mutex<Vec<i32>>;
+
+    fn first(ran: &Ran) -> BoxFuture<'_, Result<u64, AppError>> {
+        Box::pin(async move {
+            ran.lock().unwrap().push(1);
+            Ok(3)
+        })
+    }
+
+    fn second(ran: &Ran) -> BoxFuture<'_, Result<u64, AppError>> {
+        Box::pin(async move {
+            ran.lock().unwrap().push(2);
+            Ok(5)
+        })
+    }
+
+    const FAKE_MIGRATIONS: [Migration<Ran>; 2] = [
+        Migration {
+            version: 1,
+            name: "first",
+            run: first,
+        },
+        Migration {
+            version: 2,
+            name: "second",
+            run: second,
+        },
+    ];
+
+    #[tokio::test]
+    async fn records_the_version_after_every_migration() {
+        let history = History::default();
+        let ran = Ran::default();
+
+        let report = apply_migrations(&history, &ran, &FAKE_MIGRATIONS, false)
+            .await
+            .unwrap();
+
+        assert_eq!((report.from_version, report.to_version), (0, 2));
+        assert_eq!(*ran.lock().unwrap(), [1, 2]);
+        assert_eq!(history.version(), 2);
+        assert_eq!(history.applied(), [1, 2]);
+        assert_eq!(*history.applied.lock().unwrap(), report.migrations);
+    }
+
+    #[tokio::test]
+    async fn skips_the_applied_migrations() {
+        let history = History::at(1);
+        let ran = Ran::default();
+
+        let report = apply_migrations(&history, &ran, &FAKE_MIGRATIONS, false)
+            .await
+            .unwrap();
+
+        assert_eq!((report.from_version, report.to_version), (1, 2));
+        assert_eq!(*ran.lock().unwrap(), [2]);
+        assert_eq!(history.applied(), [2]);
+
+        // Nothing is left to apply on the next run
+        let report = apply_migrations(&history, &ran, &FAKE_MIGRATIONS, false)
+            .await
+            .unwrap();
+        assert!(report.migrations.is_empty());
+        assert_eq!(*ran.lock().unwrap(), [2]);
+    }
+
+    #[tokio::test]
+    async fn refuses_a_newer_schema() {
+        let history = History::at(3);
+        let ran = Ran::default();
+
+        let migrated = apply_migrations(&history, &ran, &FAKE_MIGRATIONS, false).await;
+
+        assert!(matches!(migrated, Err(AppError::Internal(_))));
+        assert!(ran.lock().unwrap().is_empty());
+        assert_eq!(history.version(), 3);
+        assert!(matches!(
+            checked_version(&history, &FAKE_MIGRATIONS).await,
+            Err(AppError::Internal(_))
+        ));
+        assert_eq!(
+            checked_version(&History::at(2), &FAKE_MIGRATIONS)
+                .await
+                .unwrap(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn dry_runs_count_the_documents_without_recording_them() {
+        let history = History::default();
+        let ran = Ran::default();
+
+        let report = apply_migrations(&history, &ran, &FAKE_MIGRATIONS, true)
+            .await
+            .unwrap();
+
+        assert!(report.dry_run);
+        assert_eq!((report.from_version, report.to_version), (0, 2));
+        let documents: Vec<u64> = report
+            .migrations
+            .iter()
+            .map(|migration| migration.documents)
+            .collect();
+        assert_eq!(documents, [3, 5]);
+        assert_eq!(history.version(), 0);
+        assert!(history.applied().is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+use futures::stream::TryStreamExt;
+use mongodb::{
+    Database,
+    bson::{doc, to_bson},
+    options::{FindOptions, ReplaceOptions},
+};
+
+use crate::cluster::Cluster;
+use crate::error::AppError;
+use crate::models::{RefreshSchedule, Timestamp, document_id};
+
+pub const REFRESH_SCHEDULES: &str = "refresh_schedules";
+
+pub async fn get_refresh_schedule(
+    db: &Database,
+    cluster: Cluster,
+    address: &str,
+) -> Result<Option<RefreshSchedule>, AppError> {
+    let schedule = db
+        .collection::<RefreshSchedule>(REFRESH_SCHEDULES)
+        .find_one(doc! {"_id": document_id(cluster, address)})
+        .await?;
+
+    Ok(schedule)
+}
+
+pub async fn save_refresh_schedule(
+    db: &Database,
+    schedule: &RefreshSchedule,
+) -> Result<(), AppError> {
+    let options = ReplaceOptions::builder().upsert(true).build();
+
+    db.collection::<RefreshSchedule>(REFRESH_SCHEDULES)
+        .replace_one(doc! {"_id": &schedule.id}, schedule)
+        .with_options(options)
+        .await?;
+
+    Ok(())
+}
+
+// The schedules of the given clusters whose refresh is due, the most overdue first
+pub async fn get_due_refresh_schedules(
+    db: &Database,
+    clusters: &[Cluster],
+    limit: i64,
+) -> Result<Vec<RefreshSchedule>, AppError> {
+    let options = FindOptions::builder()
+        .sort(doc! {"next_refresh_at": 1})
+        .limit(limit)
+        .build();
+
+    let schedules = db
+        .collection::<RefreshSchedule>(REFRESH_SCHEDULES)
+        .find(doc! {
+            "cluster": {"$in": to_bson(clusters)?},
+            "next_refresh_at": {"$lte": Timestamp::now()},
+        })
+        .with_options(options)
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(schedules)
+}
+
+pub async fn reschedule_refresh(
+    db: &Database,
+    id: &str,
+    next_refresh_at: Timestamp,
+) -> Result<(), AppError> {
+    db.collection::<RefreshSchedule>(REFRESH_SCHEDULES)
+        .update_one(
+            doc! {"_id": id},
+            doc! {"$set": {"next_refresh_at": next_refresh_at}},
+        )
+        .await?;
+
+    Ok(())
+}
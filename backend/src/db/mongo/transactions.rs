@@ -0,0 +1,542 @@
+use std::collections::{HashMap, HashSet};
+
+use futures::stream::TryStreamExt;
+use mongodb::{
+    Database,
+    bson::{Bson, Document, doc, to_document},
+    options::{
+        DeleteManyModel, FindOneOptions, FindOptions, UpdateManyModel, UpdateOneModel, WriteModel,
+    },
+};
+use serde::Serialize;
+
+use crate::cluster::Cluster;
+use crate::db::mongo::dead_letters::DEAD_LETTER_COLLECTION;
+use crate::error::AppError;
+use crate::models::{
+    AddressTransaction, FINALIZED, Transaction, TransactionSignature, UnfinalizedSignature,
+    UpsertCounts, Upserted, document_id, membership_id,
+};
+
+pub const SIGNATURE_COLLECTION: &str = "transaction_signatures";
+pub const TRANSACTION_COLLECTION: &str = "transactions";
+pub const ADDRESS_TRANSACTION_COLLECTION: &str = "address_transactions";
+
+// Build the upsert of a record that only overwrites the given fields of a stored document
+// The remaining fields are written once when the document is inserted
+fn upsert_model<T: Serialize>(
+    db: &Database,
+    collection: &str,
+    record: &T,
+    mutable_fields: &[&str],
+) -> Result<UpdateOneModel, AppError> {
+    let mut on_insert = to_document(record)?;
+    let id = on_insert.remove("_id").unwrap_or(Bson::Null);
+
+    let mut set = Document::new();
+    for field in mutable_fields {
+        if let Some(value) = on_insert.remove(*field) {
+            set.insert(*field, value);
+        }
+    }
+
+    Ok(UpdateOneModel::builder()
+        .namespace(db.collection::<Document>(collection).namespace())
+        .filter(doc! {"_id": id})
+        .update(doc! {"$set": set, "$setOnInsert": on_insert})
+        .upsert(true)
+        .build())
+}
+
+// Run the upserts as one unordered bulk write, so a failed write doesn't stop the rest
+// and a re-run of the same batch only updates what has changed
+async fn bulk_upsert(db: &Database, models: Vec<UpdateOneModel>) -> Result<UpsertCounts, AppError> {
+    if models.is_empty() {
+        return Ok(UpsertCounts::default());
+    }
+
+    let result = db.client().bulk_write(models).ordered(false).await?;
+
+    Ok(UpsertCounts {
+        inserted: result.upserted_count as u64,
+        updated: result.modified_count as u64,
+        skipped: (result.matched_count - result.modified_count) as u64,
+    })
+}
+
+// Run the upserts like bulk_upsert but tell the outcome of every upsert, in the order of the models
+async fn bulk_upsert_each(
+    db: &Database,
+    models: Vec<UpdateOneModel>,
+) -> Result<Vec<Upserted>, AppError> {
+    if models.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let count = models.len();
+    let result = db
+        .client()
+        .bulk_write(models)
+        .ordered(false)
+        .verbose_results()
+        .await?;
+
+    Ok((0..count)
+        .map(|index| match result.update_results.get(&index) {
+            Some(update) if update.upserted_id.is_some() => Upserted::Inserted,
+            Some(update) if update.modified_count > 0 => Upserted::Updated,
+            _ => Upserted::Skipped,
+        })
+        .collect())
+}
+
+pub async fn upsert_transaction_signatures(
+    db: &Database,
+    signatures: &[TransactionSignature],
+) -> Result<UpsertCounts, AppError> {
+    let models = signatures
+        .iter()
+        .map(|signature| {
+            upsert_model(
+                db,
+                SIGNATURE_COLLECTION,
+                signature,
+                &["slot", "block_time", "confirmation_status"],
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    bulk_upsert(db, models).await
+}
+
+// Store the transaction bodies once per signature and link every one of them to the address
+// A transaction counts as inserted when it is newly linked to the address
+// and as updated when its stored body has changed
+pub async fn upsert_transactions(
+    db: &Database,
+    address: &str,
+    txns: &[Transaction],
+) -> Result<UpsertCounts, AppError> {
+    let models = txns
+        .iter()
+        .map(|txn| {
+            upsert_model(
+                db,
+                TRANSACTION_COLLECTION,
+                txn,
+                &[
+                    "slot",
+                    "block_time",
+                    "version",
+                    "account_keys",
+                    "loaded_addresses",
+                    "transaction",
+                ],
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let bodies = bulk_upsert_each(db, models).await?;
+
+    let models = txns
+        .iter()
+        .map(|txn| {
+            let membership = AddressTransaction {
+                id: membership_id(txn.cluster, address, &txn.signature),
+                cluster: txn.cluster,
+                account_address: address.to_string(),
+                signature: txn.signature.clone(),
+                slot: txn.slot,
+                block_time: txn.block_time,
+                indexed_at: txn.indexed_at,
+            };
+            upsert_model(
+                db,
+                ADDRESS_TRANSACTION_COLLECTION,
+                &membership,
+                &["slot", "block_time"],
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let links = bulk_upsert_each(db, models).await?;
+
+    let mut counts = UpsertCounts::default();
+    for (body, link) in bodies.into_iter().zip(links) {
+        counts.add_transaction(body, link);
+    }
+
+    Ok(counts)
+}
+
+pub async fn get_transaction_signatures(
+    db: &Database,
+    cluster: Cluster,
+    address: String,
+    skip: u64,
+    limit: i64,
+) -> Result<Vec<TransactionSignature>, AppError> {
+    let signatures: Vec<TransactionSignature> = db
+        .collection::<TransactionSignature>(SIGNATURE_COLLECTION)
+        .find(doc! {"cluster": cluster.as_str(), "account_address": address})
+        .sort(doc! {"slot": -1})
+        .skip(skip)
+        .limit(limit)
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(signatures)
+}
+
+pub async fn get_transactions(
+    db: &Database,
+    cluster: Cluster,
+    address: String,
+    skip: u64,
+    limit: i64,
+) -> Result<Vec<Transaction>, AppError> {
+    // Page through the transactions linked to the address
+    let memberships: Vec<AddressTransaction> = db
+        .collection::<AddressTransaction>(ADDRESS_TRANSACTION_COLLECTION)
+        .find(doc! {"cluster": cluster.as_str(), "account_address": address})
+        .sort(doc! {"slot": -1})
+        .skip(skip)
+        .limit(limit)
+        .await?
+        .try_collect()
+        .await?;
+
+    let ids: Vec<String> = memberships
+        .iter()
+        .map(|membership| document_id(cluster, &membership.signature))
+        .collect();
+
+    // Then load their bodies and keep them in the order of the page
+    let mut txns: HashMap<String, Transaction> = db
+        .collection::<Transaction>(TRANSACTION_COLLECTION)
+        .find(doc! {"_id": {"$in": ids.as_slice()}})
+        .await?
+        .map_ok(|txn| (txn.id.clone(), txn))
+        .try_collect()
+        .await?;
+
+    Ok(ids.iter().filter_map(|id| txns.remove(id)).collect())
+}
+
+pub async fn get_transaction(
+    db: &Database,
+    cluster: Cluster,
+    address: String,
+    signature: String,
+) -> Result<Option<Transaction>, AppError> {
+    // The transaction has to belong to the address
+    let membership = db
+        .collection::<AddressTransaction>(ADDRESS_TRANSACTION_COLLECTION)
+        .find_one(doc! {"_id": membership_id(cluster, &address, &signature)})
+        .await?;
+    if membership.is_none() {
+        return Ok(None);
+    }
+
+    let txn = db
+        .collection::<Transaction>(TRANSACTION_COLLECTION)
+        .find_one(doc! {"_id": document_id(cluster, &signature)})
+        .await?;
+
+    Ok(txn)
+}
+
+#[derive(serde::Deserialize)]
+struct SignatureOnly {
+    signature: String,
+}
+
+// The latest stored signature of the address, a sync continues from it
+// An address without any transaction yet has none
+pub async fn get_latest_signature(
+    db: &Database,
+    cluster: Cluster,
+    address: String,
+) -> Result<Option<String>, AppError> {
+    let options = FindOneOptions::builder()
+        .sort(doc! {"slot": -1})
+        .projection(doc! {"_id": 0, "signature": 1})
+        .build();
+
+    let latest_record = db
+        .collection::<SignatureOnly>(SIGNATURE_COLLECTION)
+        .find_one(doc! {"cluster": cluster.as_str(), "account_address": address})
+        .with_options(options)
+        .await?;
+
+    Ok(latest_record.map(|r| r.signature))
+}
+
+// The oldest stored signature of the address, where a deeper history continues from
+pub async fn get_oldest_signature(
+    db: &Database,
+    cluster: Cluster,
+    address: &str,
+) -> Result<Option<String>, AppError> {
+    let options = FindOneOptions::builder()
+        .sort(doc! {"slot": 1})
+        .projection(doc! {"_id": 0, "signature": 1})
+        .build();
+
+    let oldest_record = db
+        .collection::<SignatureOnly>(SIGNATURE_COLLECTION)
+        .find_one(doc! {"cluster": cluster.as_str(), "account_address": address})
+        .with_options(options)
+        .await?;
+
+    Ok(oldest_record.map(|r| r.signature))
+}
+
+// The given signatures that are stored for the address
+pub async fn get_stored_signatures(
+    db: &Database,
+    cluster: Cluster,
+    address: &str,
+    signatures: &[String],
+) -> Result<HashSet<String>, AppError> {
+    let options = FindOptions::builder()
+        .projection(doc! {"_id": 0, "signature": 1})
+        .build();
+
+    let records: Vec<SignatureOnly> = db
+        .collection::<SignatureOnly>(SIGNATURE_COLLECTION)
+        .find(doc! {
+            "cluster": cluster.as_str(),
+            "account_address": address,
+            "signature": {"$in": signatures},
+        })
+        .with_options(options)
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(records.into_iter().map(|r| r.signature).collect())
+}
+
+// The stored signatures of the address without a stored transaction, newest first
+// The ones that are already retried as dead letters are left out
+pub async fn get_signatures_without_transactions(
+    db: &Database,
+    cluster: Cluster,
+    address: &str,
+) -> Result<Vec<String>, AppError> {
+    let records: Vec<SignatureOnly> = db
+        .collection::<Document>(SIGNATURE_COLLECTION)
+        .aggregate([
+            doc! {
+                "$match": {
+                    "cluster": cluster.as_str(),
+                    "account_address": address
+                }
+            },
+            // A membership and a dead letter share their _id with the signature of the address
+            doc! {
+                "$lookup": {
+                    "from": ADDRESS_TRANSACTION_COLLECTION,
+                    "localField": "_id",
+                    "foreignField": "_id",
+                    "as": "membership",
+                    "pipeline": [{"$project": {"_id": 1}}]
+                }
+            },
+            doc! {
+                "$match": {
+                    "membership": []
+                }
+            },
+            doc! {
+                "$lookup": {
+                    "from": DEAD_LETTER_COLLECTION,
+                    "localField": "_id",
+                    "foreignField": "_id",
+                    "as": "dead_letter",
+                    "pipeline": [{"$project": {"_id": 1}}]
+                }
+            },
+            doc! {
+                "$match": {
+                    "dead_letter": []
+                }
+            },
+            doc! {
+                "$sort": {
+                    "slot": -1
+                }
+            },
+            doc! {
+                "$project": {
+                    "_id": 0,
+                    "signature": 1
+                }
+            },
+        ])
+        .with_type::<SignatureOnly>()
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(records.into_iter().map(|r| r.signature).collect())
+}
+
+pub async fn get_signatures_count(
+    db: &Database,
+    cluster: Cluster,
+    address: &str,
+) -> Result<u64, AppError> {
+    let count = db
+        .collection::<TransactionSignature>(SIGNATURE_COLLECTION)
+        .count_documents(doc! {
+            "cluster": cluster.as_str(),
+            "account_address": address
+        })
+        .await?;
+
+    Ok(count)
+}
+
+pub async fn get_transactions_count(
+    db: &Database,
+    cluster: Cluster,
+    address: &str,
+) -> Result<u64, AppError> {
+    let count = db
+        .collection::<AddressTransaction>(ADDRESS_TRANSACTION_COLLECTION)
+        .count_documents(doc! {
+            "cluster": cluster.as_str(),
+            "account_address": address
+        })
+        .await?;
+
+    Ok(count)
+}
+
+// The stored signatures of the cluster that still have to be finalized, the oldest first
+pub async fn get_unfinalized_signatures(
+    db: &Database,
+    cluster: Cluster,
+    limit: i64,
+) -> Result<Vec<UnfinalizedSignature>, AppError> {
+    let options = FindOptions::builder()
+        .sort(doc! {"indexed_at": 1})
+        .projection(doc! {"_id": 0, "signature": 1, "indexed_at": 1})
+        .limit(limit)
+        .build();
+
+    let signatures = db
+        .collection::<UnfinalizedSignature>(SIGNATURE_COLLECTION)
+        .find(doc! {"cluster": cluster.as_str(), "confirmation_status": {"$ne": FINALIZED}})
+        .with_options(options)
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(signatures)
+}
+
+// Mark the signatures as finalized for every address that they belong to
+// along with the slot they were finalized in, which can differ after a fork
+pub async fn finalize_signatures(
+    db: &Database,
+    cluster: Cluster,
+    finalized: &[(String, i64)],
+) -> Result<(), AppError> {
+    if finalized.is_empty() {
+        return Ok(());
+    }
+
+    let mut models: Vec<WriteModel> = Vec::with_capacity(finalized.len() * 3);
+    for (signature, slot) in finalized {
+        models.push(
+            UpdateManyModel::builder()
+                .namespace(db.collection::<Document>(SIGNATURE_COLLECTION).namespace())
+                .filter(doc! {"cluster": cluster.as_str(), "signature": signature})
+                .update(doc! {"$set": {"confirmation_status": FINALIZED, "slot": slot}})
+                .build()
+                .into(),
+        );
+        models.push(
+            UpdateManyModel::builder()
+                .namespace(
+                    db.collection::<Document>(ADDRESS_TRANSACTION_COLLECTION)
+                        .namespace(),
+                )
+                .filter(doc! {"cluster": cluster.as_str(), "signature": signature})
+                .update(doc! {"$set": {"slot": slot}})
+                .build()
+                .into(),
+        );
+        models.push(
+            UpdateOneModel::builder()
+                .namespace(
+                    db.collection::<Document>(TRANSACTION_COLLECTION)
+                        .namespace(),
+                )
+                .filter(doc! {"_id": document_id(cluster, signature)})
+                .update(doc! {"$set": {"slot": slot}})
+                .build()
+                .into(),
+        );
+    }
+
+    db.client().bulk_write(models).ordered(false).await?;
+
+    Ok(())
+}
+
+// Delete the signatures that were dropped on a fork along with their transactions
+// and dead letters, for every address that they belonged to
+pub async fn remove_signatures(
+    db: &Database,
+    cluster: Cluster,
+    signatures: &[String],
+) -> Result<(), AppError> {
+    if signatures.is_empty() {
+        return Ok(());
+    }
+
+    let ids: Vec<String> = signatures
+        .iter()
+        .map(|signature| document_id(cluster, signature))
+        .collect();
+    let by_signature = doc! {"cluster": cluster.as_str(), "signature": {"$in": signatures}};
+
+    let models: Vec<WriteModel> = vec![
+        DeleteManyModel::builder()
+            .namespace(db.collection::<Document>(SIGNATURE_COLLECTION).namespace())
+            .filter(by_signature.clone())
+            .build()
+            .into(),
+        DeleteManyModel::builder()
+            .namespace(
+                db.collection::<Document>(ADDRESS_TRANSACTION_COLLECTION)
+                    .namespace(),
+            )
+            .filter(by_signature.clone())
+            .build()
+            .into(),
+        DeleteManyModel::builder()
+            .namespace(
+                db.collection::<Document>(DEAD_LETTER_COLLECTION)
+                    .namespace(),
+            )
+            .filter(by_signature)
+            .build()
+            .into(),
+        DeleteManyModel::builder()
+            .namespace(
+                db.collection::<Document>(TRANSACTION_COLLECTION)
+                    .namespace(),
+            )
+            .filter(doc! {"_id": {"$in": ids.as_slice()}})
+            .build()
+            .into(),
+    ];
+
+    db.client().bulk_write(models).ordered(false).await?;
+
+    Ok(())
+}
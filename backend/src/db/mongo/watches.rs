@@ -0,0 +1,52 @@
+use futures::stream::TryStreamExt;
+use mongodb::{
+    Database,
+    bson::{doc, to_bson},
+    options::ReplaceOptions,
+};
+
+use crate::cluster::Cluster;
+use crate::error::AppError;
+use crate::models::{WatchedAddress, document_id};
+
+pub const WATCHED_ADDRESSES: &str = "watched_addresses";
+
+pub async fn save_watched_address(db: &Database, watch: &WatchedAddress) -> Result<(), AppError> {
+    let options = ReplaceOptions::builder().upsert(true).build();
+
+    db.collection::<WatchedAddress>(WATCHED_ADDRESSES)
+        .replace_one(doc! {"_id": &watch.id}, watch)
+        .with_options(options)
+        .await?;
+
+    Ok(())
+}
+
+// Stop watching the address, returns whether it was watched
+pub async fn delete_watched_address(
+    db: &Database,
+    cluster: Cluster,
+    address: &str,
+) -> Result<bool, AppError> {
+    let deleted = db
+        .collection::<WatchedAddress>(WATCHED_ADDRESSES)
+        .delete_one(doc! {"_id": document_id(cluster, address)})
+        .await?;
+
+    Ok(deleted.deleted_count > 0)
+}
+
+// The watched addresses of the given clusters
+pub async fn get_watched_addresses(
+    db: &Database,
+    clusters: &[Cluster],
+) -> Result<Vec<WatchedAddress>, AppError> {
+    let watches = db
+        .collection::<WatchedAddress>(WATCHED_ADDRESSES)
+        .find(doc! {"cluster": {"$in": to_bson(clusters)?}})
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(watches)
+}
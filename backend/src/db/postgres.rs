@@ -0,0 +1,777 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{
+    PgPool,
+    postgres::{PgPoolOptions, Postgres},
+    types::Json,
+};
+
+use crate::cluster::Cluster;
+use crate::db::sql::{self, SqlQueries, text, upserted};
+use crate::db::{
+    AccountStore, DeadLetterStore, IndexingStateStore, JobStore, ScheduleStore, SignatureStore,
+    TransactionStore, WatchStore,
+};
+use crate::error::AppError;
+use crate::models::{
+    Account, AddressIndexingState, DeadLetter, DeadLetterStatus, FINALIZED, IndexerStats,
+    IndexingBounds, IndexingCheckpoint, IndexingError, IndexingJob, IndexingState, JobStatus,
+    RefreshSchedule, Timestamp, Transaction, TransactionSignature, UnfinalizedSignature,
+    UpdateAccount, UpdateAddressIndexingState, UpsertCounts, WatchedAddress, document_id,
+    membership_id,
+};
+
+// The storage backed by a PostgreSQL database
+// Accounts, signatures and the indexer's own records are stored in typed columns
+// while the transaction bodies are stored as JSONB
+#[derive(Debug, Clone)]
+pub struct PostgresStorage {
+    pool: PgPool,
+}
+
+impl PostgresStorage {
+    // Connect to the database and bring its schema up to date
+    pub async fn init() -> Result<Self, AppError> {
+        let url = std::env::var("POSTGRES_URL").expect("POSTGRES_URL env variable is mising");
+
+        let pool = PgPoolOptions::new().connect(&url).await?;
+        sqlx::migrate!("./migrations/postgres").run(&pool).await?;
+
+        Ok(PostgresStorage { pool })
+    }
+
+    fn queries(&self) -> SqlQueries<'_, Postgres> {
+        SqlQueries { pool: &self.pool }
+    }
+}
+
+// A limit of 0 means no limit like it does for a Mongo find
+fn limit(limit: i64) -> Option<i64> {
+    (limit != 0).then(|| limit.abs())
+}
+
+// The shared rows with their timestamps stored as TIMESTAMPTZ
+type DeadLetterRow = sql::DeadLetterRow<DateTime<Utc>>;
+type IndexingStateRow = sql::IndexingStateRow<DateTime<Utc>>;
+type JobRow = sql::JobRow<DateTime<Utc>>;
+type ScheduleRow = sql::ScheduleRow<DateTime<Utc>>;
+type SignatureRow = sql::SignatureRow<DateTime<Utc>>;
+type UnfinalizedRow = sql::UnfinalizedRow<DateTime<Utc>>;
+type WatchRow = sql::WatchRow<DateTime<Utc>>;
+type TransactionRow = sql::TransactionRow<DateTime<Utc>, Vec<String>>;
+
+#[async_trait]
+impl AccountStore for PostgresStorage {
+    async fn get_account(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Option<Account>, AppError> {
+        self.queries().get_account(cluster, address).await
+    }
+
+    async fn insert_account(&self, account: &Account) -> Result<(), AppError> {
+        self.queries().insert_account(account).await
+    }
+
+    async fn update_account(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        account: UpdateAccount,
+    ) -> Result<Account, AppError> {
+        self.queries()
+            .update_account(cluster, address, account)
+            .await
+    }
+
+    async fn get_indexer_stats(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<IndexerStats, AppError> {
+        self.queries().get_indexer_stats(cluster, address).await
+    }
+}
+
+#[async_trait]
+impl IndexingStateStore for PostgresStorage {
+    async fn get_address_indexing_state(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<AddressIndexingState, AppError> {
+        self.queries()
+            .get_address_indexing_state(cluster, address)
+            .await
+    }
+
+    async fn insert_address_indexing_state(
+        &self,
+        record: AddressIndexingState,
+    ) -> Result<bool, AppError> {
+        let inserted = sqlx::query(
+            "INSERT INTO address_indexing_states (id, cluster, address, state, checkpoint, bounds,
+                last_error, started_at, finished_at, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            ON CONFLICT (id) DO NOTHING",
+        )
+        .bind(&record.id)
+        .bind(record.cluster.as_str())
+        .bind(&record.address)
+        .bind(text(&record.state)?)
+        .bind(record.checkpoint.map(Json))
+        .bind(Json(record.bounds))
+        .bind(record.last_error.map(Json))
+        .bind(record.started_at.map(|time| time.to_chrono()))
+        .bind(record.finished_at.map(|time| time.to_chrono()))
+        .bind(record.created_at.to_chrono())
+        .bind(record.updated_at.to_chrono())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(inserted.rows_affected() > 0)
+    }
+
+    async fn update_address_indexing_state(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        update: UpdateAddressIndexingState,
+    ) -> Result<(), AppError> {
+        let sources = IndexingState::sources(update.state)
+            .iter()
+            .map(text)
+            .collect::<Result<Vec<_>, _>>()?;
+        let running = update.state.is_running();
+        let finished = !running && update.state != IndexingState::Queued;
+
+        // The update only matches when the address is in a state that can move to the next one
+        let updated = sqlx::query(
+            "UPDATE address_indexing_states SET
+                state = $3,
+                checkpoint = $4,
+                last_error = $5,
+                updated_at = $6,
+                started_at = CASE WHEN $7 THEN $6 ELSE started_at END,
+                finished_at = CASE WHEN $7 THEN NULL WHEN $8 THEN $6 ELSE finished_at END
+            WHERE id = $1 AND state = ANY($2)",
+        )
+        .bind(document_id(cluster, address))
+        .bind(&sources)
+        .bind(text(&update.state)?)
+        .bind(update.checkpoint.map(Json))
+        .bind(update.last_error.map(Json))
+        .bind(update.updated_at.to_chrono())
+        .bind(running)
+        .bind(finished)
+        .execute(&self.pool)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            // Tell an illegal transition apart from a missing address
+            let current = self.get_address_indexing_state(cluster, address).await?;
+            return Err(AppError::BadRequest(format!(
+                "Address can't move from {} to {}",
+                current.state, update.state
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn get_unfinished_address_states(&self) -> Result<Vec<AddressIndexingState>, AppError> {
+        let states = [
+            IndexingState::Queued,
+            IndexingState::Indexing,
+            IndexingState::Syncing,
+        ]
+        .iter()
+        .map(text)
+        .collect::<Result<Vec<_>, _>>()?;
+
+        sqlx::query_as::<_, IndexingStateRow>(
+            "SELECT * FROM address_indexing_states WHERE state = ANY($1)",
+        )
+        .bind(&states)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(AddressIndexingState::try_from)
+        .collect()
+    }
+
+    async fn get_idle_addresses(&self, cluster: Cluster) -> Result<Vec<String>, AppError> {
+        self.queries().get_idle_addresses(cluster).await
+    }
+
+    async fn save_indexing_checkpoint(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        checkpoint: &IndexingCheckpoint,
+        updated_at: Timestamp,
+    ) -> Result<(), AppError> {
+        self.queries()
+            .save_indexing_checkpoint(cluster, address, checkpoint, updated_at)
+            .await
+    }
+
+    async fn save_indexing_bounds(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        bounds: &IndexingBounds,
+        updated_at: Timestamp,
+    ) -> Result<(), AppError> {
+        self.queries()
+            .save_indexing_bounds(cluster, address, bounds, updated_at)
+            .await
+    }
+}
+
+#[async_trait]
+impl SignatureStore for PostgresStorage {
+    async fn upsert_transaction_signatures(
+        &self,
+        signatures: &[TransactionSignature],
+    ) -> Result<UpsertCounts, AppError> {
+        let mut counts = UpsertCounts::default();
+        let mut tx = self.pool.begin().await?;
+        for signature in signatures {
+            // Only a changed slot, block time or confirmation status is written,
+            // an up to date row returns nothing
+            let inserted = sqlx::query_scalar::<_, bool>(
+                "INSERT INTO transaction_signatures (id, cluster, signature, account_address, slot,
+                    block_time, confirmation_status, indexed_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT (id) DO UPDATE SET
+                    slot = EXCLUDED.slot,
+                    block_time = EXCLUDED.block_time,
+                    confirmation_status = EXCLUDED.confirmation_status
+                WHERE (transaction_signatures.slot, transaction_signatures.block_time,
+                        transaction_signatures.confirmation_status)
+                    IS DISTINCT FROM (EXCLUDED.slot, EXCLUDED.block_time, EXCLUDED.confirmation_status)
+                RETURNING (xmax = 0)",
+            )
+            .bind(&signature.id)
+            .bind(signature.cluster.as_str())
+            .bind(&signature.signature)
+            .bind(&signature.account_address)
+            .bind(signature.slot)
+            .bind(signature.block_time)
+            .bind(&signature.confirmation_status)
+            .bind(signature.indexed_at.to_chrono())
+            .fetch_optional(&mut *tx)
+            .await?;
+            counts.add(upserted(inserted));
+        }
+        tx.commit().await?;
+
+        Ok(counts)
+    }
+
+    async fn get_transaction_signatures(
+        &self,
+        cluster: Cluster,
+        address: String,
+        skip: u64,
+        limit: i64,
+    ) -> Result<Vec<TransactionSignature>, AppError> {
+        sqlx::query_as::<_, SignatureRow>(
+            "SELECT * FROM transaction_signatures
+            WHERE cluster = $1 AND account_address = $2
+            ORDER BY slot DESC
+            OFFSET $3 LIMIT $4",
+        )
+        .bind(cluster.as_str())
+        .bind(address)
+        .bind(skip as i64)
+        .bind(self::limit(limit))
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(TransactionSignature::try_from)
+        .collect()
+    }
+
+    async fn get_latest_signature(
+        &self,
+        cluster: Cluster,
+        address: String,
+    ) -> Result<Option<String>, AppError> {
+        self.queries().get_latest_signature(cluster, address).await
+    }
+
+    async fn get_oldest_signature(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Option<String>, AppError> {
+        self.queries().get_oldest_signature(cluster, address).await
+    }
+
+    async fn get_stored_signatures(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        signatures: &[String],
+    ) -> Result<HashSet<String>, AppError> {
+        let stored = sqlx::query_scalar::<_, String>(
+            "SELECT signature FROM transaction_signatures
+            WHERE cluster = $1 AND account_address = $2 AND signature = ANY($3)",
+        )
+        .bind(cluster.as_str())
+        .bind(address)
+        .bind(signatures)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(stored.into_iter().collect())
+    }
+
+    async fn get_signatures_count(&self, cluster: Cluster, address: &str) -> Result<u64, AppError> {
+        self.queries().get_signatures_count(cluster, address).await
+    }
+
+    async fn get_unfinalized_signatures(
+        &self,
+        cluster: Cluster,
+        limit: i64,
+    ) -> Result<Vec<UnfinalizedSignature>, AppError> {
+        let rows = sqlx::query_as::<_, UnfinalizedRow>(
+            "SELECT signature, indexed_at FROM transaction_signatures
+            WHERE cluster = $1 AND confirmation_status <> $2
+            ORDER BY indexed_at
+            LIMIT $3",
+        )
+        .bind(cluster.as_str())
+        .bind(FINALIZED)
+        .bind(self::limit(limit))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(UnfinalizedSignature::from).collect())
+    }
+
+    async fn finalize_signatures(
+        &self,
+        cluster: Cluster,
+        finalized: &[(String, i64)],
+    ) -> Result<(), AppError> {
+        self.queries().finalize_signatures(cluster, finalized).await
+    }
+
+    async fn remove_signatures(
+        &self,
+        cluster: Cluster,
+        signatures: &[String],
+    ) -> Result<(), AppError> {
+        if signatures.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self.pool.begin().await?;
+        for table in [
+            "transaction_signatures",
+            "address_transactions",
+            "dead_letters",
+            "transactions",
+        ] {
+            sqlx::query(&format!(
+                "DELETE FROM {table} WHERE cluster = $1 AND signature = ANY($2)"
+            ))
+            .bind(cluster.as_str())
+            .bind(signatures)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TransactionStore for PostgresStorage {
+    async fn upsert_transactions(
+        &self,
+        address: &str,
+        txns: &[Transaction],
+    ) -> Result<UpsertCounts, AppError> {
+        let mut counts = UpsertCounts::default();
+        let mut tx = self.pool.begin().await?;
+        for txn in txns {
+            let body = sqlx::query_scalar::<_, bool>(
+                "INSERT INTO transactions (id, cluster, signature, slot, block_time, version,
+                    account_keys, loaded_addresses, transaction, indexed_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                ON CONFLICT (id) DO UPDATE SET
+                    slot = EXCLUDED.slot,
+                    block_time = EXCLUDED.block_time,
+                    version = EXCLUDED.version,
+                    account_keys = EXCLUDED.account_keys,
+                    loaded_addresses = EXCLUDED.loaded_addresses,
+                    transaction = EXCLUDED.transaction
+                WHERE (transactions.slot, transactions.block_time, transactions.version,
+                        transactions.account_keys, transactions.loaded_addresses,
+                        transactions.transaction)
+                    IS DISTINCT FROM (EXCLUDED.slot, EXCLUDED.block_time, EXCLUDED.version,
+                        EXCLUDED.account_keys, EXCLUDED.loaded_addresses, EXCLUDED.transaction)
+                RETURNING (xmax = 0)",
+            )
+            .bind(&txn.id)
+            .bind(txn.cluster.as_str())
+            .bind(&txn.signature)
+            .bind(txn.slot)
+            .bind(txn.block_time)
+            .bind(&txn.version)
+            .bind(&txn.account_keys)
+            .bind(Json(&txn.loaded_addresses))
+            .bind(Json(&txn.transaction))
+            .bind(txn.indexed_at.to_chrono())
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            // Link the transaction to the address
+            let link = sqlx::query_scalar::<_, bool>(
+                "INSERT INTO address_transactions (id, cluster, account_address, signature, slot,
+                    block_time, indexed_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (id) DO UPDATE SET
+                    slot = EXCLUDED.slot,
+                    block_time = EXCLUDED.block_time
+                WHERE (address_transactions.slot, address_transactions.block_time)
+                    IS DISTINCT FROM (EXCLUDED.slot, EXCLUDED.block_time)
+                RETURNING (xmax = 0)",
+            )
+            .bind(membership_id(txn.cluster, address, &txn.signature))
+            .bind(txn.cluster.as_str())
+            .bind(address)
+            .bind(&txn.signature)
+            .bind(txn.slot)
+            .bind(txn.block_time)
+            .bind(txn.indexed_at.to_chrono())
+            .fetch_optional(&mut *tx)
+            .await?;
+            counts.add_transaction(upserted(body), upserted(link));
+        }
+        tx.commit().await?;
+
+        Ok(counts)
+    }
+
+    async fn get_transactions(
+        &self,
+        cluster: Cluster,
+        address: String,
+        skip: u64,
+        limit: i64,
+    ) -> Result<Vec<Transaction>, AppError> {
+        // Page through the transactions linked to the address, then load their bodies
+        sqlx::query_as::<_, TransactionRow>(
+            "SELECT t.* FROM (
+                SELECT signature, slot FROM address_transactions
+                WHERE cluster = $1 AND account_address = $2
+                ORDER BY slot DESC
+                OFFSET $3 LIMIT $4
+            ) m
+            JOIN transactions t ON t.cluster = $1 AND t.signature = m.signature
+            ORDER BY m.slot DESC",
+        )
+        .bind(cluster.as_str())
+        .bind(address)
+        .bind(skip as i64)
+        .bind(self::limit(limit))
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(Transaction::try_from)
+        .collect()
+    }
+
+    async fn get_transaction(
+        &self,
+        cluster: Cluster,
+        address: String,
+        signature: String,
+    ) -> Result<Option<Transaction>, AppError> {
+        self.queries()
+            .get_transaction(cluster, address, signature)
+            .await
+    }
+
+    async fn get_transactions_count(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<u64, AppError> {
+        self.queries()
+            .get_transactions_count(cluster, address)
+            .await
+    }
+
+    async fn get_signatures_without_transactions(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Vec<String>, AppError> {
+        self.queries()
+            .get_signatures_without_transactions(cluster, address)
+            .await
+    }
+}
+
+#[async_trait]
+impl JobStore for PostgresStorage {
+    async fn enqueue_job(&self, job: &IndexingJob) -> Result<IndexingJob, AppError> {
+        // A queued job of the address only takes the higher priority of the new one
+        sqlx::query_as::<_, JobRow>(
+            "INSERT INTO indexing_jobs (id, cluster, address, kind, priority, status, enqueued_at,
+                started_at, bounds)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ON CONFLICT (id) DO UPDATE SET
+                priority = GREATEST(indexing_jobs.priority, EXCLUDED.priority)
+            RETURNING *",
+        )
+        .bind(&job.id)
+        .bind(job.cluster.as_str())
+        .bind(&job.address)
+        .bind(text(&job.kind)?)
+        .bind(job.priority)
+        .bind(text(&job.status)?)
+        .bind(job.enqueued_at.to_chrono())
+        .bind(job.started_at.map(|time| time.to_chrono()))
+        .bind(job.bounds.as_ref().map(Json))
+        .fetch_one(&self.pool)
+        .await?
+        .try_into()
+    }
+
+    async fn claim_next_job(&self) -> Result<Option<IndexingJob>, AppError> {
+        // The row lock keeps two workers from claiming the same job
+        sqlx::query_as::<_, JobRow>(
+            "UPDATE indexing_jobs SET status = $2, started_at = $3
+            WHERE id = (
+                SELECT id FROM indexing_jobs
+                WHERE status = $1
+                ORDER BY priority DESC, enqueued_at
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *",
+        )
+        .bind(text(&JobStatus::Pending)?)
+        .bind(text(&JobStatus::Running)?)
+        .bind(Utc::now())
+        .fetch_optional(&self.pool)
+        .await?
+        .map(IndexingJob::try_from)
+        .transpose()
+    }
+
+    async fn get_pending_jobs(&self) -> Result<Vec<IndexingJob>, AppError> {
+        self.queries().get_pending_jobs().await
+    }
+
+    async fn delete_job(&self, id: &str) -> Result<(), AppError> {
+        self.queries().delete_job(id).await
+    }
+
+    async fn delete_pending_job(&self, id: &str) -> Result<bool, AppError> {
+        self.queries().delete_pending_job(id).await
+    }
+
+    async fn reset_running_jobs(&self) -> Result<u64, AppError> {
+        self.queries().reset_running_jobs().await
+    }
+}
+
+#[async_trait]
+impl DeadLetterStore for PostgresStorage {
+    async fn record_dead_letter(&self, dead_letter: &DeadLetter) -> Result<(), AppError> {
+        // A recorded signature counts the failed attempt and keeps its retry schedule
+        sqlx::query(
+            "INSERT INTO dead_letters (id, cluster, account_address, signature, status, attempts,
+                error, next_attempt_at, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ON CONFLICT (id) DO UPDATE SET
+                attempts = dead_letters.attempts + EXCLUDED.attempts,
+                error = EXCLUDED.error,
+                updated_at = EXCLUDED.updated_at",
+        )
+        .bind(&dead_letter.id)
+        .bind(dead_letter.cluster.as_str())
+        .bind(&dead_letter.account_address)
+        .bind(&dead_letter.signature)
+        .bind(text(&dead_letter.status)?)
+        .bind(dead_letter.attempts)
+        .bind(Json(&dead_letter.error))
+        .bind(dead_letter.next_attempt_at.to_chrono())
+        .bind(dead_letter.created_at.to_chrono())
+        .bind(dead_letter.updated_at.to_chrono())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_due_dead_letters(
+        &self,
+        cluster: Cluster,
+        limit: i64,
+    ) -> Result<Vec<DeadLetter>, AppError> {
+        sqlx::query_as::<_, DeadLetterRow>(
+            "SELECT * FROM dead_letters
+            WHERE cluster = $1 AND status = $2 AND next_attempt_at <= $3
+            ORDER BY next_attempt_at
+            LIMIT $4",
+        )
+        .bind(cluster.as_str())
+        .bind(text(&DeadLetterStatus::Pending)?)
+        .bind(Utc::now())
+        .bind(self::limit(limit))
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(DeadLetter::try_from)
+        .collect()
+    }
+
+    async fn update_dead_letter(
+        &self,
+        id: &str,
+        status: DeadLetterStatus,
+        error: &IndexingError,
+        next_attempt_at: Timestamp,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "UPDATE dead_letters SET attempts = attempts + 1, status = $2, error = $3,
+                next_attempt_at = $4, updated_at = $5
+            WHERE id = $1",
+        )
+        .bind(id)
+        .bind(text(&status)?)
+        .bind(Json(error))
+        .bind(next_attempt_at.to_chrono())
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_dead_letter(&self, id: &str) -> Result<(), AppError> {
+        self.queries().delete_dead_letter(id).await
+    }
+}
+
+fn cluster_names(clusters: &[Cluster]) -> Vec<&'static str> {
+    clusters.iter().map(Cluster::as_str).collect()
+}
+
+#[async_trait]
+impl ScheduleStore for PostgresStorage {
+    async fn get_refresh_schedule(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Option<RefreshSchedule>, AppError> {
+        self.queries().get_refresh_schedule(cluster, address).await
+    }
+
+    async fn save_refresh_schedule(&self, schedule: &RefreshSchedule) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO refresh_schedules (id, cluster, address, interval_secs, last_viewed_at,
+                next_refresh_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (id) DO UPDATE SET
+                interval_secs = EXCLUDED.interval_secs,
+                last_viewed_at = EXCLUDED.last_viewed_at,
+                next_refresh_at = EXCLUDED.next_refresh_at",
+        )
+        .bind(&schedule.id)
+        .bind(schedule.cluster.as_str())
+        .bind(&schedule.address)
+        .bind(schedule.interval_secs)
+        .bind(schedule.last_viewed_at.to_chrono())
+        .bind(schedule.next_refresh_at.to_chrono())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_due_refresh_schedules(
+        &self,
+        clusters: &[Cluster],
+        limit: i64,
+    ) -> Result<Vec<RefreshSchedule>, AppError> {
+        sqlx::query_as::<_, ScheduleRow>(
+            "SELECT * FROM refresh_schedules
+            WHERE cluster = ANY($1) AND next_refresh_at <= $2
+            ORDER BY next_refresh_at
+            LIMIT $3",
+        )
+        .bind(cluster_names(clusters))
+        .bind(Utc::now())
+        .bind(self::limit(limit))
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(RefreshSchedule::try_from)
+        .collect()
+    }
+
+    async fn reschedule_refresh(
+        &self,
+        id: &str,
+        next_refresh_at: Timestamp,
+    ) -> Result<(), AppError> {
+        self.queries().reschedule_refresh(id, next_refresh_at).await
+    }
+}
+
+#[async_trait]
+impl WatchStore for PostgresStorage {
+    async fn save_watched_address(&self, watch: &WatchedAddress) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO watched_addresses (id, cluster, address, watched_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (id) DO UPDATE SET watched_at = EXCLUDED.watched_at",
+        )
+        .bind(&watch.id)
+        .bind(watch.cluster.as_str())
+        .bind(&watch.address)
+        .bind(watch.watched_at.to_chrono())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_watched_address(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<bool, AppError> {
+        self.queries()
+            .delete_watched_address(cluster, address)
+            .await
+    }
+
+    async fn get_watched_addresses(
+        &self,
+        clusters: &[Cluster],
+    ) -> Result<Vec<WatchedAddress>, AppError> {
+        sqlx::query_as::<_, WatchRow>("SELECT * FROM watched_addresses WHERE cluster = ANY($1)")
+            .bind(cluster_names(clusters))
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(WatchedAddress::try_from)
+            .collect()
+    }
+}
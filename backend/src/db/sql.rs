@@ -0,0 +1,835 @@
+use chrono::{DateTime, Utc};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use sqlx::{
+    Database, Encode, Executor, FromRow, IntoArguments, Pool, Postgres, Sqlite, Type,
+    postgres::PgQueryResult, sqlite::SqliteQueryResult, types::Json,
+};
+
+use crate::cluster::Cluster;
+use crate::error::AppError;
+use crate::models::{
+    Account, AddressIndexingState, DeadLetter, DeadLetterStatus, FINALIZED, IndexerStats,
+    IndexingBounds, IndexingCheckpoint, IndexingError, IndexingJob, IndexingState, JobStatus,
+    LoadedAddresses, RefreshSchedule, Timestamp, Transaction, TransactionSignature,
+    UnfinalizedSignature, UpdateAccount, Upserted, WatchedAddress, document_id, membership_id,
+};
+
+// The enums are stored as the same lowercase text as in the Mongo documents
+pub fn text<T: Serialize>(value: &T) -> Result<String, AppError> {
+    match serde_json::to_value(value)? {
+        Value::String(text) => Ok(text),
+        other => Err(AppError::Internal(format!("{other} is not stored as text"))),
+    }
+}
+
+pub fn parse<T: DeserializeOwned>(text: String) -> Result<T, AppError> {
+    Ok(serde_json::from_value(Value::String(text))?)
+}
+
+// The outcome of an upsert from whether the row was inserted,
+// no outcome at all means that the stored row was already up to date
+pub fn upserted(inserted: Option<bool>) -> Upserted {
+    match inserted {
+        Some(true) => Upserted::Inserted,
+        Some(false) => Upserted::Updated,
+        None => Upserted::Skipped,
+    }
+}
+
+// A timestamp column, a TIMESTAMPTZ in PostgreSQL and milliseconds since the epoch in SQLite
+pub trait SqlTime {
+    fn into_timestamp(self) -> Timestamp;
+}
+
+impl SqlTime for DateTime<Utc> {
+    fn into_timestamp(self) -> Timestamp {
+        Timestamp::from_chrono(self)
+    }
+}
+
+impl SqlTime for i64 {
+    fn into_timestamp(self) -> Timestamp {
+        Timestamp::from_millis(self)
+    }
+}
+
+// A list column, a TEXT[] in PostgreSQL and a JSON array in SQLite
+pub trait SqlList {
+    fn into_vec(self) -> Vec<String>;
+}
+
+impl SqlList for Vec<String> {
+    fn into_vec(self) -> Vec<String> {
+        self
+    }
+}
+
+impl SqlList for Json<Vec<String>> {
+    fn into_vec(self) -> Vec<String> {
+        self.0
+    }
+}
+
+// The rows of the tables, shared by both backends which only differ in the column types
+// of the timestamps and lists, and their mapping to the records of the storage API
+#[derive(FromRow)]
+pub struct AccountRow<T> {
+    pub id: String,
+    pub cluster: String,
+    pub address: String,
+    pub status: String,
+    pub lamports: i64,
+    pub owner: String,
+    pub executable: bool,
+    pub data_length: i64,
+    pub rent_epoch: i64,
+    pub indexed_at: T,
+    pub last_updated_at: T,
+}
+
+impl<T: SqlTime> TryFrom<AccountRow<T>> for Account {
+    type Error = AppError;
+
+    fn try_from(row: AccountRow<T>) -> Result<Self, Self::Error> {
+        Ok(Account {
+            id: row.id,
+            cluster: parse(row.cluster)?,
+            address: row.address,
+            status: parse(row.status)?,
+            lamports: row.lamports,
+            owner: row.owner,
+            executable: row.executable,
+            data_length: row.data_length,
+            rent_epoch: row.rent_epoch,
+            indexed_at: row.indexed_at.into_timestamp(),
+            last_updated_at: row.last_updated_at.into_timestamp(),
+        })
+    }
+}
+
+#[derive(FromRow)]
+pub struct IndexerStatsRow {
+    pub signatures: i64,
+    pub transactions: i64,
+    pub unfinalized_signatures: i64,
+    pub pending_dead_letters: i64,
+    pub failed_dead_letters: i64,
+}
+
+impl From<IndexerStatsRow> for IndexerStats {
+    fn from(row: IndexerStatsRow) -> Self {
+        IndexerStats {
+            account_exists: true,
+            signatures: row.signatures,
+            transactions: row.transactions,
+            unfinalized_signatures: row.unfinalized_signatures,
+            pending_dead_letters: row.pending_dead_letters,
+            failed_dead_letters: row.failed_dead_letters,
+        }
+    }
+}
+
+#[derive(FromRow)]
+pub struct IndexingStateRow<T> {
+    pub id: String,
+    pub cluster: String,
+    pub address: String,
+    pub state: String,
+    pub checkpoint: Option<Json<IndexingCheckpoint>>,
+    pub bounds: Json<IndexingBounds>,
+    pub last_error: Option<Json<IndexingError>>,
+    pub started_at: Option<T>,
+    pub finished_at: Option<T>,
+    pub created_at: T,
+    pub updated_at: T,
+}
+
+impl<T: SqlTime> TryFrom<IndexingStateRow<T>> for AddressIndexingState {
+    type Error = AppError;
+
+    fn try_from(row: IndexingStateRow<T>) -> Result<Self, Self::Error> {
+        Ok(AddressIndexingState {
+            id: row.id,
+            cluster: parse(row.cluster)?,
+            address: row.address,
+            state: parse(row.state)?,
+            checkpoint: row.checkpoint.map(|checkpoint| checkpoint.0),
+            bounds: row.bounds.0,
+            last_error: row.last_error.map(|error| error.0),
+            started_at: row.started_at.map(SqlTime::into_timestamp),
+            finished_at: row.finished_at.map(SqlTime::into_timestamp),
+            created_at: row.created_at.into_timestamp(),
+            updated_at: row.updated_at.into_timestamp(),
+        })
+    }
+}
+
+#[derive(FromRow)]
+pub struct SignatureRow<T> {
+    pub id: String,
+    pub cluster: String,
+    pub signature: String,
+    pub account_address: String,
+    pub slot: i64,
+    pub block_time: Option<i64>,
+    pub confirmation_status: String,
+    pub indexed_at: T,
+}
+
+impl<T: SqlTime> TryFrom<SignatureRow<T>> for TransactionSignature {
+    type Error = AppError;
+
+    fn try_from(row: SignatureRow<T>) -> Result<Self, Self::Error> {
+        Ok(TransactionSignature {
+            id: row.id,
+            cluster: parse(row.cluster)?,
+            signature: row.signature,
+            account_address: row.account_address,
+            slot: row.slot,
+            block_time: row.block_time,
+            confirmation_status: row.confirmation_status,
+            indexed_at: row.indexed_at.into_timestamp(),
+        })
+    }
+}
+
+#[derive(FromRow)]
+pub struct UnfinalizedRow<T> {
+    pub signature: String,
+    pub indexed_at: T,
+}
+
+impl<T: SqlTime> From<UnfinalizedRow<T>> for UnfinalizedSignature {
+    fn from(row: UnfinalizedRow<T>) -> Self {
+        UnfinalizedSignature {
+            signature: row.signature,
+            indexed_at: row.indexed_at.into_timestamp(),
+        }
+    }
+}
+
+#[derive(FromRow)]
+pub struct TransactionRow<T, L> {
+    pub id: String,
+    pub cluster: String,
+    pub signature: String,
+    pub slot: i64,
+    pub block_time: Option<i64>,
+    pub version: Option<String>,
+    pub account_keys: L,
+    pub loaded_addresses: Json<LoadedAddresses>,
+    pub transaction: Json<Value>,
+    pub indexed_at: T,
+}
+
+impl<T: SqlTime, L: SqlList> TryFrom<TransactionRow<T, L>> for Transaction {
+    type Error = AppError;
+
+    fn try_from(row: TransactionRow<T, L>) -> Result<Self, Self::Error> {
+        Ok(Transaction {
+            id: row.id,
+            cluster: parse(row.cluster)?,
+            signature: row.signature,
+            slot: row.slot,
+            block_time: row.block_time,
+            version: row.version,
+            account_keys: row.account_keys.into_vec(),
+            loaded_addresses: row.loaded_addresses.0,
+            transaction: row.transaction.0,
+            indexed_at: row.indexed_at.into_timestamp(),
+        })
+    }
+}
+
+#[derive(FromRow)]
+pub struct JobRow<T> {
+    pub id: String,
+    pub cluster: String,
+    pub address: String,
+    pub kind: String,
+    pub priority: i32,
+    pub status: String,
+    pub enqueued_at: T,
+    pub started_at: Option<T>,
+    pub bounds: Option<Json<IndexingBounds>>,
+}
+
+impl<T: SqlTime> TryFrom<JobRow<T>> for IndexingJob {
+    type Error = AppError;
+
+    fn try_from(row: JobRow<T>) -> Result<Self, Self::Error> {
+        Ok(IndexingJob {
+            id: row.id,
+            cluster: parse(row.cluster)?,
+            address: row.address,
+            kind: parse(row.kind)?,
+            priority: row.priority,
+            status: parse(row.status)?,
+            enqueued_at: row.enqueued_at.into_timestamp(),
+            started_at: row.started_at.map(SqlTime::into_timestamp),
+            bounds: row.bounds.map(|bounds| bounds.0),
+        })
+    }
+}
+
+#[derive(FromRow)]
+pub struct DeadLetterRow<T> {
+    pub id: String,
+    pub cluster: String,
+    pub account_address: String,
+    pub signature: String,
+    pub status: String,
+    pub attempts: i32,
+    pub error: Json<IndexingError>,
+    pub next_attempt_at: T,
+    pub created_at: T,
+    pub updated_at: T,
+}
+
+impl<T: SqlTime> TryFrom<DeadLetterRow<T>> for DeadLetter {
+    type Error = AppError;
+
+    fn try_from(row: DeadLetterRow<T>) -> Result<Self, Self::Error> {
+        Ok(DeadLetter {
+            id: row.id,
+            cluster: parse(row.cluster)?,
+            account_address: row.account_address,
+            signature: row.signature,
+            status: parse(row.status)?,
+            attempts: row.attempts,
+            error: row.error.0,
+            next_attempt_at: row.next_attempt_at.into_timestamp(),
+            created_at: row.created_at.into_timestamp(),
+            updated_at: row.updated_at.into_timestamp(),
+        })
+    }
+}
+
+#[derive(FromRow)]
+pub struct ScheduleRow<T> {
+    pub id: String,
+    pub cluster: String,
+    pub address: String,
+    pub interval_secs: i64,
+    pub last_viewed_at: T,
+    pub next_refresh_at: T,
+}
+
+impl<T: SqlTime> TryFrom<ScheduleRow<T>> for RefreshSchedule {
+    type Error = AppError;
+
+    fn try_from(row: ScheduleRow<T>) -> Result<Self, Self::Error> {
+        Ok(RefreshSchedule {
+            id: row.id,
+            cluster: parse(row.cluster)?,
+            address: row.address,
+            interval_secs: row.interval_secs,
+            last_viewed_at: row.last_viewed_at.into_timestamp(),
+            next_refresh_at: row.next_refresh_at.into_timestamp(),
+        })
+    }
+}
+
+#[derive(FromRow)]
+pub struct WatchRow<T> {
+    pub id: String,
+    pub cluster: String,
+    pub address: String,
+    pub watched_at: T,
+}
+
+impl<T: SqlTime> TryFrom<WatchRow<T>> for WatchedAddress {
+    type Error = AppError;
+
+    fn try_from(row: WatchRow<T>) -> Result<Self, Self::Error> {
+        Ok(WatchedAddress {
+            id: row.id,
+            cluster: parse(row.cluster)?,
+            address: row.address,
+            watched_at: row.watched_at.into_timestamp(),
+        })
+    }
+}
+
+// The column types that differ between the SQL backends
+pub trait SqlBackend: Database {
+    type Time: SqlTime + Send + Unpin + for<'q> Encode<'q, Self> + Type<Self>;
+    type List: SqlList + Send + Unpin;
+
+    fn time(time: Timestamp) -> Self::Time;
+
+    fn rows_affected(result: &Self::QueryResult) -> u64;
+}
+
+impl SqlBackend for Postgres {
+    type Time = DateTime<Utc>;
+    type List = Vec<String>;
+
+    fn time(time: Timestamp) -> Self::Time {
+        time.to_chrono()
+    }
+
+    fn rows_affected(result: &PgQueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
+
+impl SqlBackend for Sqlite {
+    type Time = i64;
+    type List = Json<Vec<String>>;
+
+    fn time(time: Timestamp) -> Self::Time {
+        time.timestamp_millis()
+    }
+
+    fn rows_affected(result: &SqliteQueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
+
+// The queries that both SQL backends run the same way
+// SQLite takes the same $N parameters as PostgreSQL, so only the queries that rely on
+// a feature of one of them (arrays, json_each, upserts, limits) are written per backend
+pub struct SqlQueries<'a, DB: Database> {
+    pub pool: &'a Pool<DB>,
+}
+
+impl<DB> SqlQueries<'_, DB>
+where
+    DB: SqlBackend,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+    for<'q> &'q str: Encode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Type<DB>,
+    for<'q> i64: Encode<'q, DB> + Type<DB>,
+    for<'q> bool: Encode<'q, DB> + Type<DB>,
+    for<'q> Json<&'q IndexingCheckpoint>: Encode<'q, DB> + Type<DB>,
+    for<'q> Json<&'q IndexingBounds>: Encode<'q, DB> + Type<DB>,
+    for<'r> (String,): FromRow<'r, DB::Row>,
+    for<'r> (i64,): FromRow<'r, DB::Row>,
+    for<'r> IndexerStatsRow: FromRow<'r, DB::Row>,
+    for<'r> AccountRow<DB::Time>: FromRow<'r, DB::Row>,
+    for<'r> IndexingStateRow<DB::Time>: FromRow<'r, DB::Row>,
+    for<'r> TransactionRow<DB::Time, DB::List>: FromRow<'r, DB::Row>,
+    for<'r> JobRow<DB::Time>: FromRow<'r, DB::Row>,
+    for<'r> ScheduleRow<DB::Time>: FromRow<'r, DB::Row>,
+{
+    pub async fn get_account(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Option<Account>, AppError> {
+        sqlx::query_as::<_, AccountRow<DB::Time>>("SELECT * FROM accounts WHERE id = $1")
+            .bind(document_id(cluster, address))
+            .fetch_optional(self.pool)
+            .await?
+            .map(Account::try_from)
+            .transpose()
+    }
+
+    pub async fn insert_account(&self, account: &Account) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO accounts (id, cluster, address, status, lamports, owner, executable,
+                data_length, rent_epoch, indexed_at, last_updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
+        )
+        .bind(&account.id)
+        .bind(account.cluster.as_str())
+        .bind(&account.address)
+        .bind(text(&account.status)?)
+        .bind(account.lamports)
+        .bind(&account.owner)
+        .bind(account.executable)
+        .bind(account.data_length)
+        .bind(account.rent_epoch)
+        .bind(DB::time(account.indexed_at))
+        .bind(DB::time(account.last_updated_at))
+        .execute(self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_account(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        account: UpdateAccount,
+    ) -> Result<Account, AppError> {
+        sqlx::query_as::<_, AccountRow<DB::Time>>(
+            "UPDATE accounts SET status = $2, lamports = $3, owner = $4, executable = $5,
+                data_length = $6, rent_epoch = $7, last_updated_at = $8
+            WHERE id = $1
+            RETURNING *",
+        )
+        .bind(document_id(cluster, address))
+        .bind(text(&account.status)?)
+        .bind(account.lamports)
+        .bind(account.owner)
+        .bind(account.executable)
+        .bind(account.data_length)
+        .bind(account.rent_epoch)
+        .bind(DB::time(account.last_updated_at))
+        .fetch_optional(self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Account Not Found".into()))?
+        .try_into()
+    }
+
+    pub async fn get_indexer_stats(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<IndexerStats, AppError> {
+        // A transaction only counts once the signature of the address that it shares its id with
+        // is finalized
+        let stats = sqlx::query_as::<_, IndexerStatsRow>(
+            "SELECT
+                (SELECT COUNT(*) FROM transaction_signatures
+                    WHERE cluster = $2 AND account_address = $3 AND confirmation_status = $4)
+                    AS signatures,
+                (SELECT COUNT(*) FROM address_transactions m
+                    JOIN transaction_signatures s ON s.id = m.id
+                    WHERE m.cluster = $2 AND m.account_address = $3
+                        AND s.confirmation_status = $4)
+                    AS transactions,
+                (SELECT COUNT(*) FROM transaction_signatures
+                    WHERE cluster = $2 AND account_address = $3 AND confirmation_status <> $4)
+                    AS unfinalized_signatures,
+                (SELECT COUNT(*) FROM dead_letters
+                    WHERE cluster = $2 AND account_address = $3 AND status = $5)
+                    AS pending_dead_letters,
+                (SELECT COUNT(*) FROM dead_letters
+                    WHERE cluster = $2 AND account_address = $3 AND status = $6)
+                    AS failed_dead_letters
+            FROM accounts WHERE id = $1",
+        )
+        .bind(document_id(cluster, address))
+        .bind(cluster.as_str())
+        .bind(address)
+        .bind(FINALIZED)
+        .bind(text(&DeadLetterStatus::Pending)?)
+        .bind(text(&DeadLetterStatus::Failed)?)
+        .fetch_optional(self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Account Not Found".into()))?;
+
+        Ok(stats.into())
+    }
+
+    pub async fn get_address_indexing_state(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<AddressIndexingState, AppError> {
+        sqlx::query_as::<_, IndexingStateRow<DB::Time>>(
+            "SELECT * FROM address_indexing_states WHERE id = $1",
+        )
+        .bind(document_id(cluster, address))
+        .fetch_optional(self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Address not found".to_string()))?
+        .try_into()
+    }
+
+    pub async fn get_idle_addresses(&self, cluster: Cluster) -> Result<Vec<String>, AppError> {
+        let addresses = sqlx::query_scalar::<_, String>(
+            "SELECT address FROM address_indexing_states WHERE cluster = $1 AND state = $2",
+        )
+        .bind(cluster.as_str())
+        .bind(text(&IndexingState::Idle)?)
+        .fetch_all(self.pool)
+        .await?;
+
+        Ok(addresses)
+    }
+
+    pub async fn save_indexing_checkpoint(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        checkpoint: &IndexingCheckpoint,
+        updated_at: Timestamp,
+    ) -> Result<(), AppError> {
+        let updated = sqlx::query(
+            "UPDATE address_indexing_states SET checkpoint = $2, updated_at = $3 WHERE id = $1",
+        )
+        .bind(document_id(cluster, address))
+        .bind(Json(checkpoint))
+        .bind(DB::time(updated_at))
+        .execute(self.pool)
+        .await?;
+
+        if DB::rows_affected(&updated) == 0 {
+            return Err(AppError::NotFound("Address Not Found".into()));
+        }
+
+        Ok(())
+    }
+
+    pub async fn save_indexing_bounds(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        bounds: &IndexingBounds,
+        updated_at: Timestamp,
+    ) -> Result<(), AppError> {
+        let updated = sqlx::query(
+            "UPDATE address_indexing_states SET bounds = $2, updated_at = $3 WHERE id = $1",
+        )
+        .bind(document_id(cluster, address))
+        .bind(Json(bounds))
+        .bind(DB::time(updated_at))
+        .execute(self.pool)
+        .await?;
+
+        if DB::rows_affected(&updated) == 0 {
+            return Err(AppError::NotFound("Address Not Found".into()));
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_latest_signature(
+        &self,
+        cluster: Cluster,
+        address: String,
+    ) -> Result<Option<String>, AppError> {
+        let signature = sqlx::query_scalar::<_, String>(
+            "SELECT signature FROM transaction_signatures
+            WHERE cluster = $1 AND account_address = $2
+            ORDER BY slot DESC
+            LIMIT 1",
+        )
+        .bind(cluster.as_str())
+        .bind(address)
+        .fetch_optional(self.pool)
+        .await?;
+
+        Ok(signature)
+    }
+
+    pub async fn get_oldest_signature(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Option<String>, AppError> {
+        let signature = sqlx::query_scalar::<_, String>(
+            "SELECT signature FROM transaction_signatures
+            WHERE cluster = $1 AND account_address = $2
+            ORDER BY slot
+            LIMIT 1",
+        )
+        .bind(cluster.as_str())
+        .bind(address)
+        .fetch_optional(self.pool)
+        .await?;
+
+        Ok(signature)
+    }
+
+    pub async fn get_signatures_count(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<u64, AppError> {
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM transaction_signatures WHERE cluster = $1 AND account_address = $2",
+        )
+        .bind(cluster.as_str())
+        .bind(address)
+        .fetch_one(self.pool)
+        .await?;
+
+        Ok(count as u64)
+    }
+
+    pub async fn finalize_signatures(
+        &self,
+        cluster: Cluster,
+        finalized: &[(String, i64)],
+    ) -> Result<(), AppError> {
+        if finalized.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self.pool.begin().await?;
+        for (signature, slot) in finalized {
+            sqlx::query(
+                "UPDATE transaction_signatures SET confirmation_status = $3, slot = $4
+                WHERE cluster = $1 AND signature = $2",
+            )
+            .bind(cluster.as_str())
+            .bind(signature)
+            .bind(FINALIZED)
+            .bind(slot)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query(
+                "UPDATE address_transactions SET slot = $3 WHERE cluster = $1 AND signature = $2",
+            )
+            .bind(cluster.as_str())
+            .bind(signature)
+            .bind(slot)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query("UPDATE transactions SET slot = $2 WHERE id = $1")
+                .bind(document_id(cluster, signature))
+                .bind(slot)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn get_transaction(
+        &self,
+        cluster: Cluster,
+        address: String,
+        signature: String,
+    ) -> Result<Option<Transaction>, AppError> {
+        // The transaction has to belong to the address
+        sqlx::query_as::<_, TransactionRow<DB::Time, DB::List>>(
+            "SELECT * FROM transactions
+            WHERE id = $1 AND EXISTS (SELECT 1 FROM address_transactions WHERE id = $2)",
+        )
+        .bind(document_id(cluster, &signature))
+        .bind(membership_id(cluster, &address, &signature))
+        .fetch_optional(self.pool)
+        .await?
+        .map(Transaction::try_from)
+        .transpose()
+    }
+
+    pub async fn get_transactions_count(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<u64, AppError> {
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM address_transactions WHERE cluster = $1 AND account_address = $2",
+        )
+        .bind(cluster.as_str())
+        .bind(address)
+        .fetch_one(self.pool)
+        .await?;
+
+        Ok(count as u64)
+    }
+
+    pub async fn get_signatures_without_transactions(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Vec<String>, AppError> {
+        // A link and a dead letter share their id with the signature of the address
+        let signatures = sqlx::query_scalar::<_, String>(
+            "SELECT s.signature FROM transaction_signatures s
+            WHERE s.cluster = $1 AND s.account_address = $2
+                AND NOT EXISTS (SELECT 1 FROM address_transactions m WHERE m.id = s.id)
+                AND NOT EXISTS (SELECT 1 FROM dead_letters d WHERE d.id = s.id)
+            ORDER BY s.slot DESC",
+        )
+        .bind(cluster.as_str())
+        .bind(address)
+        .fetch_all(self.pool)
+        .await?;
+
+        Ok(signatures)
+    }
+
+    pub async fn get_pending_jobs(&self) -> Result<Vec<IndexingJob>, AppError> {
+        sqlx::query_as::<_, JobRow<DB::Time>>(
+            "SELECT * FROM indexing_jobs WHERE status = $1 ORDER BY priority DESC, enqueued_at",
+        )
+        .bind(text(&JobStatus::Pending)?)
+        .fetch_all(self.pool)
+        .await?
+        .into_iter()
+        .map(IndexingJob::try_from)
+        .collect()
+    }
+
+    pub async fn delete_job(&self, id: &str) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM indexing_jobs WHERE id = $1")
+            .bind(id)
+            .execute(self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_pending_job(&self, id: &str) -> Result<bool, AppError> {
+        let deleted = sqlx::query("DELETE FROM indexing_jobs WHERE id = $1 AND status = $2")
+            .bind(id)
+            .bind(text(&JobStatus::Pending)?)
+            .execute(self.pool)
+            .await?;
+
+        Ok(DB::rows_affected(&deleted) > 0)
+    }
+
+    pub async fn reset_running_jobs(&self) -> Result<u64, AppError> {
+        let updated = sqlx::query(
+            "UPDATE indexing_jobs SET status = $2, started_at = NULL WHERE status = $1",
+        )
+        .bind(text(&JobStatus::Running)?)
+        .bind(text(&JobStatus::Pending)?)
+        .execute(self.pool)
+        .await?;
+
+        Ok(DB::rows_affected(&updated))
+    }
+
+    pub async fn delete_dead_letter(&self, id: &str) -> Result<(), AppError> {
+        sqlx::query("DELETE FROM dead_letters WHERE id = $1")
+            .bind(id)
+            .execute(self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_refresh_schedule(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Option<RefreshSchedule>, AppError> {
+        sqlx::query_as::<_, ScheduleRow<DB::Time>>("SELECT * FROM refresh_schedules WHERE id = $1")
+            .bind(document_id(cluster, address))
+            .fetch_optional(self.pool)
+            .await?
+            .map(RefreshSchedule::try_from)
+            .transpose()
+    }
+
+    pub async fn reschedule_refresh(
+        &self,
+        id: &str,
+        next_refresh_at: Timestamp,
+    ) -> Result<(), AppError> {
+        sqlx::query("UPDATE refresh_schedules SET next_refresh_at = $2 WHERE id = $1")
+            .bind(id)
+            .bind(DB::time(next_refresh_at))
+            .execute(self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_watched_address(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<bool, AppError> {
+        let deleted = sqlx::query("DELETE FROM watched_addresses WHERE id = $1")
+            .bind(document_id(cluster, address))
+            .execute(self.pool)
+            .await?;
+
+        Ok(DB::rows_affected(&deleted) > 0)
+    }
+}
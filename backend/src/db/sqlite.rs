@@ -0,0 +1,833 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::{
+    SqlitePool, Transaction as SqlTransaction,
+    query::Query,
+    sqlite::{Sqlite, SqliteArguments, SqliteConnectOptions, SqlitePoolOptions},
+    types::Json,
+};
+
+use crate::cluster::Cluster;
+use crate::db::sql::{self, SqlQueries, text};
+use crate::db::{
+    AccountStore, DeadLetterStore, IndexingStateStore, JobStore, ScheduleStore, SignatureStore,
+    TransactionStore, WatchStore,
+};
+use crate::error::AppError;
+use crate::models::{
+    Account, AddressIndexingState, DeadLetter, DeadLetterStatus, FINALIZED, IndexerStats,
+    IndexingBounds, IndexingCheckpoint, IndexingError, IndexingJob, IndexingState, JobStatus,
+    RefreshSchedule, Timestamp, Transaction, TransactionSignature, UnfinalizedSignature,
+    UpdateAccount, UpdateAddressIndexingState, UpsertCounts, Upserted, WatchedAddress, document_id,
+    membership_id,
+};
+
+// The storage embedded in the process, everything is kept in a single local SQLite file
+// so the backend runs without any external service
+#[derive(Debug, Clone)]
+pub struct SqliteStorage {
+    pool: SqlitePool,
+}
+
+impl SqliteStorage {
+    // Open the database file, creating it on the first run, and bring its schema up to date
+    pub async fn init() -> Result<Self, AppError> {
+        let path = std::env::var("SQLITE_PATH").unwrap_or_else(|_| "solwatch.db".to_string());
+
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        Self::open(SqlitePoolOptions::new(), options).await
+    }
+
+    // Connect to the database and bring its schema up to date
+    async fn open(
+        pool: SqlitePoolOptions,
+        options: SqliteConnectOptions,
+    ) -> Result<Self, AppError> {
+        let pool = pool.connect_with(options).await?;
+        sqlx::migrate!("./migrations/sqlite").run(&pool).await?;
+
+        Ok(SqliteStorage { pool })
+    }
+
+    // A database that only lives as long as the storage, for the tests
+    // The pool keeps its single connection open since the database goes away with it
+    #[cfg(test)]
+    pub async fn in_memory() -> Result<Self, AppError> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None);
+        Self::open(pool, SqliteConnectOptions::new().in_memory(true)).await
+    }
+
+    fn queries(&self) -> SqlQueries<'_, Sqlite> {
+        SqlQueries { pool: &self.pool }
+    }
+}
+
+// A list of values bound as JSON text and matched through json_each
+fn list<T: Serialize>(items: &[T]) -> Result<String, AppError> {
+    Ok(serde_json::to_string(items)?)
+}
+
+// A limit of 0 means no limit like it does for a Mongo find, as does a negative one in SQLite
+fn limit(limit: i64) -> i64 {
+    if limit == 0 { -1 } else { limit.abs() }
+}
+
+// Insert the row when it is new, otherwise run the update that only matches a changed row
+// The insert comes first so the transaction holds the write lock from its first statement
+async fn upsert<'q>(
+    tx: &mut SqlTransaction<'_, Sqlite>,
+    insert: Query<'q, Sqlite, SqliteArguments<'q>>,
+    update: Query<'q, Sqlite, SqliteArguments<'q>>,
+) -> Result<Upserted, AppError> {
+    if insert.execute(&mut **tx).await?.rows_affected() > 0 {
+        return Ok(Upserted::Inserted);
+    }
+
+    if update.execute(&mut **tx).await?.rows_affected() > 0 {
+        Ok(Upserted::Updated)
+    } else {
+        Ok(Upserted::Skipped)
+    }
+}
+
+// The shared rows with their timestamps stored as milliseconds
+type DeadLetterRow = sql::DeadLetterRow<i64>;
+type IndexingStateRow = sql::IndexingStateRow<i64>;
+type JobRow = sql::JobRow<i64>;
+type ScheduleRow = sql::ScheduleRow<i64>;
+type SignatureRow = sql::SignatureRow<i64>;
+type UnfinalizedRow = sql::UnfinalizedRow<i64>;
+type WatchRow = sql::WatchRow<i64>;
+type TransactionRow = sql::TransactionRow<i64, Json<Vec<String>>>;
+
+#[async_trait]
+impl AccountStore for SqliteStorage {
+    async fn get_account(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Option<Account>, AppError> {
+        self.queries().get_account(cluster, address).await
+    }
+
+    async fn insert_account(&self, account: &Account) -> Result<(), AppError> {
+        self.queries().insert_account(account).await
+    }
+
+    async fn update_account(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        account: UpdateAccount,
+    ) -> Result<Account, AppError> {
+        self.queries()
+            .update_account(cluster, address, account)
+            .await
+    }
+
+    async fn get_indexer_stats(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<IndexerStats, AppError> {
+        self.queries().get_indexer_stats(cluster, address).await
+    }
+}
+
+#[async_trait]
+impl IndexingStateStore for SqliteStorage {
+    async fn get_address_indexing_state(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<AddressIndexingState, AppError> {
+        self.queries()
+            .get_address_indexing_state(cluster, address)
+            .await
+    }
+
+    async fn insert_address_indexing_state(
+        &self,
+        record: AddressIndexingState,
+    ) -> Result<bool, AppError> {
+        let inserted = sqlx::query(
+            "INSERT INTO address_indexing_states (id, cluster, address, state, checkpoint,
+                bounds, last_error, started_at, finished_at, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+            ON CONFLICT (id) DO NOTHING",
+        )
+        .bind(&record.id)
+        .bind(record.cluster.as_str())
+        .bind(&record.address)
+        .bind(text(&record.state)?)
+        .bind(record.checkpoint.map(Json))
+        .bind(Json(record.bounds))
+        .bind(record.last_error.map(Json))
+        .bind(record.started_at.map(|time| time.timestamp_millis()))
+        .bind(record.finished_at.map(|time| time.timestamp_millis()))
+        .bind(record.created_at.timestamp_millis())
+        .bind(record.updated_at.timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(inserted.rows_affected() > 0)
+    }
+
+    async fn update_address_indexing_state(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        update: UpdateAddressIndexingState,
+    ) -> Result<(), AppError> {
+        let sources = IndexingState::sources(update.state)
+            .iter()
+            .map(text)
+            .collect::<Result<Vec<_>, _>>()?;
+        let running = update.state.is_running();
+        let finished = !running && update.state != IndexingState::Queued;
+
+        // The update only matches when the address is in a state that can move to the next one
+        let updated = sqlx::query(
+            "UPDATE address_indexing_states SET
+                state = ?3,
+                checkpoint = ?4,
+                last_error = ?5,
+                updated_at = ?6,
+                started_at = CASE WHEN ?7 THEN ?6 ELSE started_at END,
+                finished_at = CASE WHEN ?7 THEN NULL WHEN ?8 THEN ?6 ELSE finished_at END
+            WHERE id = ?1 AND state IN (SELECT value FROM json_each(?2))",
+        )
+        .bind(document_id(cluster, address))
+        .bind(list(&sources)?)
+        .bind(text(&update.state)?)
+        .bind(update.checkpoint.map(Json))
+        .bind(update.last_error.map(Json))
+        .bind(update.updated_at.timestamp_millis())
+        .bind(running)
+        .bind(finished)
+        .execute(&self.pool)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            // Tell an illegal transition apart from a missing address
+            let current = self.get_address_indexing_state(cluster, address).await?;
+            return Err(AppError::BadRequest(format!(
+                "Address can't move from {} to {}",
+                current.state, update.state
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn get_unfinished_address_states(&self) -> Result<Vec<AddressIndexingState>, AppError> {
+        let states = [
+            IndexingState::Queued,
+            IndexingState::Indexing,
+            IndexingState::Syncing,
+        ];
+
+        sqlx::query_as::<_, IndexingStateRow>(
+            "SELECT * FROM address_indexing_states
+            WHERE state IN (SELECT value FROM json_each(?1))",
+        )
+        .bind(list(&states)?)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(AddressIndexingState::try_from)
+        .collect()
+    }
+
+    async fn get_idle_addresses(&self, cluster: Cluster) -> Result<Vec<String>, AppError> {
+        self.queries().get_idle_addresses(cluster).await
+    }
+
+    async fn save_indexing_checkpoint(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        checkpoint: &IndexingCheckpoint,
+        updated_at: Timestamp,
+    ) -> Result<(), AppError> {
+        self.queries()
+            .save_indexing_checkpoint(cluster, address, checkpoint, updated_at)
+            .await
+    }
+
+    async fn save_indexing_bounds(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        bounds: &IndexingBounds,
+        updated_at: Timestamp,
+    ) -> Result<(), AppError> {
+        self.queries()
+            .save_indexing_bounds(cluster, address, bounds, updated_at)
+            .await
+    }
+}
+
+#[async_trait]
+impl SignatureStore for SqliteStorage {
+    async fn upsert_transaction_signatures(
+        &self,
+        signatures: &[TransactionSignature],
+    ) -> Result<UpsertCounts, AppError> {
+        let mut counts = UpsertCounts::default();
+        let mut tx = self.pool.begin().await?;
+        for signature in signatures {
+            let insert = sqlx::query(
+                "INSERT INTO transaction_signatures (id, cluster, signature, account_address, slot,
+                    block_time, confirmation_status, indexed_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                ON CONFLICT (id) DO NOTHING",
+            )
+            .bind(&signature.id)
+            .bind(signature.cluster.as_str())
+            .bind(&signature.signature)
+            .bind(&signature.account_address)
+            .bind(signature.slot)
+            .bind(signature.block_time)
+            .bind(&signature.confirmation_status)
+            .bind(signature.indexed_at.timestamp_millis());
+
+            // Only a changed slot, block time or confirmation status is written
+            let update = sqlx::query(
+                "UPDATE transaction_signatures SET slot = ?2, block_time = ?3,
+                    confirmation_status = ?4
+                WHERE id = ?1
+                    AND (slot, block_time, confirmation_status) IS NOT (?2, ?3, ?4)",
+            )
+            .bind(&signature.id)
+            .bind(signature.slot)
+            .bind(signature.block_time)
+            .bind(&signature.confirmation_status);
+
+            counts.add(upsert(&mut tx, insert, update).await?);
+        }
+        tx.commit().await?;
+
+        Ok(counts)
+    }
+
+    async fn get_transaction_signatures(
+        &self,
+        cluster: Cluster,
+        address: String,
+        skip: u64,
+        limit: i64,
+    ) -> Result<Vec<TransactionSignature>, AppError> {
+        sqlx::query_as::<_, SignatureRow>(
+            "SELECT * FROM transaction_signatures
+            WHERE cluster = ?1 AND account_address = ?2
+            ORDER BY slot DESC
+            LIMIT ?4 OFFSET ?3",
+        )
+        .bind(cluster.as_str())
+        .bind(address)
+        .bind(skip as i64)
+        .bind(self::limit(limit))
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(TransactionSignature::try_from)
+        .collect()
+    }
+
+    async fn get_latest_signature(
+        &self,
+        cluster: Cluster,
+        address: String,
+    ) -> Result<Option<String>, AppError> {
+        self.queries().get_latest_signature(cluster, address).await
+    }
+
+    async fn get_oldest_signature(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Option<String>, AppError> {
+        self.queries().get_oldest_signature(cluster, address).await
+    }
+
+    async fn get_stored_signatures(
+        &self,
+        cluster: Cluster,
+        address: &str,
+        signatures: &[String],
+    ) -> Result<HashSet<String>, AppError> {
+        let stored = sqlx::query_scalar::<_, String>(
+            "SELECT signature FROM transaction_signatures
+            WHERE cluster = ?1 AND account_address = ?2
+                AND signature IN (SELECT value FROM json_each(?3))",
+        )
+        .bind(cluster.as_str())
+        .bind(address)
+        .bind(list(signatures)?)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(stored.into_iter().collect())
+    }
+
+    async fn get_signatures_count(&self, cluster: Cluster, address: &str) -> Result<u64, AppError> {
+        self.queries().get_signatures_count(cluster, address).await
+    }
+
+    async fn get_unfinalized_signatures(
+        &self,
+        cluster: Cluster,
+        limit: i64,
+    ) -> Result<Vec<UnfinalizedSignature>, AppError> {
+        let rows = sqlx::query_as::<_, UnfinalizedRow>(
+            "SELECT signature, indexed_at FROM transaction_signatures
+            WHERE cluster = ?1 AND confirmation_status <> ?2
+            ORDER BY indexed_at
+            LIMIT ?3",
+        )
+        .bind(cluster.as_str())
+        .bind(FINALIZED)
+        .bind(self::limit(limit))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(UnfinalizedSignature::from).collect())
+    }
+
+    async fn finalize_signatures(
+        &self,
+        cluster: Cluster,
+        finalized: &[(String, i64)],
+    ) -> Result<(), AppError> {
+        self.queries().finalize_signatures(cluster, finalized).await
+    }
+
+    async fn remove_signatures(
+        &self,
+        cluster: Cluster,
+        signatures: &[String],
+    ) -> Result<(), AppError> {
+        if signatures.is_empty() {
+            return Ok(());
+        }
+
+        let signatures = list(signatures)?;
+        let mut tx = self.pool.begin().await?;
+        for table in [
+            "transaction_signatures",
+            "address_transactions",
+            "dead_letters",
+            "transactions",
+        ] {
+            sqlx::query(&format!(
+                "DELETE FROM {table}
+                WHERE cluster = ?1 AND signature IN (SELECT value FROM json_each(?2))"
+            ))
+            .bind(cluster.as_str())
+            .bind(&signatures)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TransactionStore for SqliteStorage {
+    async fn upsert_transactions(
+        &self,
+        address: &str,
+        txns: &[Transaction],
+    ) -> Result<UpsertCounts, AppError> {
+        let mut counts = UpsertCounts::default();
+        let mut tx = self.pool.begin().await?;
+        for txn in txns {
+            let account_keys = list(&txn.account_keys)?;
+            let loaded_addresses = serde_json::to_string(&txn.loaded_addresses)?;
+            let transaction = serde_json::to_string(&txn.transaction)?;
+
+            let insert = sqlx::query(
+                "INSERT INTO transactions (id, cluster, signature, slot, block_time, version,
+                    account_keys, loaded_addresses, \"transaction\", indexed_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                ON CONFLICT (id) DO NOTHING",
+            )
+            .bind(&txn.id)
+            .bind(txn.cluster.as_str())
+            .bind(&txn.signature)
+            .bind(txn.slot)
+            .bind(txn.block_time)
+            .bind(&txn.version)
+            .bind(&account_keys)
+            .bind(&loaded_addresses)
+            .bind(&transaction)
+            .bind(txn.indexed_at.timestamp_millis());
+
+            let update = sqlx::query(
+                "UPDATE transactions SET slot = ?2, block_time = ?3, version = ?4,
+                    account_keys = ?5, loaded_addresses = ?6, \"transaction\" = ?7
+                WHERE id = ?1
+                    AND (slot, block_time, version, account_keys, loaded_addresses, \"transaction\")
+                        IS NOT (?2, ?3, ?4, ?5, ?6, ?7)",
+            )
+            .bind(&txn.id)
+            .bind(txn.slot)
+            .bind(txn.block_time)
+            .bind(&txn.version)
+            .bind(&account_keys)
+            .bind(&loaded_addresses)
+            .bind(&transaction);
+
+            let body = upsert(&mut tx, insert, update).await?;
+
+            // Link the transaction to the address
+            let id = membership_id(txn.cluster, address, &txn.signature);
+            let insert = sqlx::query(
+                "INSERT INTO address_transactions (id, cluster, account_address, signature, slot,
+                    block_time, indexed_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                ON CONFLICT (id) DO NOTHING",
+            )
+            .bind(&id)
+            .bind(txn.cluster.as_str())
+            .bind(address)
+            .bind(&txn.signature)
+            .bind(txn.slot)
+            .bind(txn.block_time)
+            .bind(txn.indexed_at.timestamp_millis());
+
+            let update = sqlx::query(
+                "UPDATE address_transactions SET slot = ?2, block_time = ?3
+                WHERE id = ?1 AND (slot, block_time) IS NOT (?2, ?3)",
+            )
+            .bind(&id)
+            .bind(txn.slot)
+            .bind(txn.block_time);
+
+            let link = upsert(&mut tx, insert, update).await?;
+            counts.add_transaction(body, link);
+        }
+        tx.commit().await?;
+
+        Ok(counts)
+    }
+
+    async fn get_transactions(
+        &self,
+        cluster: Cluster,
+        address: String,
+        skip: u64,
+        limit: i64,
+    ) -> Result<Vec<Transaction>, AppError> {
+        // Page through the transactions linked to the address, then load their bodies
+        sqlx::query_as::<_, TransactionRow>(
+            "SELECT t.* FROM (
+                SELECT signature, slot FROM address_transactions
+                WHERE cluster = ?1 AND account_address = ?2
+                ORDER BY slot DESC
+                LIMIT ?4 OFFSET ?3
+            ) m
+            JOIN transactions t ON t.cluster = ?1 AND t.signature = m.signature
+            ORDER BY m.slot DESC",
+        )
+        .bind(cluster.as_str())
+        .bind(address)
+        .bind(skip as i64)
+        .bind(self::limit(limit))
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(Transaction::try_from)
+        .collect()
+    }
+
+    async fn get_transaction(
+        &self,
+        cluster: Cluster,
+        address: String,
+        signature: String,
+    ) -> Result<Option<Transaction>, AppError> {
+        self.queries()
+            .get_transaction(cluster, address, signature)
+            .await
+    }
+
+    async fn get_transactions_count(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<u64, AppError> {
+        self.queries()
+            .get_transactions_count(cluster, address)
+            .await
+    }
+
+    async fn get_signatures_without_transactions(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Vec<String>, AppError> {
+        self.queries()
+            .get_signatures_without_transactions(cluster, address)
+            .await
+    }
+}
+
+#[async_trait]
+impl JobStore for SqliteStorage {
+    async fn enqueue_job(&self, job: &IndexingJob) -> Result<IndexingJob, AppError> {
+        // A queued job of the address only takes the higher priority of the new one
+        sqlx::query_as::<_, JobRow>(
+            "INSERT INTO indexing_jobs (id, cluster, address, kind, priority, status, enqueued_at,
+                started_at, bounds)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            ON CONFLICT (id) DO UPDATE SET
+                priority = MAX(indexing_jobs.priority, excluded.priority)
+            RETURNING *",
+        )
+        .bind(&job.id)
+        .bind(job.cluster.as_str())
+        .bind(&job.address)
+        .bind(text(&job.kind)?)
+        .bind(job.priority)
+        .bind(text(&job.status)?)
+        .bind(job.enqueued_at.timestamp_millis())
+        .bind(job.started_at.map(|time| time.timestamp_millis()))
+        .bind(job.bounds.as_ref().map(Json))
+        .fetch_one(&self.pool)
+        .await?
+        .try_into()
+    }
+
+    async fn claim_next_job(&self) -> Result<Option<IndexingJob>, AppError> {
+        // SQLite runs one write at a time so no two workers can claim the same job
+        sqlx::query_as::<_, JobRow>(
+            "UPDATE indexing_jobs SET status = ?2, started_at = ?3
+            WHERE id = (
+                SELECT id FROM indexing_jobs
+                WHERE status = ?1
+                ORDER BY priority DESC, enqueued_at
+                LIMIT 1
+            )
+            RETURNING *",
+        )
+        .bind(text(&JobStatus::Pending)?)
+        .bind(text(&JobStatus::Running)?)
+        .bind(Timestamp::now().timestamp_millis())
+        .fetch_optional(&self.pool)
+        .await?
+        .map(IndexingJob::try_from)
+        .transpose()
+    }
+
+    async fn get_pending_jobs(&self) -> Result<Vec<IndexingJob>, AppError> {
+        self.queries().get_pending_jobs().await
+    }
+
+    async fn delete_job(&self, id: &str) -> Result<(), AppError> {
+        self.queries().delete_job(id).await
+    }
+
+    async fn delete_pending_job(&self, id: &str) -> Result<bool, AppError> {
+        self.queries().delete_pending_job(id).await
+    }
+
+    async fn reset_running_jobs(&self) -> Result<u64, AppError> {
+        self.queries().reset_running_jobs().await
+    }
+}
+
+#[async_trait]
+impl DeadLetterStore for SqliteStorage {
+    async fn record_dead_letter(&self, dead_letter: &DeadLetter) -> Result<(), AppError> {
+        // A recorded signature counts the failed attempt and keeps its retry schedule
+        sqlx::query(
+            "INSERT INTO dead_letters (id, cluster, account_address, signature, status, attempts,
+                error, next_attempt_at, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            ON CONFLICT (id) DO UPDATE SET
+                attempts = dead_letters.attempts + excluded.attempts,
+                error = excluded.error,
+                updated_at = excluded.updated_at",
+        )
+        .bind(&dead_letter.id)
+        .bind(dead_letter.cluster.as_str())
+        .bind(&dead_letter.account_address)
+        .bind(&dead_letter.signature)
+        .bind(text(&dead_letter.status)?)
+        .bind(dead_letter.attempts)
+        .bind(Json(&dead_letter.error))
+        .bind(dead_letter.next_attempt_at.timestamp_millis())
+        .bind(dead_letter.created_at.timestamp_millis())
+        .bind(dead_letter.updated_at.timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_due_dead_letters(
+        &self,
+        cluster: Cluster,
+        limit: i64,
+    ) -> Result<Vec<DeadLetter>, AppError> {
+        sqlx::query_as::<_, DeadLetterRow>(
+            "SELECT * FROM dead_letters
+            WHERE cluster = ?1 AND status = ?2 AND next_attempt_at <= ?3
+            ORDER BY next_attempt_at
+            LIMIT ?4",
+        )
+        .bind(cluster.as_str())
+        .bind(text(&DeadLetterStatus::Pending)?)
+        .bind(Timestamp::now().timestamp_millis())
+        .bind(self::limit(limit))
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(DeadLetter::try_from)
+        .collect()
+    }
+
+    async fn update_dead_letter(
+        &self,
+        id: &str,
+        status: DeadLetterStatus,
+        error: &IndexingError,
+        next_attempt_at: Timestamp,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "UPDATE dead_letters SET attempts = attempts + 1, status = ?2, error = ?3,
+                next_attempt_at = ?4, updated_at = ?5
+            WHERE id = ?1",
+        )
+        .bind(id)
+        .bind(text(&status)?)
+        .bind(Json(error))
+        .bind(next_attempt_at.timestamp_millis())
+        .bind(Timestamp::now().timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_dead_letter(&self, id: &str) -> Result<(), AppError> {
+        self.queries().delete_dead_letter(id).await
+    }
+}
+
+#[async_trait]
+impl ScheduleStore for SqliteStorage {
+    async fn get_refresh_schedule(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<Option<RefreshSchedule>, AppError> {
+        self.queries().get_refresh_schedule(cluster, address).await
+    }
+
+    async fn save_refresh_schedule(&self, schedule: &RefreshSchedule) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO refresh_schedules (id, cluster, address, interval_secs, last_viewed_at,
+                next_refresh_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            ON CONFLICT (id) DO UPDATE SET
+                interval_secs = excluded.interval_secs,
+                last_viewed_at = excluded.last_viewed_at,
+                next_refresh_at = excluded.next_refresh_at",
+        )
+        .bind(&schedule.id)
+        .bind(schedule.cluster.as_str())
+        .bind(&schedule.address)
+        .bind(schedule.interval_secs)
+        .bind(schedule.last_viewed_at.timestamp_millis())
+        .bind(schedule.next_refresh_at.timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_due_refresh_schedules(
+        &self,
+        clusters: &[Cluster],
+        limit: i64,
+    ) -> Result<Vec<RefreshSchedule>, AppError> {
+        sqlx::query_as::<_, ScheduleRow>(
+            "SELECT * FROM refresh_schedules
+            WHERE cluster IN (SELECT value FROM json_each(?1)) AND next_refresh_at <= ?2
+            ORDER BY next_refresh_at
+            LIMIT ?3",
+        )
+        .bind(list(clusters)?)
+        .bind(Timestamp::now().timestamp_millis())
+        .bind(self::limit(limit))
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(RefreshSchedule::try_from)
+        .collect()
+    }
+
+    async fn reschedule_refresh(
+        &self,
+        id: &str,
+        next_refresh_at: Timestamp,
+    ) -> Result<(), AppError> {
+        self.queries().reschedule_refresh(id, next_refresh_at).await
+    }
+}
+
+#[async_trait]
+impl WatchStore for SqliteStorage {
+    async fn save_watched_address(&self, watch: &WatchedAddress) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO watched_addresses (id, cluster, address, watched_at)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (id) DO UPDATE SET watched_at = excluded.watched_at",
+        )
+        .bind(&watch.id)
+        .bind(watch.cluster.as_str())
+        .bind(&watch.address)
+        .bind(watch.watched_at.timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_watched_address(
+        &self,
+        cluster: Cluster,
+        address: &str,
+    ) -> Result<bool, AppError> {
+        self.queries()
+            .delete_watched_address(cluster, address)
+            .await
+    }
+
+    async fn get_watched_addresses(
+        &self,
+        clusters: &[Cluster],
+    ) -> Result<Vec<WatchedAddress>, AppError> {
+        sqlx::query_as::<_, WatchRow>(
+            "SELECT * FROM watched_addresses WHERE cluster IN (SELECT value FROM json_each(?1))",
+        )
+        .bind(list(clusters)?)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(WatchedAddress::try_from)
+        .collect()
+    }
+}
@@ -0,0 +1,416 @@
+// The behaviour every storage backend has to share, run against the in-memory storage
+// and an in-memory SQLite database (MongoDB and PostgreSQL need a running server)
+use serde_json::json;
+
+use super::*;
+use crate::models::{
+    AccountStatus, FINALIZED, IndexingState, JobKind, JobPriority, JobStatus, document_id,
+    membership_id,
+};
+
+const ADDRESS: &str = "address";
+const OTHER_ADDRESS: &str = "other-address";
+const THIRD_ADDRESS: &str = "third-address";
+
+fn indexing_state(state: IndexingState) -> AddressIndexingState {
+    AddressIndexingState {
+        id: document_id(Cluster::Devnet, ADDRESS),
+        cluster: Cluster::Devnet,
+        address: ADDRESS.to_string(),
+        state,
+        checkpoint: None,
+        bounds: IndexingBounds::default(),
+        last_error: None,
+        started_at: None,
+        finished_at: None,
+        created_at: Timestamp::now(),
+        updated_at: Timestamp::now(),
+    }
+}
+
+fn move_to(state: IndexingState) -> UpdateAddressIndexingState {
+    UpdateAddressIndexingState {
+        state,
+        checkpoint: None,
+        last_error: None,
+        updated_at: Timestamp::now(),
+    }
+}
+
+fn account() -> Account {
+    Account {
+        id: document_id(Cluster::Devnet, ADDRESS),
+        cluster: Cluster::Devnet,
+        address: ADDRESS.to_string(),
+        status: AccountStatus::Active,
+        lamports: 1,
+        owner: "owner".to_string(),
+        executable: false,
+        data_length: 0,
+        rent_epoch: 0,
+        indexed_at: Timestamp::now(),
+        last_updated_at: Timestamp::now(),
+    }
+}
+
+fn signature(signature: &str, slot: i64, confirmation_status: &str) -> TransactionSignature {
+    TransactionSignature {
+        id: membership_id(Cluster::Devnet, ADDRESS, signature),
+        cluster: Cluster::Devnet,
+        signature: signature.to_string(),
+        account_address: ADDRESS.to_string(),
+        slot,
+        block_time: Some(slot * 10),
+        confirmation_status: confirmation_status.to_string(),
+        indexed_at: Timestamp::now(),
+    }
+}
+
+fn transaction(signature: &str, slot: i64) -> Transaction {
+    Transaction {
+        id: document_id(Cluster::Devnet, signature),
+        cluster: Cluster::Devnet,
+        signature: signature.to_string(),
+        slot,
+        block_time: Some(slot * 10),
+        version: Some("legacy".to_string()),
+        account_keys: vec![ADDRESS.to_string()],
+        loaded_addresses: Default::default(),
+        transaction: json!({"signatures": [signature]}),
+        indexed_at: Timestamp::now(),
+    }
+}
+
+fn job(address: &str, priority: JobPriority, enqueued_at: i64) -> IndexingJob {
+    IndexingJob {
+        id: document_id(Cluster::Devnet, address),
+        cluster: Cluster::Devnet,
+        address: address.to_string(),
+        kind: JobKind::Index,
+        priority: priority.rank(),
+        status: JobStatus::Pending,
+        enqueued_at: Timestamp::from_millis(enqueued_at),
+        started_at: None,
+        bounds: None,
+    }
+}
+
+fn dead_letter(signature: &str) -> DeadLetter {
+    DeadLetter {
+        id: membership_id(Cluster::Devnet, ADDRESS, signature),
+        cluster: Cluster::Devnet,
+        account_address: ADDRESS.to_string(),
+        signature: signature.to_string(),
+        status: DeadLetterStatus::Pending,
+        attempts: 1,
+        error: IndexingError {
+            kind: "solana".to_string(),
+            message: "Transaction not available".to_string(),
+            occurred_at: Timestamp::now(),
+        },
+        next_attempt_at: Timestamp::now(),
+        created_at: Timestamp::now(),
+        updated_at: Timestamp::now(),
+    }
+}
+
+fn counts(counts: UpsertCounts) -> (u64, u64, u64) {
+    (counts.inserted, counts.updated, counts.skipped)
+}
+
+async fn only_legal_transitions_are_applied(db: &dyn Storage) {
+    assert!(
+        db.insert_address_indexing_state(indexing_state(IndexingState::Indexing))
+            .await
+            .unwrap()
+    );
+    // An existing record is never overwritten
+    assert!(
+        !db.insert_address_indexing_state(indexing_state(IndexingState::Failed))
+            .await
+            .unwrap()
+    );
+
+    db.update_address_indexing_state(Cluster::Devnet, ADDRESS, move_to(IndexingState::Idle))
+        .await
+        .unwrap();
+    let rejected = db
+        .update_address_indexing_state(Cluster::Devnet, ADDRESS, move_to(IndexingState::Paused))
+        .await;
+    assert!(matches!(rejected, Err(AppError::BadRequest(_))));
+
+    let stored = db
+        .get_address_indexing_state(Cluster::Devnet, ADDRESS)
+        .await
+        .unwrap();
+    assert_eq!(stored.state, IndexingState::Idle);
+
+    let missing = db
+        .update_address_indexing_state(Cluster::Devnet, OTHER_ADDRESS, move_to(IndexingState::Idle))
+        .await;
+    assert!(matches!(missing, Err(AppError::NotFound(_))));
+}
+
+async fn signature_upserts_are_counted(db: &dyn Storage) {
+    let signatures = [
+        signature("first", 1, "confirmed"),
+        signature("second", 2, "confirmed"),
+    ];
+    let written = db.upsert_transaction_signatures(&signatures).await.unwrap();
+    assert_eq!(counts(written), (2, 0, 0));
+
+    let written = db.upsert_transaction_signatures(&signatures).await.unwrap();
+    assert_eq!(counts(written), (0, 0, 2));
+
+    let promoted = [
+        signature("first", 1, FINALIZED),
+        signature("second", 2, "confirmed"),
+    ];
+    let written = db.upsert_transaction_signatures(&promoted).await.unwrap();
+    assert_eq!(counts(written), (0, 1, 1));
+}
+
+async fn transaction_upserts_are_counted_per_address(db: &dyn Storage) {
+    let txns = [transaction("first", 1), transaction("second", 2)];
+    let written = db.upsert_transactions(ADDRESS, &txns).await.unwrap();
+    assert_eq!(counts(written), (2, 0, 0));
+
+    // The bodies are stored already, but they are new to the other address
+    let written = db.upsert_transactions(OTHER_ADDRESS, &txns).await.unwrap();
+    assert_eq!(counts(written), (2, 0, 0));
+
+    let written = db.upsert_transactions(ADDRESS, &txns).await.unwrap();
+    assert_eq!(counts(written), (0, 0, 2));
+
+    let mut changed = transaction("first", 1);
+    changed.transaction = json!({"signatures": ["first"], "meta": {}});
+    let written = db
+        .upsert_transactions(ADDRESS, &[changed, transaction("second", 2)])
+        .await
+        .unwrap();
+    assert_eq!(counts(written), (0, 1, 1));
+
+    // A transaction new to the address counts as inserted even when its body changed,
+    // without the change being counted for another transaction of the batch
+    db.upsert_transactions(THIRD_ADDRESS, &[transaction("second", 2)])
+        .await
+        .unwrap();
+    let mut changed = transaction("first", 1);
+    changed.transaction = json!({"signatures": ["first"], "meta": {"fee": 5000}});
+    let written = db
+        .upsert_transactions(THIRD_ADDRESS, &[changed, transaction("second", 2)])
+        .await
+        .unwrap();
+    assert_eq!(counts(written), (1, 0, 1));
+
+    assert_eq!(
+        db.get_transactions_count(Cluster::Devnet, ADDRESS)
+            .await
+            .unwrap(),
+        2
+    );
+}
+
+async fn jobs_are_claimed_by_priority_then_age(db: &dyn Storage) {
+    db.enqueue_job(&job("background", JobPriority::Background, 1))
+        .await
+        .unwrap();
+    db.enqueue_job(&job("second", JobPriority::Interactive, 3))
+        .await
+        .unwrap();
+    db.enqueue_job(&job("first", JobPriority::Interactive, 2))
+        .await
+        .unwrap();
+    // A queued job is bumped to the higher priority of a new one
+    let bumped = db
+        .enqueue_job(&job("background", JobPriority::Interactive, 4))
+        .await
+        .unwrap();
+    assert_eq!(bumped.priority, JobPriority::Interactive.rank());
+    assert_eq!(bumped.enqueued_at, Timestamp::from_millis(1));
+
+    let pending: Vec<String> = db
+        .get_pending_jobs()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|job| job.address)
+        .collect();
+    assert_eq!(pending, ["background", "first", "second"]);
+
+    let claimed = db.claim_next_job().await.unwrap().unwrap();
+    assert_eq!(claimed.address, "background");
+    assert_eq!(claimed.status, JobStatus::Running);
+    assert!(claimed.started_at.is_some());
+
+    // A running job is neither claimed again nor dropped as a pending one
+    assert!(!db.delete_pending_job(&claimed.id).await.unwrap());
+    assert!(
+        db.delete_pending_job(&document_id(Cluster::Devnet, "second"))
+            .await
+            .unwrap()
+    );
+    assert_eq!(db.claim_next_job().await.unwrap().unwrap().address, "first");
+    assert!(db.claim_next_job().await.unwrap().is_none());
+
+    assert_eq!(db.reset_running_jobs().await.unwrap(), 2);
+    assert_eq!(db.get_pending_jobs().await.unwrap().len(), 2);
+}
+
+async fn finalized_signatures_are_promoted(db: &dyn Storage) {
+    let signatures = [
+        signature("first", 1, "confirmed"),
+        signature("second", 2, "confirmed"),
+    ];
+    db.upsert_transaction_signatures(&signatures).await.unwrap();
+
+    db.finalize_signatures(Cluster::Devnet, &[("first".to_string(), 5)])
+        .await
+        .unwrap();
+
+    let unfinalized: Vec<String> = db
+        .get_unfinalized_signatures(Cluster::Devnet, 10)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|sign| sign.signature)
+        .collect();
+    assert_eq!(unfinalized, ["second"]);
+    // Another cluster has none of them
+    assert!(
+        db.get_unfinalized_signatures(Cluster::Mainnet, 10)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+async fn removed_signatures_take_their_records_along(db: &dyn Storage) {
+    db.insert_account(&account()).await.unwrap();
+    let signatures = [
+        signature("dropped", 1, "confirmed"),
+        signature("kept", 2, "confirmed"),
+    ];
+    db.upsert_transaction_signatures(&signatures).await.unwrap();
+    db.upsert_transactions(
+        ADDRESS,
+        &[transaction("dropped", 1), transaction("kept", 2)],
+    )
+    .await
+    .unwrap();
+    db.record_dead_letter(&dead_letter("dropped"))
+        .await
+        .unwrap();
+
+    db.remove_signatures(Cluster::Devnet, &["dropped".to_string()])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        db.get_signatures_count(Cluster::Devnet, ADDRESS)
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        db.get_transactions_count(Cluster::Devnet, ADDRESS)
+            .await
+            .unwrap(),
+        1
+    );
+    assert!(
+        db.get_due_dead_letters(Cluster::Devnet, 10)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+async fn stats_count_the_finalized_records(db: &dyn Storage) {
+    assert!(matches!(
+        db.get_indexer_stats(Cluster::Devnet, ADDRESS).await,
+        Err(AppError::NotFound(_))
+    ));
+
+    db.insert_account(&account()).await.unwrap();
+    let signatures = [
+        signature("first", 1, FINALIZED),
+        signature("second", 2, FINALIZED),
+        signature("third", 3, "confirmed"),
+    ];
+    db.upsert_transaction_signatures(&signatures).await.unwrap();
+    db.upsert_transactions(ADDRESS, &[transaction("first", 1), transaction("third", 3)])
+        .await
+        .unwrap();
+    db.record_dead_letter(&dead_letter("second")).await.unwrap();
+    db.record_dead_letter(&dead_letter("fourth")).await.unwrap();
+    db.update_dead_letter(
+        &membership_id(Cluster::Devnet, ADDRESS, "fourth"),
+        DeadLetterStatus::Failed,
+        &dead_letter("fourth").error,
+        Timestamp::now(),
+    )
+    .await
+    .unwrap();
+
+    let stats = db
+        .get_indexer_stats(Cluster::Devnet, ADDRESS)
+        .await
+        .unwrap();
+    assert!(stats.account_exists);
+    assert_eq!(stats.signatures, 2);
+    assert_eq!(stats.transactions, 1);
+    assert_eq!(stats.unfinalized_signatures, 1);
+    assert_eq!(stats.pending_dead_letters, 1);
+    assert_eq!(stats.failed_dead_letters, 1);
+}
+
+// Run every check of the suite against a fresh storage of the backend
+macro_rules! storage_tests {
+    ($backend:ident, $storage:expr) => {
+        mod $backend {
+            use super::*;
+
+            #[tokio::test]
+            async fn only_legal_transitions_are_applied() {
+                super::only_legal_transitions_are_applied(&$storage).await;
+            }
+
+            #[tokio::test]
+            async fn signature_upserts_are_counted() {
+                super::signature_upserts_are_counted(&$storage).await;
+            }
+
+            #[tokio::test]
+            async fn transaction_upserts_are_counted_per_address() {
+                super::transaction_upserts_are_counted_per_address(&$storage).await;
+            }
+
+            #[tokio::test]
+            async fn jobs_are_claimed_by_priority_then_age() {
+                super::jobs_are_claimed_by_priority_then_age(&$storage).await;
+            }
+
+            #[tokio::test]
+            async fn finalized_signatures_are_promoted() {
+                super::finalized_signatures_are_promoted(&$storage).await;
+            }
+
+            #[tokio::test]
+            async fn removed_signatures_take_their_records_along() {
+                super::removed_signatures_take_their_records_along(&$storage).await;
+            }
+
+            #[tokio::test]
+            async fn stats_count_the_finalized_records() {
+                super::stats_count_the_finalized_records(&$storage).await;
+            }
+        }
+    };
+}
+
+storage_tests!(memory_storage, memory::MemoryStorage::default());
+storage_tests!(
+    sqlite_storage,
+    sqlite::SqliteStorage::in_memory().await.unwrap()
+);
@@ -0,0 +1,294 @@
+use std::time::Duration;
+
+use tracing::{error, info, warn};
+
+use crate::{
+    app_state::AppState,
+    cluster::Cluster,
+    error::AppError,
+    models::{DeadLetter, DeadLetterStatus, IndexingError, Timestamp},
+    solana::fetch_transaction,
+};
+
+// Number of dead letters of a cluster that are retried in one round
+const DEAD_LETTERS_PER_RETRY: i64 = 100;
+// Upper limit of the delay between two retries of a dead letter
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+// Periodically retry fetching the transactions that failed while indexing their address.
+// A transaction that is fetched is stored like any other one of the address,
+// one that keeps failing is retried with backoff until it runs out of attempts
+pub fn spawn_dead_letter_retries(state: &AppState) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        info!("Dead letter retries started");
+        let mut ticker = tokio::time::interval(state.config.dead_letter_retry_interval);
+        loop {
+            ticker.tick().await;
+            for &cluster in state.rpc.keys() {
+                if let Err(err) = retry_dead_letters(&state, cluster).await {
+                    error!(%cluster, %err, "Error occurred while retrying the dead letters");
+                }
+            }
+        }
+    });
+}
+
+async fn retry_dead_letters(state: &AppState, cluster: Cluster) -> Result<(), AppError> {
+    let dead_letters = state
+        .db
+        .get_due_dead_letters(cluster, DEAD_LETTERS_PER_RETRY)
+        .await?;
+    if dead_letters.is_empty() {
+        return Ok(());
+    }
+
+    // A dead letter that can't be updated is retried again in the next round,
+    // so it doesn't hold back the others of this one
+    let mut stored = 0;
+    for dead_letter in &dead_letters {
+        match retry_dead_letter(state, cluster, dead_letter).await {
+            Ok(true) => stored += 1,
+            Ok(false) => {}
+            Err(err) => error!(
+                %cluster,
+                signature = %dead_letter.signature,
+                %err,
+                "Error occurred while retrying the dead letter"
+            ),
+        }
+    }
+
+    info!(
+        %cluster,
+        retried = dead_letters.len(),
+        stored,
+        "Dead letters retried"
+    );
+
+    Ok(())
+}
+
+// Retry fetching the transaction of the dead letter, true when it is stored now
+async fn retry_dead_letter(
+    state: &AppState,
+    cluster: Cluster,
+    dead_letter: &DeadLetter,
+) -> Result<bool, AppError> {
+    match fetch_transaction(state, None, cluster, &dead_letter.signature).await {
+        Ok(txn) => {
+            state
+                .db
+                .upsert_transactions(&dead_letter.account_address, &[txn])
+                .await?;
+            state.db.delete_dead_letter(&dead_letter.id).await?;
+            Ok(true)
+        }
+        Err(err) => {
+            record_retry_failure(state, dead_letter, &err).await?;
+            Ok(false)
+        }
+    }
+}
+
+// Schedule the next retry of the dead letter or give up on it after the last attempt
+async fn record_retry_failure(
+    state: &AppState,
+    dead_letter: &DeadLetter,
+    err: &AppError,
+) -> Result<(), AppError> {
+    let attempts = dead_letter.attempts + 1;
+    let status = if attempts >= state.config.dead_letter_max_attempts {
+        warn!(
+            signature = %dead_letter.signature,
+            address = %dead_letter.account_address,
+            %err,
+            attempts,
+            "Gave up on the transaction that keeps failing"
+        );
+        DeadLetterStatus::Failed
+    } else {
+        DeadLetterStatus::Pending
+    };
+
+    let error = IndexingError {
+        kind: err.kind().to_string(),
+        message: err.to_string(),
+        occurred_at: Timestamp::now(),
+    };
+
+    state
+        .db
+        .update_dead_letter(
+            &dead_letter.id,
+            status,
+            &error,
+            next_attempt_at(state, attempts),
+        )
+        .await
+}
+
+// The delay doubles for every failed attempt (capped at MAX_RETRY_DELAY)
+pub fn next_attempt_at(state: &AppState, attempts: i32) -> Timestamp {
+    let exponent = attempts.saturating_sub(1).clamp(0, 31) as u32;
+    let delay = state
+        .config
+        .dead_letter_retry_base_delay
+        .saturating_mul(2u32.saturating_pow(exponent))
+        .min(MAX_RETRY_DELAY);
+
+    Timestamp::from_millis(Timestamp::now().timestamp_millis() + delay.as_millis() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        config::Config,
+        models::{Account, IndexerStats, document_id, membership_id},
+        solana::account_update,
+        testing::{FakeCluster, state_with},
+    };
+
+    const ADDRESS: &str = "address";
+
+    fn state_with_delay(cluster: FakeCluster, base_delay: Duration) -> AppState {
+        let mut state = state_with(cluster);
+        state.config = Arc::new(Config {
+            dead_letter_retry_base_delay: base_delay,
+            ..Config::from_env()
+        });
+        state
+    }
+
+    // Record the signature as a dead letter that is due for its next retry
+    async fn record(state: &AppState, signature: &str, attempts: i32) {
+        let now = Timestamp::now();
+        state
+            .db
+            .record_dead_letter(&DeadLetter {
+                id: membership_id(Cluster::Devnet, ADDRESS, signature),
+                cluster: Cluster::Devnet,
+                account_address: ADDRESS.to_string(),
+                signature: signature.to_string(),
+                status: DeadLetterStatus::Pending,
+                attempts,
+                error: IndexingError {
+                    kind: "solana".to_string(),
+                    message: "Transaction not available".to_string(),
+                    occurred_at: now,
+                },
+                next_attempt_at: Timestamp::from_millis(now.timestamp_millis() - 1),
+                created_at: now,
+                updated_at: now,
+            })
+            .await
+            .unwrap();
+    }
+
+    async fn stats(state: &AppState) -> IndexerStats {
+        // The stats are only kept for a stored account
+        if !state
+            .db
+            .check_account_exists(Cluster::Devnet, ADDRESS)
+            .await
+        {
+            let update = account_update(None, true);
+            let account = Account {
+                id: document_id(Cluster::Devnet, ADDRESS),
+                cluster: Cluster::Devnet,
+                address: ADDRESS.to_string(),
+                status: update.status,
+                lamports: update.lamports,
+                owner: update.owner,
+                executable: update.executable,
+                data_length: update.data_length,
+                rent_epoch: update.rent_epoch,
+                indexed_at: Timestamp::now(),
+                last_updated_at: update.last_updated_at,
+            };
+            state.db.insert_account(&account).await.unwrap();
+        }
+
+        state
+            .db
+            .get_indexer_stats(Cluster::Devnet, ADDRESS)
+            .await
+            .unwrap()
+    }
+
+    fn delay_of(next_attempt_at: Timestamp) -> Duration {
+        let millis = next_attempt_at.timestamp_millis() - Timestamp::now().timestamp_millis();
+        Duration::from_millis(millis.max(0) as u64)
+    }
+
+    #[test]
+    fn retries_back_off_exponentially_up_to_a_day() {
+        let state = state_with_delay(FakeCluster::new(0), Duration::from_secs(60));
+        let close_to = |attempts, expected: Duration| {
+            let delay = delay_of(next_attempt_at(&state, attempts));
+            assert!(
+                delay <= expected && expected - delay < Duration::from_secs(5),
+                "attempt {attempts}: {delay:?} instead of {expected:?}"
+            );
+        };
+
+        close_to(1, Duration::from_secs(60));
+        close_to(2, Duration::from_secs(120));
+        close_to(4, Duration::from_secs(480));
+        close_to(20, MAX_RETRY_DELAY);
+        close_to(i32::MAX, MAX_RETRY_DELAY);
+    }
+
+    #[tokio::test]
+    async fn fetched_dead_letters_are_stored_and_deleted() {
+        let mut cluster = FakeCluster::new(2);
+        let fetched = cluster.history[0].signature.clone();
+        let failing = cluster.history[1].signature.clone();
+        cluster.failing.push(failing.clone());
+        let state = state_with_delay(cluster, Duration::from_secs(60));
+        record(&state, &fetched, 1).await;
+        record(&state, &failing, 1).await;
+
+        retry_dead_letters(&state, Cluster::Devnet).await.unwrap();
+
+        let stored = state
+            .db
+            .get_transactions_count(Cluster::Devnet, ADDRESS)
+            .await
+            .unwrap();
+        assert_eq!(stored, 1);
+        assert_eq!(stats(&state).await.pending_dead_letters, 1);
+        // The failed retry waits for its backoff before it is due again
+        let due = state
+            .db
+            .get_due_dead_letters(Cluster::Devnet, 10)
+            .await
+            .unwrap();
+        assert!(due.is_empty());
+    }
+
+    #[tokio::test]
+    async fn dead_letters_out_of_attempts_are_given_up() {
+        let mut cluster = FakeCluster::new(1);
+        let failing = cluster.history[0].signature.clone();
+        cluster.failing.push(failing.clone());
+        let state = state_with_delay(cluster, Duration::ZERO);
+        let max_attempts = state.config.dead_letter_max_attempts;
+        record(&state, &failing, max_attempts - 1).await;
+
+        retry_dead_letters(&state, Cluster::Devnet).await.unwrap();
+
+        let stats = stats(&state).await;
+        assert_eq!(stats.pending_dead_letters, 0);
+        assert_eq!(stats.failed_dead_letters, 1);
+        let due = state
+            .db
+            .get_due_dead_letters(Cluster::Devnet, 10)
+            .await
+            .unwrap();
+        assert!(due.is_empty());
+    }
+}
@@ -2,15 +2,19 @@ use std::{env::VarError, io::Error as IoError};
 
 use axum::{Json, http::StatusCode, response::IntoResponse};
 use mongodb::bson::de::Error as MongoDeserializeError;
+use mongodb::bson::document::ValueAccessError;
 use mongodb::bson::ser::Error as MongoSerializeError;
 use mongodb::error::Error as MongoError;
 use serde::Serialize;
 use serde_json::Error as SerdeJsonError;
-use solana_client::client_error::ClientError;
+use solana_client::{client_error::ClientError, nonblocking::pubsub_client::PubsubClientError};
 use solana_sdk::{pubkey::ParsePubkeyError, signature::ParseSignatureError};
+use sqlx::{Error as SqlxError, migrate::MigrateError};
 use thiserror::Error;
 use tracing::{error, instrument};
 
+use crate::app_state::StopRequest;
+
 // Create an AppError using thiserror that handles almost all errors
 #[derive(Error, Debug)]
 pub enum AppError {
@@ -20,6 +24,9 @@ pub enum AppError {
     #[error("{0} Not Found")]
     NotFound(String),
 
+    #[error("Unauthorized - {0}")]
+    Unauthorized(String),
+
     #[error("Internal Error - {0}")]
     Internal(String),
 
@@ -28,6 +35,29 @@ pub enum AppError {
 
     #[error("Solana Error - {0}")]
     Solana(String),
+
+    // The RPC endpoints kept failing with errors that a later attempt can get past
+    #[error("Solana Unavailable - {0}")]
+    Unavailable(String),
+
+    #[error("The run was {0}")]
+    Stopped(StopRequest),
+}
+
+impl AppError {
+    // Short name of the variant that is stored with the failed runs of an address
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(_) => "bad_request",
+            AppError::NotFound(_) => "not_found",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Internal(_) => "internal",
+            AppError::Database(_) => "database",
+            AppError::Solana(_) => "solana",
+            AppError::Unavailable(_) => "unavailable",
+            AppError::Stopped(_) => "stopped",
+        }
+    }
 }
 
 // Custom Error Response
@@ -43,9 +73,12 @@ impl IntoResponse for AppError {
         let (status_code, message) = match self {
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+            AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
             AppError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
             AppError::Database(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
             AppError::Solana(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
+            AppError::Unavailable(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg),
+            AppError::Stopped(request) => (StatusCode::CONFLICT, format!("The run was {request}")),
         };
         error!(?message);
 
@@ -72,6 +105,25 @@ impl From<MongoDeserializeError> for AppError {
         AppError::Database(e.to_string())
     }
 }
+// Map the ValueAccessError of a missing or mistyped document field to the Database variant
+impl From<ValueAccessError> for AppError {
+    fn from(e: ValueAccessError) -> Self {
+        AppError::Database(e.to_string())
+    }
+}
+
+// Map the sqlx Error of the PostgreSQL storage to the Database variant of the AppError
+impl From<SqlxError> for AppError {
+    fn from(e: SqlxError) -> Self {
+        AppError::Database(e.to_string())
+    }
+}
+// Map the MigrateError of the embedded SQL migrations to the Database variant of the AppError
+impl From<MigrateError> for AppError {
+    fn from(e: MigrateError) -> Self {
+        AppError::Database(e.to_string())
+    }
+}
 
 // Map the std::io::Error to the Internal variant of the AppError
 impl From<IoError> for AppError {
@@ -108,6 +160,13 @@ impl From<ClientError> for AppError {
     }
 }
 
+// Map the Solana PubsubClientError of the websocket subscriptions to Solana
+impl From<PubsubClientError> for AppError {
+    fn from(e: PubsubClientError) -> Self {
+        AppError::Solana(e.to_string())
+    }
+}
+
 // Map the Solana ParseSignatureError to Solana
 impl From<ParseSignatureError> for AppError {
     fn from(e: ParseSignatureError) -> Self {
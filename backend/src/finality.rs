@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use solana_sdk::signature::Signature;
+use solana_transaction_status::TransactionConfirmationStatus;
+use tracing::{error, info, warn};
+
+use crate::{app_state::AppState, cluster::Cluster, error::AppError, models::Timestamp};
+
+// Number of signatures whose statuses are asked for in a single RPC call (the RPC maximum)
+const STATUS_BATCH_SIZE: usize = 256;
+// Number of stored signatures of a cluster that are checked in one round
+const SIGNATURES_PER_CHECK: i64 = 2560;
+// Number of rounds in a row that a signature past the grace period has to be unknown
+// to the cluster before it counts as dropped, so a single lagging node can't remove it
+const MISSES_BEFORE_REMOVAL: u32 = 3;
+
+// Periodically re-check the stored signatures that are not finalized yet.
+// A signature that the cluster has finalized since is promoted, while a signature that
+// the cluster still doesn't know after the grace period and in several rounds in a row
+// was dropped on a fork and is removed along with its transaction,
+// so only finalized data is left to account for
+pub fn spawn_finality_checks(state: &AppState) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        info!("Finality checks started");
+        let mut ticker = tokio::time::interval(state.config.finality_check_interval);
+        // Rounds in a row that every signature of a cluster was unknown to it
+        let mut misses: HashMap<Cluster, HashMap<String, u32>> = HashMap::new();
+        loop {
+            ticker.tick().await;
+            for &cluster in state.rpc.keys() {
+                let misses = misses.entry(cluster).or_default();
+                if let Err(err) = check_finality(&state, cluster, misses).await {
+                    error!(%cluster, %err, "Error occurred while checking the finality of the signatures");
+                }
+            }
+        }
+    });
+}
+
+async fn check_finality(
+    state: &AppState,
+    cluster: Cluster,
+    misses: &mut HashMap<String, u32>,
+) -> Result<(), AppError> {
+    let unfinalized = state
+        .db
+        .get_unfinalized_signatures(cluster, SIGNATURES_PER_CHECK)
+        .await?;
+    if unfinalized.is_empty() {
+        return Ok(());
+    }
+
+    // The same signature is stored once for every address that it belongs to,
+    // the oldest of them tells when it was first seen
+    let mut first_seen: HashMap<String, Timestamp> = HashMap::new();
+    for record in unfinalized {
+        first_seen
+            .entry(record.signature)
+            .or_insert(record.indexed_at);
+    }
+    let signatures: Vec<String> = first_seen.keys().cloned().collect();
+
+    let rpc = state.rpc_for(cluster)?;
+    let grace_cutoff =
+        Timestamp::now().timestamp_millis() - state.config.fork_grace_period.as_millis() as i64;
+    let mut finalized: Vec<(String, i64)> = vec![];
+    let mut dropped: Vec<String> = vec![];
+    // A signature that is known to the cluster again or not checked anymore starts over
+    let mut missed: HashMap<String, u32> = HashMap::new();
+
+    for chunk in signatures.chunks(STATUS_BATCH_SIZE) {
+        let parsed = chunk
+            .iter()
+            .map(|signature| Signature::from_str(signature))
+            .collect::<Result<Vec<_>, _>>()?;
+        let statuses = rpc.get_signature_statuses(None, &parsed).await?;
+
+        for (signature, status) in chunk.iter().zip(statuses) {
+            match status {
+                Some(status)
+                    if status.confirmation_status() == TransactionConfirmationStatus::Finalized =>
+                {
+                    finalized.push((signature.clone(), status.slot as i64));
+                }
+                // Still confirmed, checked again in the next round
+                Some(_) => {}
+                None if first_seen[signature].timestamp_millis() < grace_cutoff => {
+                    let count = misses.get(signature).copied().unwrap_or(0) + 1;
+                    if count >= MISSES_BEFORE_REMOVAL {
+                        dropped.push(signature.clone());
+                    } else {
+                        missed.insert(signature.clone(), count);
+                    }
+                }
+                // Not known to the RPC node yet
+                None => {}
+            }
+        }
+    }
+
+    *misses = missed;
+
+    state.db.finalize_signatures(cluster, &finalized).await?;
+
+    // Log the signatures before they are gone, so a wrong removal can still be traced
+    for signature in &dropped {
+        warn!(%cluster, %signature, "Removing a signature that was dropped on a fork");
+    }
+    state.db.remove_signatures(cluster, &dropped).await?;
+    info!(
+        %cluster,
+        checked = signatures.len(),
+        finalized = finalized.len(),
+        dropped = dropped.len(),
+        "Finality of the stored signatures checked"
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use solana_transaction_status::TransactionConfirmationStatus::{Confirmed, Finalized};
+
+    use super::*;
+    use crate::{
+        models::{TransactionSignature, membership_id},
+        testing::{FakeCluster, sharing},
+    };
+
+    const ADDRESS: &str = "address";
+
+    // Store the history of the cluster as confirmed signatures indexed the given time ago
+    async fn store_confirmed(state: &AppState, cluster: &FakeCluster, age: Duration) {
+        let indexed_at =
+            Timestamp::from_millis(Timestamp::now().timestamp_millis() - age.as_millis() as i64);
+        let signatures: Vec<TransactionSignature> = cluster
+            .history
+            .iter()
+            .map(|sign| TransactionSignature {
+                id: membership_id(Cluster::Devnet, ADDRESS, &sign.signature),
+                cluster: Cluster::Devnet,
+                signature: sign.signature.clone(),
+                account_address: ADDRESS.to_string(),
+                slot: sign.slot as i64,
+                block_time: None,
+                confirmation_status: "confirmed".to_string(),
+                indexed_at,
+            })
+            .collect();
+        state
+            .db
+            .upsert_transaction_signatures(&signatures)
+            .await
+            .unwrap();
+    }
+
+    async fn unfinalized(state: &AppState) -> Vec<String> {
+        let mut signatures: Vec<String> = state
+            .db
+            .get_unfinalized_signatures(Cluster::Devnet, 100)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|sign| sign.signature)
+            .collect();
+        signatures.sort();
+        signatures
+    }
+
+    async fn stored(state: &AppState) -> u64 {
+        state
+            .db
+            .get_signatures_count(Cluster::Devnet, ADDRESS)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn finalized_signatures_are_promoted() {
+        let cluster = Arc::new(FakeCluster::new(2));
+        let state = sharing(cluster.clone());
+        store_confirmed(&state, &cluster, Duration::ZERO).await;
+        let promoted = cluster.history[0].signature.clone();
+        let pending = cluster.history[1].signature.clone();
+        cluster.set_status(&promoted, 50, Some(Finalized));
+        cluster.set_status(&pending, 40, Some(Confirmed));
+
+        let mut misses = HashMap::new();
+        check_finality(&state, Cluster::Devnet, &mut misses)
+            .await
+            .unwrap();
+
+        assert_eq!(unfinalized(&state).await, [pending]);
+        assert_eq!(stored(&state).await, 2);
+    }
+
+    #[tokio::test]
+    async fn unknown_signatures_are_kept_during_the_grace_period() {
+        let cluster = Arc::new(FakeCluster::new(2));
+        let state = sharing(cluster.clone());
+        store_confirmed(&state, &cluster, Duration::ZERO).await;
+
+        let mut misses = HashMap::new();
+        for _ in 0..MISSES_BEFORE_REMOVAL + 1 {
+            check_finality(&state, Cluster::Devnet, &mut misses)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(stored(&state).await, 2);
+        assert!(misses.is_empty());
+    }
+
+    #[tokio::test]
+    async fn dropped_signatures_are_removed_after_several_missed_rounds() {
+        let cluster = Arc::new(FakeCluster::new(2));
+        let state = sharing(cluster.clone());
+        let grace_period = state.config.fork_grace_period;
+        store_confirmed(&state, &cluster, grace_period * 2).await;
+
+        let mut misses = HashMap::new();
+        for _ in 0..MISSES_BEFORE_REMOVAL - 1 {
+            check_finality(&state, Cluster::Devnet, &mut misses)
+                .await
+                .unwrap();
+            assert_eq!(stored(&state).await, 2);
+        }
+
+        check_finality(&state, Cluster::Devnet, &mut misses)
+            .await
+            .unwrap();
+        assert_eq!(stored(&state).await, 0);
+        assert!(misses.is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_reappearing_signature_starts_over() {
+        let cluster = Arc::new(FakeCluster::new(1));
+        let state = sharing(cluster.clone());
+        let grace_period = state.config.fork_grace_period;
+        store_confirmed(&state, &cluster, grace_period * 2).await;
+        let signature = cluster.history[0].signature.clone();
+
+        let mut misses = HashMap::new();
+        for _ in 0..MISSES_BEFORE_REMOVAL - 1 {
+            check_finality(&state, Cluster::Devnet, &mut misses)
+                .await
+                .unwrap();
+        }
+        assert_eq!(misses.get(&signature), Some(&(MISSES_BEFORE_REMOVAL - 1)));
+
+        // A lagging node caught up, the signature is known again
+        cluster.set_status(&signature, 1, Some(Confirmed));
+        check_finality(&state, Cluster::Devnet, &mut misses)
+            .await
+            .unwrap();
+        assert!(misses.is_empty());
+
+        // It has to be missed in as many rounds again before it's removed
+        cluster.set_status(&signature, 1, None);
+        for _ in 0..MISSES_BEFORE_REMOVAL - 1 {
+            check_finality(&state, Cluster::Devnet, &mut misses)
+                .await
+                .unwrap();
+        }
+        assert_eq!(stored(&state).await, 1);
+        check_finality(&state, Cluster::Devnet, &mut misses)
+            .await
+            .unwrap();
+        assert_eq!(stored(&state).await, 0);
+    }
+}
@@ -1,5 +1,5 @@
 use std::convert::Infallible;
-use std::sync::atomic::Ordering;
+use std::sync::{Arc, Weak, atomic::Ordering};
 
 use axum::{
     extract::{Json, Path, Query, State},
@@ -8,36 +8,78 @@ use axum::{
         sse::{Event, KeepAlive, Sse},
     },
 };
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use tokio_stream::wrappers::BroadcastStream;
 use tokio_stream::{Stream, StreamExt};
 use tracing::{error, info, instrument, warn};
 
 use crate::{
-    app_state::AppState,
-    db::{
-        accounts::{get_account, get_address_indexing_state, get_indexer_stats},
-        transactions::{get_transaction, get_transaction_signatures, get_transactions},
-    },
+    app_state::{AddressSession, AppState, StopRequest},
+    cluster::Cluster,
+    config::DisconnectPolicy,
     error::AppError,
+    integrity, live,
     message::SyncStatus,
-    solana,
+    models::{AddressIndexingState, IndexingBounds, JobKind, JobPriority, RetryRecommendation},
+    queue::{self, JobOutcome},
+    scheduler,
 };
 
+// Path of the account routes
+// The routes without a cluster segment use the default cluster of the deployment
+#[derive(Debug, Deserialize)]
+pub struct AccountPath {
+    cluster: Option<Cluster>,
+    address: String,
+}
+
+// Path of the single transaction routes
+#[derive(Debug, Deserialize)]
+pub struct TransactionPath {
+    cluster: Option<Cluster>,
+    address: String,
+    signature: String,
+}
+
+// Resolve the cluster of the request and make sure this deployment can index it
+fn resolve_cluster(state: &AppState, cluster: Option<Cluster>) -> Result<Cluster, AppError> {
+    let cluster = cluster.unwrap_or(state.config.default_cluster);
+    state.rpc_for(cluster)?;
+    Ok(cluster)
+}
+
+// Indexing state of the address along with whether it is worth indexing/refreshing it again
+#[derive(Debug, Serialize)]
+pub struct AccountStatus {
+    #[serde(flatten)]
+    state: AddressIndexingState,
+    retry: RetryRecommendation,
+}
+
 // Entry point API of the app that checks whether the Solana account is indexed or not
 #[instrument(skip(state))]
 pub async fn account_status(
-    Path(address): Path<String>,
+    Path(path): Path<AccountPath>,
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, AppError> {
     let state = state.clone();
+    let cluster = resolve_cluster(&state, path.cluster)?;
 
-    let address_state = get_address_indexing_state(&state.db, &address).await?;
-    Ok(Json(address_state))
+    let address_state = state
+        .db
+        .get_address_indexing_state(cluster, &path.address)
+        .await?;
+    let retry = address_state.retry_recommendation();
+    Ok(Json(AccountStatus {
+        state: address_state,
+        retry,
+    }))
 }
 
 fn sync_message_to_event(msg: SyncStatus) -> Event {
     match msg {
+        SyncStatus::Queued(data) => Event::default().event("queued").data(data),
         SyncStatus::Indexing => Event::default().event("indexing").data("started"),
         SyncStatus::Syncing => Event::default().event("syncing").data("started"),
         SyncStatus::AccountData(data) => Event::default().event("account-data").data(data),
@@ -47,39 +89,129 @@ fn sync_message_to_event(msg: SyncStatus) -> Event {
         SyncStatus::TransactionDetails(data) => {
             Event::default().event("transactions-fetched").data(data)
         }
+        SyncStatus::RateLimited(data) => Event::default().event("rate-limited").data(data),
+        SyncStatus::LiveTransaction(data) => Event::default().event("live-transaction").data(data),
         SyncStatus::Error(message) => Event::default().event("error").data(message),
         SyncStatus::Completed => Event::default().event("close").data("close the connection"),
+        SyncStatus::Paused => Event::default().event("paused").data("the run was paused"),
+        SyncStatus::Cancelled => Event::default()
+            .event("cancelled")
+            .data("the run was cancelled"),
+    }
+}
+
+// Counts the SSE clients following the job of a session and applies the disconnect policy
+// once the last of them is gone. It only holds a weak reference to the session
+// so the event streams still end when the job is done and its session is dropped
+struct SubscriberGuard {
+    state: AppState,
+    cluster: Cluster,
+    address: String,
+    session: Weak<AddressSession>,
+}
+
+impl SubscriberGuard {
+    fn new(
+        state: &AppState,
+        cluster: Cluster,
+        address: &str,
+        session: &Arc<AddressSession>,
+    ) -> Self {
+        session.subscribers.fetch_add(1, Ordering::AcqRel);
+        SubscriberGuard {
+            state: state.clone(),
+            cluster,
+            address: address.to_string(),
+            session: Arc::downgrade(session),
+        }
+    }
+}
+
+impl Drop for SubscriberGuard {
+    fn drop(&mut self) {
+        let Some(session) = self.session.upgrade() else {
+            return;
+        };
+        let remaining = session.subscribers.fetch_sub(1, Ordering::AcqRel) - 1;
+        if remaining > 0 || self.state.config.disconnect_policy != DisconnectPolicy::Pause {
+            return;
+        }
+
+        info!(cluster = %self.cluster, address = %self.address, "Last subscriber left, pausing the job");
+        let state = self.state.clone();
+        let cluster = self.cluster;
+        let address = std::mem::take(&mut self.address);
+        tokio::spawn(async move {
+            if let Err(err) = queue::stop_job(&state, cluster, &address, StopRequest::Pause).await {
+                warn!(%cluster, %address, %err, "Error occurred while pausing the unwatched job");
+            }
+        });
+    }
+}
+
+// Optional bounds of the history to index e.g. ?since=2025-01-01T00:00:00Z&max_signatures=5000
+// Without any of them the default depth of the deployment is indexed, unless full_history is set
+#[derive(Debug, Deserialize)]
+pub struct BoundsQuery {
+    max_signatures: Option<i64>,
+    since: Option<DateTime<Utc>>,
+    min_slot: Option<i64>,
+    #[serde(default)]
+    full_history: bool,
+}
+
+impl BoundsQuery {
+    fn into_bounds(self, state: &AppState) -> Result<IndexingBounds, AppError> {
+        if self
+            .max_signatures
+            .is_some_and(|max_signatures| max_signatures < 1)
+        {
+            return Err(AppError::BadRequest(
+                "max_signatures has to be at least 1".to_string(),
+            ));
+        }
+        if self.min_slot.is_some_and(|min_slot| min_slot < 0) {
+            return Err(AppError::BadRequest(
+                "min_slot can't be negative".to_string(),
+            ));
+        }
+
+        let bounds = IndexingBounds {
+            max_signatures: self.max_signatures,
+            since: self.since.map(|since| since.timestamp()),
+            min_slot: self.min_slot,
+        };
+        if bounds == IndexingBounds::default() && !self.full_history {
+            return Ok(state.config.default_bounds());
+        }
+
+        Ok(bounds)
     }
 }
 
 // Indexer SSE API is called when the account is not found in DB (not indexed)
 // it is used to fetch the account and transaction data via RPC and insert them in DB
+// An indexed address is indexed again only to extend its history down to deeper bounds
 // Using broadcast channel to send the sync status messages to all the receivers or the sse clients
 pub async fn indexer_sse(
     State(state): State<AppState>,
-    Path(address): Path<String>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    let session = state.get_or_create_session(&address);
+    Path(path): Path<AccountPath>,
+    Query(query): Query<BoundsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let cluster = resolve_cluster(&state, path.cluster)?;
+    let bounds = query.into_bounds(&state)?;
+    let address = path.address;
+    let session = queue::enqueue(
+        &state,
+        cluster,
+        address.clone(),
+        JobKind::Index,
+        JobPriority::Interactive,
+        Some(bounds),
+    )
+    .await?;
     let receiver = session.sender.subscribe();
-    warn!(
-        "started AtomicBool value: {}",
-        session.started.load(Ordering::Relaxed)
-    );
-
-    if !session.started.swap(true, Ordering::AcqRel) {
-        let session = session.clone();
-        tokio::spawn(async move {
-            if let Err(e) = solana::indexer(state.clone(), session.clone(), address.clone()).await {
-                session.emit_event(SyncStatus::Error(e.to_string())).await;
-                error!(
-                    "Error occcured while sending event to channel: {}",
-                    e.to_string()
-                );
-            }
-            let removed = state.remove_session(&address);
-            info!("Session removed: {}", removed);
-        });
-    }
+    let guard = SubscriberGuard::new(&state, cluster, &address, &session);
 
     // Convert the past_events iterator to a stream
     // for making sure to send all the events to the late subscribers
@@ -102,8 +234,12 @@ pub async fn indexer_sse(
     });
 
     // Combine or Chain the two streams: replay_stream and live_stream
-    let stream = replay_stream.chain(live_stream);
-    Sse::new(stream).keep_alive(KeepAlive::default())
+    // The guard lives as long as the stream, i.e. until the client disconnects
+    let stream = replay_stream.chain(live_stream).map(move |event| {
+        let _guard = &guard;
+        event
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
 }
 
 // Refresh SSE API is called to get the latest account and transaction data.
@@ -112,31 +248,21 @@ pub async fn indexer_sse(
 // Using broadcast channel to send the sync status messages to all the receivers or the sse clients
 pub async fn refresh_sse(
     State(state): State<AppState>,
-    Path(address): Path<String>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    let session = state.get_or_create_session(&address);
+    Path(path): Path<AccountPath>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let cluster = resolve_cluster(&state, path.cluster)?;
+    let address = path.address;
+    let session = queue::enqueue(
+        &state,
+        cluster,
+        address.clone(),
+        JobKind::Refresh,
+        JobPriority::Interactive,
+        None,
+    )
+    .await?;
     let receiver = session.sender.subscribe();
-    warn!(
-        "started AtomicBool value: {}",
-        session.started.load(Ordering::Relaxed)
-    );
-
-    if !session.started.swap(true, Ordering::AcqRel) {
-        let session = session.clone();
-        tokio::spawn(async move {
-            if let Err(e) = solana::refresher(state.clone(), session.clone(), address.clone()).await
-            {
-                session.emit_event(SyncStatus::Error(e.to_string())).await;
-                error!(
-                    "Error occcured while sending event to channel: {}",
-                    e.to_string()
-                );
-            }
-
-            let removed = state.remove_session(&address);
-            info!("Session removed: {}", removed);
-        });
-    }
+    let guard = SubscriberGuard::new(&state, cluster, &address, &session);
 
     // Convert the past_events iterator to a stream
     // for making sure to send all the events to the late subscribers
@@ -159,19 +285,51 @@ pub async fn refresh_sse(
     });
 
     // Combine or Chain the two streams: replay_stream and live_stream
-    let stream = replay_stream.chain(live_stream);
-    Sse::new(stream).keep_alive(KeepAlive::default())
+    // The guard lives as long as the stream, i.e. until the client disconnects
+    let stream = replay_stream.chain(live_stream).map(move |event| {
+        let _guard = &guard;
+        event
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+// Live SSE API follows a watched address, every transaction and account change
+// that the live ingestion stores is streamed to the client as it lands
+pub async fn live_sse(
+    State(state): State<AppState>,
+    Path(path): Path<AccountPath>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let cluster = resolve_cluster(&state, path.cluster)?;
+    let receiver = live::subscribe(&state, cluster, &path.address)?;
+
+    let stream = BroadcastStream::new(receiver).map(|msg_result| match msg_result {
+        Ok(msg) => Ok(sync_message_to_event(msg)),
+        Err(e) => {
+            error!("Broadcast Error: {:#?}", e);
+            Ok(Event::default()
+                .event("warning")
+                .data("client request delayed/lagged"))
+        }
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
 }
 
+// The stored account data of the address
+// A closed or never funded address is returned with its status and zeroed account data
 #[instrument(skip(state))]
 pub async fn account_data(
     State(state): State<AppState>,
-    Path(address): Path<String>,
+    Path(path): Path<AccountPath>,
 ) -> Result<impl IntoResponse, AppError> {
     let state = state.clone();
+    let cluster = resolve_cluster(&state, path.cluster)?;
 
-    if let Some(account) = get_account(&state.db, &address).await? {
+    if let Some(account) = state.db.get_account(cluster, &path.address).await? {
         info!(?account);
+        // Every view of the account speeds up its automatic refresh
+        if let Err(err) = scheduler::record_view(&state, cluster, &path.address).await {
+            error!(%err, "Error occurred while recording the view of the account");
+        }
         Ok(Json(account))
     } else {
         Err(AppError::NotFound("Account Not Found".to_string()))
@@ -187,36 +345,48 @@ pub struct Pagination {
 #[instrument(skip(state))]
 pub async fn transaction_signatures(
     State(state): State<AppState>,
-    Path(address): Path<String>,
+    Path(path): Path<AccountPath>,
     Query(pagination): Query<Pagination>,
 ) -> Result<impl IntoResponse, AppError> {
     let state = state.clone();
+    let cluster = resolve_cluster(&state, path.cluster)?;
 
-    let txns =
-        get_transaction_signatures(&state.db, address, pagination.skip, pagination.limit).await?;
+    let txns = state
+        .db
+        .get_transaction_signatures(cluster, path.address, pagination.skip, pagination.limit)
+        .await?;
     Ok(Json(txns))
 }
 
 #[instrument(skip(state))]
 pub async fn transactions(
     State(state): State<AppState>,
-    Path(address): Path<String>,
+    Path(path): Path<AccountPath>,
     Query(pagination): Query<Pagination>,
 ) -> Result<impl IntoResponse, AppError> {
     let state = state.clone();
+    let cluster = resolve_cluster(&state, path.cluster)?;
 
-    let txns = get_transactions(&state.db, address, pagination.skip, pagination.limit).await?;
+    let txns = state
+        .db
+        .get_transactions(cluster, path.address, pagination.skip, pagination.limit)
+        .await?;
     Ok(Json(txns))
 }
 
 #[instrument(skip(state))]
 pub async fn transaction_from_signature(
     State(state): State<AppState>,
-    Path((address, signature)): Path<(String, String)>,
+    Path(path): Path<TransactionPath>,
 ) -> Result<impl IntoResponse, AppError> {
     let state = state.clone();
+    let cluster = resolve_cluster(&state, path.cluster)?;
 
-    if let Some(txn) = get_transaction(&state.db, address, signature).await? {
+    if let Some(txn) = state
+        .db
+        .get_transaction(cluster, path.address, path.signature)
+        .await?
+    {
         Ok(Json(txn))
     } else {
         Err(AppError::NotFound("Transaction Not Found".to_string()))
@@ -226,10 +396,141 @@ pub async fn transaction_from_signature(
 #[instrument(skip(state))]
 pub async fn indexer_stats(
     State(state): State<AppState>,
-    Path(address): Path<String>,
+    Path(path): Path<AccountPath>,
 ) -> Result<impl IntoResponse, AppError> {
     let state = state.clone();
+    let cluster = resolve_cluster(&state, path.cluster)?;
 
-    let stats = get_indexer_stats(&state.db, &address).await?;
+    let stats = state.db.get_indexer_stats(cluster, &path.address).await?;
     Ok(Json(stats))
 }
+
+// Admin API that scans the stored history of the address for missing signatures and transactions
+#[instrument(skip(state))]
+pub async fn integrity_report(
+    State(state): State<AppState>,
+    Path(path): Path<AccountPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let cluster = resolve_cluster(&state, path.cluster)?;
+
+    let report = integrity::scan_address(&state, cluster, &path.address).await?;
+    Ok(Json(report))
+}
+
+// Outcome of an admin action on the job of an address
+#[derive(Debug, Serialize)]
+pub struct JobActionResponse {
+    cluster: Cluster,
+    address: String,
+    action: &'static str,
+    outcome: JobOutcome,
+}
+
+// Admin API that cancels the queued or running job of the address
+// A cancelled run keeps its stored batches and resumes from them when the address is indexed again
+#[instrument(skip(state))]
+pub async fn cancel_job(
+    State(state): State<AppState>,
+    Path(path): Path<AccountPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let cluster = resolve_cluster(&state, path.cluster)?;
+
+    let outcome = queue::stop_job(&state, cluster, &path.address, StopRequest::Cancel).await?;
+    Ok(Json(JobActionResponse {
+        cluster,
+        address: path.address,
+        action: "cancel",
+        outcome,
+    }))
+}
+
+// Admin API that pauses the queued or running job of the address until it is resumed
+#[instrument(skip(state))]
+pub async fn pause_job(
+    State(state): State<AppState>,
+    Path(path): Path<AccountPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let cluster = resolve_cluster(&state, path.cluster)?;
+
+    let outcome = queue::stop_job(&state, cluster, &path.address, StopRequest::Pause).await?;
+    Ok(Json(JobActionResponse {
+        cluster,
+        address: path.address,
+        action: "pause",
+        outcome,
+    }))
+}
+
+// Admin API that queues the paused run of the address again
+#[instrument(skip(state))]
+pub async fn resume_job(
+    State(state): State<AppState>,
+    Path(path): Path<AccountPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let cluster = resolve_cluster(&state, path.cluster)?;
+
+    let outcome = queue::resume_job(&state, cluster, path.address.clone()).await?;
+    Ok(Json(JobActionResponse {
+        cluster,
+        address: path.address,
+        action: "resume",
+        outcome,
+    }))
+}
+
+// Admin API that queues a repair job backfilling the missing history of the address
+#[instrument(skip(state))]
+pub async fn repair_job(
+    State(state): State<AppState>,
+    Path(path): Path<AccountPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let cluster = resolve_cluster(&state, path.cluster)?;
+
+    let outcome = queue::repair_job(&state, cluster, path.address.clone()).await?;
+    Ok(Json(JobActionResponse {
+        cluster,
+        address: path.address,
+        action: "repair",
+        outcome,
+    }))
+}
+
+// Outcome of an admin action on the live ingestion of an address
+#[derive(Debug, Serialize)]
+pub struct WatchResponse {
+    cluster: Cluster,
+    address: String,
+    watched: bool,
+}
+
+// Admin API that starts the live ingestion of an indexed address
+#[instrument(skip(state))]
+pub async fn watch_address(
+    State(state): State<AppState>,
+    Path(path): Path<AccountPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let cluster = resolve_cluster(&state, path.cluster)?;
+
+    live::watch(&state, cluster, &path.address).await?;
+    Ok(Json(WatchResponse {
+        cluster,
+        address: path.address,
+        watched: true,
+    }))
+}
+
+// Admin API that stops the live ingestion of the address
+#[instrument(skip(state))]
+pub async fn unwatch_address(
+    State(state): State<AppState>,
+    Path(path): Path<AccountPath>,
+) -> Result<impl IntoResponse, AppError> {
+    let cluster = resolve_cluster(&state, path.cluster)?;
+
+    live::unwatch(&state, cluster, &path.address).await?;
+    Ok(Json(WatchResponse {
+        cluster,
+        address: path.address,
+        watched: false,
+    }))
+}
@@ -0,0 +1,274 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use futures::{StreamExt, TryStreamExt, stream};
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use tokio::time::Instant;
+use tracing::{error, info, instrument};
+
+use crate::{
+    app_state::{AddressSession, AppState},
+    cluster::Cluster,
+    error::AppError,
+    message::SyncStatus,
+    models::{JobKind, JobPriority, Transaction, TransactionSignature},
+    queue,
+    solana::{fetch_or_skip, signature_record},
+};
+
+// Number of signatures asked for in a single RPC call while scanning
+const SCAN_BATCH_SIZE: usize = 1000;
+
+// A range of slots where the cluster has signatures of the address that are not stored
+#[derive(Debug, serde::Serialize)]
+pub struct SlotGap {
+    // Slots of the oldest and the newest missing signature
+    pub from_slot: i64,
+    pub to_slot: i64,
+    pub signatures: usize,
+}
+
+// What the integrity scan of an address found
+#[derive(Debug, serde::Serialize)]
+pub struct IntegrityReport {
+    pub cluster: Cluster,
+    pub address: String,
+    // Stored signatures without a stored transaction, the dead letters aside
+    pub missing_transactions: Vec<String>,
+    // Signatures between the oldest and the newest stored one that are not stored
+    pub missing_signatures: Vec<String>,
+    pub slot_gaps: Vec<SlotGap>,
+}
+
+// The report of a scan along with the missing signatures in DB format
+struct Scan {
+    report: IntegrityReport,
+    missing_signatures: Vec<TransactionSignature>,
+}
+
+// Scan the stored history of the address without repairing it
+pub async fn scan_address(
+    state: &AppState,
+    cluster: Cluster,
+    address: &str,
+) -> Result<IntegrityReport, AppError> {
+    Ok(scan(state, None, cluster, address).await?.report)
+}
+
+// Find the signatures of the address whose transaction was never stored
+// e.g. because a run failed between storing a page of signatures and its transactions,
+// and the signatures that the cluster has within the stored range of slots but the DB hasn't
+async fn scan(
+    state: &AppState,
+    session: Option<&AddressSession>,
+    cluster: Cluster,
+    address: &str,
+) -> Result<Scan, AppError> {
+    let public_key = Pubkey::from_str(address)?;
+    let rpc = state.rpc_for(cluster)?;
+
+    if !state.db.check_account_exists(cluster, address).await {
+        return Err(AppError::NotFound("Account Not Found".to_string()));
+    }
+
+    let missing_transactions = state
+        .db
+        .get_signatures_without_transactions(cluster, address)
+        .await?;
+
+    // Page through the signatures the cluster has between the newest and the oldest
+    // stored one and compare every page with the stored signatures.
+    // The newer signatures are left to a refresh and the older ones to the bounds
+    let mut missing_signatures: Vec<TransactionSignature> = vec![];
+    let mut slot_gaps: Vec<SlotGap> = vec![];
+    let newest = state
+        .db
+        .get_latest_signature(cluster, address.to_string())
+        .await?;
+    let oldest = state.db.get_oldest_signature(cluster, address).await?;
+    if let (Some(newest), Some(oldest)) = (newest, oldest)
+        && newest != oldest
+    {
+        let mut before = Some(Signature::from_str(&newest)?);
+        let until = Some(Signature::from_str(&oldest)?);
+        // The gap that the missing signatures of the current run belong to
+        let mut gap: Option<SlotGap> = None;
+
+        loop {
+            let signatures = rpc
+                .get_signatures_for_address_with_config(
+                    session,
+                    &public_key,
+                    GetConfirmedSignaturesForAddress2Config {
+                        before,
+                        until,
+                        limit: Some(SCAN_BATCH_SIZE),
+                        commitment: Some(state.config.commitment()),
+                    },
+                )
+                .await?;
+            let Some(last_signature) = signatures.last() else {
+                break;
+            };
+            before = Some(Signature::from_str(&last_signature.signature)?);
+
+            let page: Vec<String> = signatures
+                .iter()
+                .map(|sign| sign.signature.clone())
+                .collect();
+            let stored = state
+                .db
+                .get_stored_signatures(cluster, address, &page)
+                .await?;
+
+            // A page is ordered from the newest signature to the oldest,
+            // so a gap grows towards the older slots until a stored signature closes it
+            for sign in &signatures {
+                if stored.contains(&sign.signature) {
+                    slot_gaps.extend(gap.take());
+                    continue;
+                }
+
+                let slot = sign.slot as i64;
+                let gap = gap.get_or_insert(SlotGap {
+                    from_slot: slot,
+                    to_slot: slot,
+                    signatures: 0,
+                });
+                gap.from_slot = slot;
+                gap.signatures += 1;
+                missing_signatures.push(signature_record(cluster, address, sign)?);
+            }
+        }
+        slot_gaps.extend(gap);
+    }
+
+    Ok(Scan {
+        report: IntegrityReport {
+            cluster,
+            address: address.to_string(),
+            missing_transactions,
+            missing_signatures: missing_signatures
+                .iter()
+                .map(|sign| sign.signature.clone())
+                .collect(),
+            slot_gaps,
+        },
+        missing_signatures,
+    })
+}
+
+// Repair job of an address
+// Scans its stored history and backfills exactly the signatures and transactions
+// that are missing, a transaction that still can't be fetched becomes a dead letter
+#[instrument(skip(state, session))]
+pub async fn repairer(
+    state: AppState,
+    session: Arc<AddressSession>,
+    cluster: Cluster,
+    address: String,
+) -> Result<(), AppError> {
+    let Scan {
+        report,
+        missing_signatures,
+    } = scan(&state, Some(&session), cluster, &address).await?;
+    info!(
+        missing_transactions = report.missing_transactions.len(),
+        missing_signatures = report.missing_signatures.len(),
+        slot_gaps = report.slot_gaps.len(),
+        "Integrity of the address scanned"
+    );
+
+    // Store the missing signatures first, their transactions are then backfilled with the rest
+    state
+        .db
+        .upsert_transaction_signatures(&missing_signatures)
+        .await?;
+
+    let backfill: Vec<String> = report
+        .missing_transactions
+        .iter()
+        .chain(&report.missing_signatures)
+        .cloned()
+        .collect();
+    let mut stored = 0;
+    for chunk in backfill.chunks(state.config.insert_batch_size) {
+        let txns: Vec<Transaction> =
+            stream::iter(chunk.to_vec())
+                .map(|signature| {
+                    let state = state.clone();
+                    let session = session.clone();
+                    let address = address.clone();
+                    async move {
+                        fetch_or_skip(&state, Some(&session), cluster, &address, &signature).await
+                    }
+                })
+                .buffered(state.config.fetch_concurrency)
+                .try_filter_map(|txn| async move { Ok(txn) })
+                .try_collect()
+                .await?;
+
+        state.db.upsert_transactions(&address, &txns).await?;
+        stored += txns.len();
+    }
+
+    info!(
+        stored,
+        skipped = backfill.len() - stored,
+        "Repair of the address completed"
+    );
+    session.emit_event(SyncStatus::Completed).await;
+
+    Ok(())
+}
+
+// Periodically queue a repair job for every address that has no run going on
+// The repairs run as background jobs, so the runs that clients wait for go first
+pub fn spawn_integrity_checks(state: &AppState) {
+    let interval = state.config.integrity_check_interval;
+    if interval.is_zero() {
+        return;
+    }
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        info!("Integrity checks started");
+        // The first round waits for a whole interval instead of running on startup
+        let mut ticker = tokio::time::interval_at(Instant::now() + interval, interval);
+        loop {
+            ticker.tick().await;
+            for &cluster in state.rpc.keys() {
+                if let Err(err) = schedule_repairs(&state, cluster).await {
+                    error!(%cluster, %err, "Error occurred while scheduling the integrity repairs");
+                }
+            }
+        }
+    });
+}
+
+async fn schedule_repairs(state: &AppState, cluster: Cluster) -> Result<(), AppError> {
+    let addresses = state.db.get_idle_addresses(cluster).await?;
+
+    let mut queued = 0;
+    for address in addresses {
+        // An address that already has a job is checked in the next round
+        if state.find_session(cluster, &address).is_some() {
+            continue;
+        }
+
+        queue::enqueue(
+            state,
+            cluster,
+            address,
+            JobKind::Repair,
+            JobPriority::Background,
+            None,
+        )
+        .await?;
+        queued += 1;
+    }
+    info!(%cluster, queued, "Integrity repairs scheduled");
+
+    Ok(())
+}
@@ -1,32 +1,73 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use solana_client::nonblocking::rpc_client::RpcClient;
+use tracing::info;
+
+use crate::config::StorageBackend;
 
 pub mod app_state;
+pub mod auth;
+pub mod cluster;
+pub mod config;
 pub mod cors;
 pub mod db;
+pub mod dead_letters;
 pub mod error;
+pub mod finality;
 pub mod handlers;
+pub mod integrity;
+pub mod live;
 pub mod message;
 pub mod models;
+pub mod queue;
+pub mod recovery;
 pub mod routes;
+pub mod rpc;
+pub mod scheduler;
 pub mod solana;
+#[cfg(test)]
+mod testing;
 pub mod tracer;
 
-// Solana Devnet RPC URL
-const DEV_NET: &str = "https://api.devnet.solana.com";
-// Solana Mainnet RPC URL
-const _MAIN_NET: &str = "https://api.mainnet-beta.solana.com";
-
 pub async fn build_app() -> Result<axum::Router, error::AppError> {
-    // Setup Mongo Database
-    let db = db::init().await?;
+    // Load the indexer tunables from the env
+    let config = Arc::new(config::Config::from_env());
+
+    // Setup the storage backend, MongoDB unless the config selects another one
+    let db = db::init(&config).await?;
+
+    // Connect to every enabled Solana cluster through RPC (Remote Procedure Call)
+    // behind the shared rate limiting, retry and failover layer of its endpoint pool
+    let mut rpc: HashMap<_, Arc<dyn rpc::SolanaApi>> = HashMap::new();
+    for (cluster, endpoints) in &config.rpc_endpoints {
+        let cluster_rpc = Arc::new(rpc::SolanaRpc::new(endpoints, &config));
+        cluster_rpc.spawn_health_checks(config.rpc_health_check_interval);
+        rpc.insert(*cluster, cluster_rpc);
+    }
+    info!(clusters = ?config.clusters, default = %config.default_cluster, "RPC clusters ready");
+
+    // Create an AppState containing the Storage, RpcClient and the Config
+    let state = app_state::AppState::new(db, rpc, config);
+
+    // Pick up the runs that the last shutdown interrupted
+    // and start the workers of the indexing job queue
+    recovery::recover_interrupted_runs(&state).await?;
+    queue::start_workers(&state).await?;
+
+    // Promote the confirmed signatures once they are finalized
+    finality::spawn_finality_checks(&state);
 
-    // Connect to the Solana Devnet through RPC (Remote Procedure Call)
-    let rpc = Arc::new(RpcClient::new(DEV_NET.to_string()));
+    // Retry the transactions that could not be fetched while indexing
+    dead_letters::spawn_dead_letter_retries(&state);
 
-    // Create an AppState containing Mongo Database and RpcClient
-    let state = app_state::AppState::new(db, rpc);
+    // Queue the scheduled repairs of the stored history of the idle addresses
+    integrity::spawn_integrity_checks(&state);
+
+    // Refresh the indexed addresses on their own, the more viewed ones more often
+    scheduler::spawn_auto_refresh(&state);
+
+    // Ingest the new transactions of the watched addresses live when it is enabled
+    live::start_watchers(&state).await?;
 
     // Create an app router for handling requests
     // that takes in the AppState to perform DB operations & RPC calls
@@ -34,3 +75,23 @@ pub async fn build_app() -> Result<axum::Router, error::AppError> {
 
     Ok(app)
 }
+
+// Apply the pending data migrations of the stored documents without starting the server
+// A dry run only reports how many documents every pending migration would change
+pub async fn run_migrations(dry_run: bool) -> Result<(), error::AppError> {
+    let config = config::Config::from_env();
+
+    match config.storage_backend {
+        StorageBackend::Mongo => {
+            // Only the documents are migrated here, the indexes are synced when the server starts
+            let storage = db::mongo::MongoStorage::init().await?;
+            let report = storage.migrate(config.legacy_cluster, dry_run).await?;
+            info!(?report, "Migrations finished");
+        }
+        // The SQL backends bring their schema up to date when they connect
+        // and the memory backend starts empty
+        backend => info!(?backend, "The storage backend has no data migrations"),
+    }
+
+    Ok(())
+}
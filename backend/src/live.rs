@@ -0,0 +1,452 @@
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use dashmap::mapref::entry::Entry;
+use futures::StreamExt;
+use solana_client::{
+    nonblocking::pubsub_client::PubsubClient,
+    rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_config::{RpcAccountInfoConfig, RpcTransactionLogsConfig, RpcTransactionLogsFilter},
+    rpc_response::RpcConfirmedTransactionStatusWithSignature,
+};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, instrument, warn};
+
+use crate::{
+    app_state::AppState,
+    cluster::Cluster,
+    error::AppError,
+    message::SyncStatus,
+    models::{Timestamp, WatchedAddress, document_id},
+    solana::{account_update, fetch_or_skip, signature_record},
+};
+
+// Number of signatures asked for in a single RPC call while catching up
+const CATCH_UP_BATCH_SIZE: usize = 1000;
+// Upper limit of the delay between two reconnects of a dropped subscription
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+// The live ingestion of a watched address
+// Its clients follow the ingested transactions and account changes through the sender
+#[derive(Debug)]
+pub struct AddressWatcher {
+    pub sender: broadcast::Sender<SyncStatus>,
+    cancel: CancellationToken,
+}
+
+impl AddressWatcher {
+    fn new() -> Self {
+        let (sender, _) = broadcast::channel(100);
+        AddressWatcher {
+            sender,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    // Nobody may be following the address, so a send without receivers is fine
+    fn emit_event(&self, event: SyncStatus) {
+        let _ = self.sender.send(event);
+    }
+}
+
+// Start the live ingestion of every watched address of the enabled clusters
+pub async fn start_watchers(state: &AppState) -> Result<(), AppError> {
+    if !state.config.live_ingestion {
+        return Ok(());
+    }
+
+    let clusters: Vec<Cluster> = state.rpc.keys().copied().collect();
+    let watches = state.db.get_watched_addresses(&clusters).await?;
+    info!(watched = watches.len(), "Live ingestion started");
+    for watch in watches {
+        spawn_watcher(state, watch.cluster, watch.address);
+    }
+
+    Ok(())
+}
+
+// Watch an indexed address, its new transactions are ingested as soon as they land
+pub async fn watch(state: &AppState, cluster: Cluster, address: &str) -> Result<(), AppError> {
+    if !state.config.live_ingestion {
+        return Err(AppError::BadRequest(
+            "Live ingestion is not enabled".to_string(),
+        ));
+    }
+    Pubkey::from_str(address)?;
+    state.rpc_for(cluster)?;
+    if !state.db.check_account_exists(cluster, address).await {
+        return Err(AppError::BadRequest("Account is not indexed".to_string()));
+    }
+
+    state
+        .db
+        .save_watched_address(&WatchedAddress {
+            id: document_id(cluster, address),
+            cluster,
+            address: address.to_string(),
+            watched_at: Timestamp::now(),
+        })
+        .await?;
+    spawn_watcher(state, cluster, address.to_string());
+
+    Ok(())
+}
+
+// Stop watching the address and close its subscriptions
+pub async fn unwatch(state: &AppState, cluster: Cluster, address: &str) -> Result<(), AppError> {
+    if !state.db.delete_watched_address(cluster, address).await? {
+        return Err(AppError::NotFound("The address is not watched".to_string()));
+    }
+
+    if let Some((_, watcher)) = state.watchers.remove(&(cluster, address.to_string())) {
+        watcher.cancel.cancel();
+    }
+    info!(%cluster, %address, "Address is no longer watched");
+
+    Ok(())
+}
+
+// Follow the live ingestion of a watched address
+pub fn subscribe(
+    state: &AppState,
+    cluster: Cluster,
+    address: &str,
+) -> Result<broadcast::Receiver<SyncStatus>, AppError> {
+    state
+        .watchers
+        .get(&(cluster, address.to_string()))
+        .map(|watcher| watcher.sender.subscribe())
+        .ok_or_else(|| AppError::NotFound("The address is not watched".to_string()))
+}
+
+fn spawn_watcher(state: &AppState, cluster: Cluster, address: String) {
+    // An address that is already watched keeps its running watcher
+    let watcher = match state.watchers.entry((cluster, address.clone())) {
+        Entry::Occupied(_) => return,
+        Entry::Vacant(entry) => Arc::clone(&entry.insert(Arc::new(AddressWatcher::new()))),
+    };
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        run_watcher(&state, cluster, &address, &watcher).await;
+    });
+}
+
+// Keep the subscriptions of the address open until it is unwatched
+// A dropped connection is opened again with backoff and catches up on what it missed
+#[instrument(skip(state, watcher))]
+async fn run_watcher(state: &AppState, cluster: Cluster, address: &str, watcher: &AddressWatcher) {
+    let mut attempt: u32 = 0;
+    loop {
+        let result = ingest(state, cluster, address, watcher, &mut attempt).await;
+        if watcher.cancel.is_cancelled() {
+            info!("Live subscriptions of the address closed");
+            return;
+        }
+
+        let delay = state
+            .config
+            .live_reconnect_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_RECONNECT_DELAY);
+        attempt = attempt.saturating_add(1);
+        match result {
+            Ok(()) => warn!(
+                ?delay,
+                "Live subscriptions of the address dropped, reconnecting"
+            ),
+            Err(err) => {
+                warn!(%err, ?delay, "Live subscriptions of the address failed, reconnecting")
+            }
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = watcher.cancel.cancelled() => return,
+        }
+    }
+}
+
+// Open the logs and account subscriptions of the address and ingest every notification
+// Returns once the connection drops or the address is unwatched
+async fn ingest(
+    state: &AppState,
+    cluster: Cluster,
+    address: &str,
+    watcher: &AddressWatcher,
+    attempt: &mut u32,
+) -> Result<(), AppError> {
+    let public_key = Pubkey::from_str(address)?;
+    let ws_url = state
+        .config
+        .ws_endpoints
+        .get(&cluster)
+        .ok_or_else(|| AppError::BadRequest(format!("Cluster {cluster} is not enabled")))?;
+
+    let client = PubsubClient::new(ws_url).await?;
+    let (mut logs, _logs_unsubscribe) = client
+        .logs_subscribe(
+            RpcTransactionLogsFilter::Mentions(vec![address.to_string()]),
+            RpcTransactionLogsConfig {
+                commitment: Some(state.config.commitment()),
+            },
+        )
+        .await?;
+    let (mut accounts, _accounts_unsubscribe) = client
+        .account_subscribe(
+            &public_key,
+            Some(RpcAccountInfoConfig {
+                commitment: Some(state.config.commitment()),
+                ..Default::default()
+            }),
+        )
+        .await?;
+    *attempt = 0;
+    info!("Live subscriptions of the address opened");
+
+    // Catch up on what landed while there were no subscriptions,
+    // right after subscribing so that nothing falls in between
+    catch_up(state, cluster, address, public_key, watcher).await?;
+    refresh_account(state, cluster, address, public_key, watcher).await?;
+
+    loop {
+        tokio::select! {
+            _ = watcher.cancel.cancelled() => return Ok(()),
+            log = logs.next() => {
+                let Some(log) = log else {
+                    return Ok(());
+                };
+                info!(signature = %log.value.signature, "Live transaction notified");
+                catch_up(state, cluster, address, public_key, watcher).await?;
+            }
+            account = accounts.next() => {
+                if account.is_none() {
+                    return Ok(());
+                }
+                refresh_account(state, cluster, address, public_key, watcher).await?;
+            }
+        }
+    }
+}
+
+// Store every signature newer than the latest stored one along with its transaction
+// and push the transactions to the clients, the oldest first.
+// The notified signature alone is not enough after a reconnect or a burst of transactions
+async fn catch_up(
+    state: &AppState,
+    cluster: Cluster,
+    address: &str,
+    public_key: Pubkey,
+    watcher: &AddressWatcher,
+) -> Result<(), AppError> {
+    let rpc = state.rpc_for(cluster)?;
+    let until = state
+        .db
+        .get_latest_signature(cluster, address.to_string())
+        .await?
+        .as_deref()
+        .map(Signature::from_str)
+        .transpose()?;
+
+    let commitment = state.config.commitment();
+    let signatures = page_new_signatures(until, |before| {
+        rpc.get_signatures_for_address_with_config(
+            None,
+            &public_key,
+            GetConfirmedSignaturesForAddress2Config {
+                before,
+                until,
+                limit: Some(CATCH_UP_BATCH_SIZE),
+                commitment: Some(commitment),
+            },
+        )
+    })
+    .await?;
+    if signatures.is_empty() {
+        return Ok(());
+    }
+
+    let records = signatures
+        .iter()
+        .rev()
+        .map(|sign| signature_record(cluster, address, sign))
+        .collect::<Result<Vec<_>, _>>()?;
+    state.db.upsert_transaction_signatures(&records).await?;
+
+    for record in &records {
+        let Some(txn) = fetch_or_skip(state, None, cluster, address, &record.signature).await?
+        else {
+            continue;
+        };
+        state
+            .db
+            .upsert_transactions(address, std::slice::from_ref(&txn))
+            .await?;
+
+        match serde_json::to_string(&txn) {
+            Ok(data) => watcher.emit_event(SyncStatus::LiveTransaction(data)),
+            Err(err) => error!("Error occurred while serializing the live transaction: {err}"),
+        }
+    }
+    info!(
+        ingested = records.len(),
+        "Live transactions of the address ingested"
+    );
+
+    Ok(())
+}
+
+// Page through the signatures newer than the until signature, the newest first
+// get_page is asked for the page before the given signature, or the latest page for None
+async fn page_new_signatures<F, Fut>(
+    until: Option<Signature>,
+    mut get_page: F,
+) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, AppError>
+where
+    F: FnMut(Option<Signature>) -> Fut,
+    Fut: Future<Output = Result<Vec<RpcConfirmedTransactionStatusWithSignature>, AppError>>,
+{
+    let mut signatures = vec![];
+    let mut before = None;
+    loop {
+        let page = get_page(before).await?;
+        let Some(last_signature) = page.last() else {
+            break;
+        };
+        before = Some(Signature::from_str(&last_signature.signature)?);
+        let full = page.len() == CATCH_UP_BATCH_SIZE;
+        signatures.extend(page);
+
+        // Without a stored signature only the latest page is new to the address,
+        // the older history is left to the indexing
+        if until.is_none() || !full {
+            break;
+        }
+    }
+
+    Ok(signatures)
+}
+
+// Store the latest account data of the address and push it to the clients
+async fn refresh_account(
+    state: &AppState,
+    cluster: Cluster,
+    address: &str,
+    public_key: Pubkey,
+    watcher: &AddressWatcher,
+) -> Result<(), AppError> {
+    let account = state
+        .rpc_for(cluster)?
+        .get_account(None, &public_key, state.config.commitment())
+        .await?;
+
+    let has_history = state.db.get_signatures_count(cluster, address).await? > 0;
+    let updated = state
+        .db
+        .update_account(
+            cluster,
+            address,
+            account_update(account.as_ref(), has_history),
+        )
+        .await?;
+    watcher.emit_event(SyncStatus::AccountData(serde_json::to_string(&updated)?));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    // A page of signatures numbered from the newest one down
+    fn page(from: u64, len: u64) -> Vec<RpcConfirmedTransactionStatusWithSignature> {
+        (from..from + len)
+            .map(|n| RpcConfirmedTransactionStatusWithSignature {
+                signature: signature(n).to_string(),
+                slot: 1_000_000 - n,
+                err: None,
+                memo: None,
+                block_time: None,
+                confirmation_status: None,
+            })
+            .collect()
+    }
+
+    fn signature(n: u64) -> Signature {
+        let mut bytes = [0u8; 64];
+        bytes[..8].copy_from_slice(&n.to_le_bytes());
+        Signature::from(bytes)
+    }
+
+    // Pages through the given pages and records the before signature of every request
+    async fn catch_up_pages(
+        until: Option<Signature>,
+        pages: Vec<Vec<RpcConfirmedTransactionStatusWithSignature>>,
+    ) -> (Vec<u64>, Vec<Option<Signature>>) {
+        let pages = Mutex::new(pages.into_iter());
+        let requests = Mutex::new(vec![]);
+
+        let signatures = page_new_signatures(until, |before| {
+            requests.lock().unwrap().push(before);
+            let page = pages.lock().unwrap().next().unwrap_or_default();
+            async move { Ok(page) }
+        })
+        .await
+        .unwrap();
+
+        let slots = signatures
+            .iter()
+            .map(|sign| 1_000_000 - sign.slot)
+            .collect();
+        (slots, requests.into_inner().unwrap())
+    }
+
+    #[tokio::test]
+    async fn catch_up_pages_back_to_the_stored_signature() {
+        let batch = CATCH_UP_BATCH_SIZE as u64;
+        let pages = vec![page(0, batch), page(batch, batch), page(2 * batch, 3)];
+
+        let (signatures, requests) = catch_up_pages(Some(signature(9_999)), pages).await;
+
+        assert_eq!(signatures, (0..2 * batch + 3).collect::<Vec<_>>());
+        assert_eq!(
+            requests,
+            [
+                None,
+                Some(signature(batch - 1)),
+                Some(signature(2 * batch - 1))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn catch_up_without_a_stored_signature_takes_the_latest_page() {
+        let batch = CATCH_UP_BATCH_SIZE as u64;
+        let pages = vec![page(0, batch), page(batch, batch)];
+
+        let (signatures, requests) = catch_up_pages(None, pages).await;
+
+        assert_eq!(signatures.len() as u64, batch);
+        assert_eq!(requests, [None]);
+    }
+
+    #[tokio::test]
+    async fn catch_up_stops_at_an_empty_page() {
+        let batch = CATCH_UP_BATCH_SIZE as u64;
+
+        let (signatures, requests) = catch_up_pages(Some(signature(9_999)), vec![]).await;
+        assert!(signatures.is_empty());
+        assert_eq!(requests, [None]);
+
+        let pages = vec![page(0, batch), vec![]];
+        let (signatures, requests) = catch_up_pages(Some(signature(9_999)), pages).await;
+        assert_eq!(signatures.len() as u64, batch);
+        assert_eq!(requests.len(), 2);
+    }
+}
@@ -11,6 +11,13 @@ async fn main() -> Result<(), backend::error::AppError> {
     // Lives as long as the main fn
     let _guard = backend::tracer::setup_tracing();
 
+    // `backend migrate [--dry-run]` applies the data migrations of the storage and exits
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|command| command == "migrate") {
+        let dry_run = args.iter().any(|arg| arg == "--dry-run");
+        return backend::run_migrations(dry_run).await;
+    }
+
     // Build the app that initiates the DB, connects to Solana RPC and includes them in the
     // app state for the axum route handlers
     let app = backend::build_app().await?;
@@ -1,11 +1,17 @@
 // SyncStatus enum is used in SSE API communication
 #[derive(Clone, Debug)]
 pub enum SyncStatus {
+    Queued(String),
     Indexing,
     Syncing,
     AccountData(String),
     TransactionSignatures(String),
     TransactionDetails(String),
+    RateLimited(String),
+    // A transaction of a watched address that was ingested live
+    LiveTransaction(String),
     Completed,
+    Paused,
+    Cancelled,
     Error(String),
 }
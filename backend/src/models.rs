@@ -1,71 +1,758 @@
+use chrono::{DateTime, Utc};
 use mongodb::bson::DateTime as BsonDateTime;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value;
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::cluster::Cluster;
+
+// The _id of a document scoped by its cluster, e.g. "devnet:<address>"
+pub fn document_id(cluster: Cluster, key: &str) -> String {
+    format!("{cluster}:{key}")
+}
+
+// The _id of a document linking an address to one of its transactions
+// e.g. "devnet:<address>:<signature>"
+pub fn membership_id(cluster: Cluster, address: &str, signature: &str) -> String {
+    format!("{cluster}:{address}:{signature}")
+}
+
+// A point in time as milliseconds since the unix epoch
+// The storage backends keep it in their own time type, MongoDB as a BSON datetime
+// which is also how it is serialized so the stored documents and API responses keep their shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    pub fn now() -> Self {
+        Self(Utc::now().timestamp_millis())
+    }
+
+    pub fn from_millis(millis: i64) -> Self {
+        Self(millis)
+    }
+
+    pub fn timestamp_millis(&self) -> i64 {
+        self.0
+    }
+
+    pub fn from_chrono(time: DateTime<Utc>) -> Self {
+        Self(time.timestamp_millis())
+    }
+
+    pub fn to_chrono(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(self.0).unwrap_or_default()
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BsonDateTime::from_millis(self.0).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        BsonDateTime::deserialize(deserializer).map(|time| Self(time.timestamp_millis()))
+    }
+}
+
+// Lifecycle of an address in the indexer
+//
+//   Queued -> Indexing/Syncing -> Idle
+//                              -> Partial (failed after some batches were stored
+//                                          or finished without the transactions it skipped)
+//                              -> Failed (failed before anything was stored)
+//                              -> Paused (stopped until it is resumed)
+//                              -> Cancelled
+//
+// Partial, Failed and Cancelled runs are resumed by indexing or refreshing the address again,
+// a Paused run only through the admin resume endpoint which queues it again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum IndexingState {
-    Idle,
+    Queued,
     Indexing,
     Syncing,
+    Idle,
+    Partial,
+    Failed,
+    Paused,
+    Cancelled,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl IndexingState {
+    pub const ALL: [IndexingState; 8] = [
+        IndexingState::Queued,
+        IndexingState::Indexing,
+        IndexingState::Syncing,
+        IndexingState::Idle,
+        IndexingState::Partial,
+        IndexingState::Failed,
+        IndexingState::Paused,
+        IndexingState::Cancelled,
+    ];
+
+    // Whether an address in this state is allowed to move to the next one
+    pub fn can_transition_to(&self, next: IndexingState) -> bool {
+        use IndexingState::*;
+
+        matches!(
+            (*self, next),
+            // A run starts from a queued, finished or stopped address and a running state
+            // can move to itself so a run interrupted by a crash can be resumed.
+            // An indexed address moves back to Indexing when its history is extended
+            (Queued | Indexing | Idle | Partial | Failed | Cancelled, Indexing)
+                | (Queued | Syncing | Idle | Partial | Failed | Cancelled, Syncing)
+                | (Indexing | Syncing, Idle | Partial | Failed)
+                // The startup recovery queues an interrupted run again
+                // and a paused run is only resumed through the queue
+                | (Indexing | Syncing | Paused, Queued)
+                // The startup recovery can fail a queued run instead
+                | (Queued, Partial | Failed)
+                | (Queued | Indexing | Syncing, Paused | Cancelled)
+        )
+    }
+
+    // The states an address can move to the next state from
+    pub fn sources(next: IndexingState) -> Vec<IndexingState> {
+        IndexingState::ALL
+            .into_iter()
+            .filter(|state| state.can_transition_to(next))
+            .collect()
+    }
+
+    // Whether a run of the address is in progress
+    pub fn is_running(&self) -> bool {
+        matches!(self, IndexingState::Indexing | IndexingState::Syncing)
+    }
+
+    // Whether the indexing of the address stopped before it was completed
+    pub fn is_resumable(&self) -> bool {
+        matches!(
+            self,
+            IndexingState::Queued
+                | IndexingState::Indexing
+                | IndexingState::Partial
+                | IndexingState::Failed
+                | IndexingState::Cancelled
+        )
+    }
+}
+
+impl std::fmt::Display for IndexingState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match self {
+            IndexingState::Queued => "queued",
+            IndexingState::Indexing => "indexing",
+            IndexingState::Syncing => "syncing",
+            IndexingState::Idle => "idle",
+            IndexingState::Partial => "partial",
+            IndexingState::Failed => "failed",
+            IndexingState::Paused => "paused",
+            IndexingState::Cancelled => "cancelled",
+        };
+        f.write_str(state)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddressIndexingState {
     #[serde(rename = "_id")]
+    pub id: String,
+    pub cluster: Cluster,
     pub address: String,
     pub state: IndexingState,
-    pub created_at: BsonDateTime,
-    pub updated_at: BsonDateTime,
+    #[serde(default)]
+    pub checkpoint: Option<IndexingCheckpoint>,
+    // How deep the history of the address is indexed
+    #[serde(default)]
+    pub bounds: IndexingBounds,
+    // Error of the last failed run, cleared once a run completes
+    #[serde(default)]
+    pub last_error: Option<IndexingError>,
+    #[serde(default)]
+    pub started_at: Option<Timestamp>,
+    #[serde(default)]
+    pub finished_at: Option<Timestamp>,
+    pub created_at: Timestamp,
+    pub updated_at: Timestamp,
+}
+
+impl AddressIndexingState {
+    // Tell the client whether indexing or refreshing the address again is worth it
+    pub fn retry_recommendation(&self) -> RetryRecommendation {
+        // A run with an until signature is a sync of an already indexed address
+        let action = match &self.checkpoint {
+            Some(checkpoint) if checkpoint.until_signature.is_some() => "refresh",
+            _ => "index",
+        };
+
+        match self.state {
+            IndexingState::Queued | IndexingState::Indexing | IndexingState::Syncing => {
+                RetryRecommendation::none("The address is being indexed")
+            }
+            IndexingState::Idle => RetryRecommendation::none("The address is indexed"),
+            IndexingState::Paused => RetryRecommendation::none(
+                "The run was paused, an admin resumes it from its checkpoint through POST /api/admin/jobs/{address}/resume",
+            ),
+            IndexingState::Cancelled => RetryRecommendation::retry(
+                action,
+                "The run was cancelled and resumes from its checkpoint",
+            ),
+            IndexingState::Partial | IndexingState::Failed => match &self.last_error {
+                // The skipped transactions are retried in the background as dead letters
+                Some(error) if error.kind == SKIPPED_TRANSACTIONS => {
+                    RetryRecommendation::none(&error.message)
+                }
+                // These fail the same way however often they are retried
+                Some(error) if error.kind == "bad_request" || error.kind == "not_found" => {
+                    RetryRecommendation::none(&format!(
+                        "The run failed with an error that a retry would hit again: {}",
+                        error.message
+                    ))
+                }
+                _ => RetryRecommendation::retry(
+                    action,
+                    "The run failed with an error that a retry can get past, it resumes from its checkpoint",
+                ),
+            },
+        }
+    }
+}
+
+// Kind of the error of a run that finished without the transactions it couldn't fetch
+pub const SKIPPED_TRANSACTIONS: &str = "skipped_transactions";
+
+// Error of a failed indexing/syncing run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexingError {
+    // The AppError variant e.g. "solana" or "database", or SKIPPED_TRANSACTIONS
+    pub kind: String,
+    pub message: String,
+    pub occurred_at: Timestamp,
+}
+
+// Whether the client should index/refresh the address again and with which route
+#[derive(Debug, Serialize)]
+pub struct RetryRecommendation {
+    pub retry: bool,
+    pub action: Option<&'static str>,
+    pub reason: String,
+}
+
+impl RetryRecommendation {
+    fn none(reason: &str) -> Self {
+        RetryRecommendation {
+            retry: false,
+            action: None,
+            reason: reason.to_string(),
+        }
+    }
+
+    fn retry(action: &'static str, reason: &str) -> Self {
+        RetryRecommendation {
+            retry: true,
+            action: Some(action),
+            reason: reason.to_string(),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct UpdateAddressIndexingState {
     pub state: IndexingState,
-    pub updated_at: BsonDateTime,
+    pub checkpoint: Option<IndexingCheckpoint>,
+    pub last_error: Option<IndexingError>,
+    pub updated_at: Timestamp,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// Progress of an indexing/syncing run saved after every batch
+// so an interrupted run can continue paging from where it stopped
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexingCheckpoint {
+    pub before_signature: Option<String>,
+    pub until_signature: Option<String>,
+    pub batch: i64,
+    pub fetched_signatures: i64,
+    pub fetched_transactions: i64,
+}
+
+// How deep the history of an address is indexed
+// Paging stops at the first signature that crosses any of the bounds
+// while an unset bound doesn't limit the history at all
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IndexingBounds {
+    // Maximum number of signatures stored for the address
+    #[serde(default)]
+    pub max_signatures: Option<i64>,
+    // Oldest block time (unix timestamp in seconds) of the stored signatures
+    #[serde(default)]
+    pub since: Option<i64>,
+    // Lowest slot of the stored signatures
+    #[serde(default)]
+    pub min_slot: Option<i64>,
+}
+
+impl IndexingBounds {
+    // Whether the history within these bounds includes the whole history within the other ones
+    pub fn covers(&self, other: &IndexingBounds) -> bool {
+        // An unset bound covers any other, a set bound only covers a set one that it reaches
+        fn covers(this: Option<i64>, other: Option<i64>, reaches: fn(i64, i64) -> bool) -> bool {
+            match (this, other) {
+                (None, _) => true,
+                (Some(_), None) => false,
+                (Some(this), Some(other)) => reaches(this, other),
+            }
+        }
+
+        covers(self.max_signatures, other.max_signatures, |this, other| {
+            this >= other
+        }) && covers(self.since, other.since, |this, other| this <= other)
+            && covers(self.min_slot, other.min_slot, |this, other| this <= other)
+    }
+
+    // Whether a signature is older than the time or slot bound
+    pub fn excludes(&self, slot: i64, block_time: Option<i64>) -> bool {
+        self.min_slot.is_some_and(|min_slot| slot < min_slot)
+            || matches!((self.since, block_time), (Some(since), Some(time)) if time < since)
+    }
+}
+
+// Whether an account exists at the address
+// Closed and never funded addresses have no account but a closed one still has a history
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountStatus {
+    #[default]
+    Active,
+    Closed,
+    Nonexistent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Account {
     #[serde(rename = "_id")]
+    pub id: String,
+    pub cluster: Cluster,
     pub address: String,
+    // The account data below is zeroed for an address without an account
+    #[serde(default)]
+    pub status: AccountStatus,
     pub lamports: i64,
     pub owner: String,
     pub executable: bool,
     pub data_length: i64,
     pub rent_epoch: i64,
-    pub indexed_at: BsonDateTime,
-    pub last_updated_at: BsonDateTime,
+    pub indexed_at: Timestamp,
+    pub last_updated_at: Timestamp,
 }
 
 #[derive(Debug)]
 pub struct UpdateAccount {
+    pub status: AccountStatus,
     pub lamports: i64,
     pub owner: String,
     pub executable: bool,
     pub data_length: i64,
     pub rent_epoch: i64,
-    pub last_updated_at: BsonDateTime,
+    pub last_updated_at: Timestamp,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// A transaction signature of an indexed address
+// The same signature is stored once for every address that it belongs to
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionSignature {
     #[serde(rename = "_id")]
+    pub id: String,
+    pub cluster: Cluster,
     pub signature: String,
     pub account_address: String,
     pub slot: i64,
     pub block_time: Option<i64>,
     pub confirmation_status: String,
-    pub indexed_at: BsonDateTime,
+    pub indexed_at: Timestamp,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// A transaction body stored once per signature however many indexed addresses share it
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     #[serde(rename = "_id")]
+    pub id: String,
+    pub cluster: Cluster,
     pub signature: String,
-    pub account_address: String,
     pub slot: i64,
     pub block_time: Option<i64>,
+    // "legacy" or the version number of a versioned transaction e.g. "0"
+    #[serde(default)]
+    pub version: Option<String>,
+    // Every account key of the transaction including the ones loaded from lookup tables
+    #[serde(default)]
+    pub account_keys: Vec<String>,
+    // Addresses that a v0 transaction loaded from its address lookup tables
+    #[serde(default)]
+    pub loaded_addresses: LoadedAddresses,
     pub transaction: Value,
-    pub indexed_at: BsonDateTime,
+    pub indexed_at: Timestamp,
+}
+
+// Links an indexed address to a stored transaction body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressTransaction {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub cluster: Cluster,
+    pub account_address: String,
+    pub signature: String,
+    pub slot: i64,
+    pub block_time: Option<i64>,
+    pub indexed_at: Timestamp,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LoadedAddresses {
+    pub writable: Vec<String>,
+    pub readonly: Vec<String>,
+}
+
+// The run that picks up an address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobKind {
+    Index,
+    Refresh,
+    // Backfill what is missing from the stored history of an indexed address
+    Repair,
+}
+
+// Jobs with a higher priority are picked up first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobPriority {
+    // Runs started by the indexer itself e.g. the startup recovery
+    Background,
+    // Runs that a client is waiting for
+    Interactive,
+}
+
+impl JobPriority {
+    pub fn rank(&self) -> i32 {
+        match self {
+            JobPriority::Background => 0,
+            JobPriority::Interactive => 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Pending,
+    Running,
+}
+
+// A run of an address waiting in (or taken from) the persistent job queue
+// There is at most one job per address, it is deleted once its run is over
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexingJob {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub cluster: Cluster,
+    pub address: String,
+    pub kind: JobKind,
+    pub priority: i32,
+    pub status: JobStatus,
+    pub enqueued_at: Timestamp,
+    pub started_at: Option<Timestamp>,
+    // Bounds of the history requested for an index job, the stored ones are used without them
+    #[serde(default)]
+    pub bounds: Option<IndexingBounds>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeadLetterStatus {
+    // Waiting for its next retry
+    Pending,
+    // Ran out of retries and is left for an operator to look into
+    Failed,
+}
+
+// A signature of an address whose transaction could not be fetched while indexing
+// The run carries on without it and the transaction is retried in the background
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    // Same as the _id of the signature of the address
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub cluster: Cluster,
+    pub account_address: String,
+    pub signature: String,
+    pub status: DeadLetterStatus,
+    // Number of times fetching the transaction has failed
+    pub attempts: i32,
+    pub error: IndexingError,
+    pub next_attempt_at: Timestamp,
+    pub created_at: Timestamp,
+    pub updated_at: Timestamp,
+}
+
+// When the scheduler refreshes an indexed address on its own
+// The interval follows how often the address is viewed, so a popular address stays fresh
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshSchedule {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub cluster: Cluster,
+    pub address: String,
+    pub interval_secs: i64,
+    pub last_viewed_at: Timestamp,
+    pub next_refresh_at: Timestamp,
+}
+
+// An address whose new transactions are ingested live while the live ingestion is on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchedAddress {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub cluster: Cluster,
+    pub address: String,
+    pub watched_at: Timestamp,
+}
+
+// Confirmation status of the signatures that can't be rolled back anymore
+pub const FINALIZED: &str = "finalized";
+
+// Outcome of a bulk upsert
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct UpsertCounts {
+    // Documents that were not stored yet
+    pub inserted: u64,
+    // Stored documents that changed e.g. a promoted confirmation status
+    pub updated: u64,
+    // Stored documents that were already up to date
+    pub skipped: u64,
+}
+
+// Outcome of the upsert of a single document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upserted {
+    Inserted,
+    Updated,
+    Skipped,
+}
+
+impl UpsertCounts {
+    pub fn add(&mut self, upserted: Upserted) {
+        match upserted {
+            Upserted::Inserted => self.inserted += 1,
+            Upserted::Updated => self.updated += 1,
+            Upserted::Skipped => self.skipped += 1,
+        }
+    }
+
+    // A transaction is new to the address when its link is new,
+    // whether or not another address already stored its body.
+    // Otherwise it only counts as updated when its body changed
+    pub fn add_transaction(&mut self, body: Upserted, link: Upserted) {
+        self.add(match (link, body) {
+            (Upserted::Inserted, _) => Upserted::Inserted,
+            (_, Upserted::Skipped) => Upserted::Skipped,
+            _ => Upserted::Updated,
+        });
+    }
+}
+
+// A stored signature that isn't finalized yet
+#[derive(Debug, Deserialize)]
+pub struct UnfinalizedSignature {
+    pub signature: String,
+    pub indexed_at: Timestamp,
+}
+
+// The signatures and transactions only count once they are finalized,
+// the ones that can still be rolled back are counted as unfinalized.
+// The transactions that could not be fetched are counted as dead letters,
+// pending while they are retried and failed once they ran out of retries
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexerStats {
+    pub account_exists: bool,
+    pub signatures: i64,
+    pub transactions: i64,
+    pub unfinalized_signatures: i64,
+    pub pending_dead_letters: i64,
+    pub failed_dead_letters: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_start_from_queued_finished_and_stopped_states() {
+        use IndexingState::*;
+
+        for state in [Queued, Idle, Partial, Failed, Cancelled] {
+            assert!(state.can_transition_to(Indexing), "{state} -> indexing");
+            assert!(state.can_transition_to(Syncing), "{state} -> syncing");
+        }
+    }
+
+    #[test]
+    fn interrupted_runs_resume_in_place() {
+        use IndexingState::*;
+
+        assert!(Indexing.can_transition_to(Indexing));
+        assert!(Syncing.can_transition_to(Syncing));
+        assert!(!Indexing.can_transition_to(Syncing));
+        assert!(!Syncing.can_transition_to(Indexing));
+    }
+
+    #[test]
+    fn runs_end_in_a_finished_or_stopped_state() {
+        use IndexingState::*;
+
+        for running in [Indexing, Syncing] {
+            for next in [Idle, Partial, Failed, Paused, Cancelled, Queued] {
+                assert!(running.can_transition_to(next), "{running} -> {next}");
+            }
+        }
+    }
+
+    #[test]
+    fn paused_runs_only_resume_through_the_queue() {
+        use IndexingState::*;
+
+        assert!(Paused.can_transition_to(Queued));
+        for next in [Indexing, Syncing, Idle, Cancelled, Paused] {
+            assert!(!Paused.can_transition_to(next), "paused -> {next}");
+        }
+    }
+
+    #[test]
+    fn paused_runs_are_not_resumed_by_a_new_request() {
+        assert!(!IndexingState::Paused.is_resumable());
+
+        let record = AddressIndexingState {
+            id: document_id(Cluster::Devnet, "address"),
+            cluster: Cluster::Devnet,
+            address: "address".to_string(),
+            state: IndexingState::Paused,
+            checkpoint: None,
+            bounds: IndexingBounds::default(),
+            last_error: None,
+            started_at: None,
+            finished_at: None,
+            created_at: Timestamp::now(),
+            updated_at: Timestamp::now(),
+        };
+        let recommendation = record.retry_recommendation();
+        assert!(!recommendation.retry);
+        assert_eq!(recommendation.action, None);
+    }
+
+    #[test]
+    fn finished_states_are_not_queued_or_stopped() {
+        use IndexingState::*;
+
+        for state in [Idle, Partial, Failed, Cancelled] {
+            for next in [Queued, Idle, Partial, Failed, Paused, Cancelled] {
+                assert!(!state.can_transition_to(next), "{state} -> {next}");
+            }
+        }
+    }
+
+    #[test]
+    fn queued_runs_can_be_stopped_or_failed() {
+        use IndexingState::*;
+
+        for next in [Paused, Cancelled, Partial, Failed] {
+            assert!(Queued.can_transition_to(next), "queued -> {next}");
+        }
+        assert!(!Queued.can_transition_to(Idle));
+        assert!(!Queued.can_transition_to(Queued));
+    }
+
+    #[test]
+    fn sources_match_the_transitions() {
+        for next in IndexingState::ALL {
+            for state in IndexingState::ALL {
+                assert_eq!(
+                    IndexingState::sources(next).contains(&state),
+                    state.can_transition_to(next)
+                );
+            }
+        }
+    }
+
+    fn bounds(
+        max_signatures: Option<i64>,
+        since: Option<i64>,
+        min_slot: Option<i64>,
+    ) -> IndexingBounds {
+        IndexingBounds {
+            max_signatures,
+            since,
+            min_slot,
+        }
+    }
+
+    #[test]
+    fn unbounded_history_covers_any_bounds() {
+        let unbounded = IndexingBounds::default();
+
+        assert!(unbounded.covers(&bounds(Some(10), Some(1_000), Some(5))));
+        assert!(unbounded.covers(&unbounded));
+        assert!(!bounds(Some(10), None, None).covers(&unbounded));
+    }
+
+    #[test]
+    fn deeper_bounds_cover_shallower_ones() {
+        assert!(bounds(Some(200), None, None).covers(&bounds(Some(100), None, None)));
+        assert!(!bounds(Some(100), None, None).covers(&bounds(Some(200), None, None)));
+
+        assert!(bounds(None, Some(1_000), None).covers(&bounds(None, Some(2_000), None)));
+        assert!(!bounds(None, Some(2_000), None).covers(&bounds(None, Some(1_000), None)));
+
+        assert!(bounds(None, None, Some(5)).covers(&bounds(None, None, Some(5))));
+        assert!(!bounds(None, None, Some(6)).covers(&bounds(None, None, Some(5))));
+    }
+
+    #[test]
+    fn covers_requires_every_bound() {
+        let stored = bounds(Some(100), Some(2_000), None);
+
+        assert!(bounds(Some(100), Some(1_000), None).covers(&stored));
+        assert!(!bounds(Some(50), Some(1_000), None).covers(&stored));
+        assert!(!bounds(Some(100), Some(1_000), Some(5)).covers(&stored));
+    }
+
+    #[test]
+    fn excludes_signatures_older_than_the_bounds() {
+        let bounds = bounds(None, Some(1_000), Some(50));
+
+        assert!(!bounds.excludes(50, Some(1_000)));
+        assert!(bounds.excludes(49, Some(2_000)));
+        assert!(bounds.excludes(60, Some(999)));
+        // A signature without a block time is only bounded by its slot
+        assert!(!bounds.excludes(60, None));
+    }
+
+    #[test]
+    fn count_bound_excludes_nothing_by_itself() {
+        assert!(!bounds(Some(1), None, None).excludes(0, Some(0)));
+    }
+
+    #[test]
+    fn counts_a_transaction_by_its_link_then_its_body() {
+        use Upserted::*;
+
+        let mut counts = UpsertCounts::default();
+        // New to the address even though another address already stored the body
+        counts.add_transaction(Skipped, Inserted);
+        counts.add_transaction(Inserted, Inserted);
+        counts.add_transaction(Updated, Skipped);
+        counts.add_transaction(Skipped, Skipped);
+        counts.add_transaction(Skipped, Skipped);
+
+        assert_eq!((counts.inserted, counts.updated, counts.skipped), (2, 1, 2));
+    }
 }
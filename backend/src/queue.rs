@@ -0,0 +1,471 @@
+use std::sync::{Arc, atomic::Ordering};
+use std::time::Duration;
+
+use tracing::{error, info, warn};
+
+use crate::{
+    app_state::{AddressSession, AppState, StopRequest},
+    cluster::Cluster,
+    error::AppError,
+    integrity,
+    message::SyncStatus,
+    models::{
+        IndexingBounds, IndexingJob, IndexingState, JobKind, JobPriority, JobStatus, Timestamp,
+        UpdateAddressIndexingState, document_id,
+    },
+    solana,
+};
+
+// How often an idle worker looks for jobs that it wasn't notified about
+// e.g. the jobs enqueued before a restart
+const JOB_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// Sent to the clients of a session while its job waits in the queue
+#[derive(Debug, serde::Serialize)]
+struct QueuePosition {
+    position: usize,
+    pending: usize,
+}
+
+// What an admin action did to the job of an address
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobOutcome {
+    // The job was still pending and is dropped from the queue
+    Dequeued,
+    // The job is running and stops at its next check between RPC calls
+    StopRequested,
+    // The paused job is back in the queue
+    Queued,
+}
+
+// Queue a run of the address unless its session already has one
+// The clients follow the job by subscribing to the returned session
+// An index job without bounds indexes down to the bounds stored for the address
+pub async fn enqueue(
+    state: &AppState,
+    cluster: Cluster,
+    address: String,
+    kind: JobKind,
+    priority: JobPriority,
+    bounds: Option<IndexingBounds>,
+) -> Result<Arc<AddressSession>, AppError> {
+    let session = state.get_or_create_session(cluster, &address);
+    warn!(
+        "started AtomicBool value: {}",
+        session.started.load(Ordering::Relaxed)
+    );
+
+    if !session.started.swap(true, Ordering::AcqRel) {
+        let job = IndexingJob {
+            id: document_id(cluster, &address),
+            cluster,
+            address: address.clone(),
+            kind,
+            priority: priority.rank(),
+            status: JobStatus::Pending,
+            enqueued_at: Timestamp::now(),
+            started_at: None,
+            bounds,
+        };
+
+        if let Err(err) = state.db.enqueue_job(&job).await {
+            state.remove_session(cluster, &address);
+            return Err(err);
+        }
+        info!(%cluster, %address, ?kind, ?priority, "Job enqueued");
+
+        // Wake up an idle worker and tell the clients where the job stands
+        state.job_notify.notify_one();
+        report_queue_positions(state).await;
+    }
+
+    Ok(session)
+}
+
+// Put the jobs that were running when the backend stopped back in the queue
+// and start the fixed-size pool of workers that runs the queued jobs
+pub async fn start_workers(state: &AppState) -> Result<(), AppError> {
+    let reset = state.db.reset_running_jobs().await?;
+    info!(reset, "Running jobs of the last shutdown queued again");
+
+    for worker in 0..state.config.indexing_workers {
+        let state = state.clone();
+        tokio::spawn(async move {
+            info!(worker, "Indexing worker started");
+            loop {
+                match state.db.claim_next_job().await {
+                    Ok(Some(job)) => {
+                        report_queue_positions(&state).await;
+                        run_job(&state, worker, job).await;
+                    }
+                    Ok(None) => {
+                        // Sleep until a job is enqueued or the poll interval is over
+                        let _ =
+                            tokio::time::timeout(JOB_POLL_INTERVAL, state.job_notify.notified())
+                                .await;
+                    }
+                    Err(err) => {
+                        error!(worker, %err, "Error occurred while claiming a job");
+                        tokio::time::sleep(JOB_POLL_INTERVAL).await;
+                    }
+                }
+            }
+        });
+    }
+
+    Ok(())
+}
+
+async fn run_job(state: &AppState, worker: usize, job: IndexingJob) {
+    let IndexingJob {
+        id,
+        cluster,
+        address,
+        kind,
+        bounds,
+        ..
+    } = job;
+    info!(worker, %cluster, %address, ?kind, "Job started");
+
+    // A job enqueued before a restart has no session yet
+    let session = state.get_or_create_session(cluster, &address);
+    session.started.store(true, Ordering::Release);
+
+    let result = match kind {
+        JobKind::Index => {
+            solana::indexer(
+                state.clone(),
+                session.clone(),
+                cluster,
+                address.clone(),
+                bounds,
+            )
+            .await
+        }
+        JobKind::Refresh => {
+            solana::refresher(state.clone(), session.clone(), cluster, address.clone()).await
+        }
+        JobKind::Repair => {
+            integrity::repairer(state.clone(), session.clone(), cluster, address.clone()).await
+        }
+    };
+    match result {
+        Ok(()) => {}
+        Err(AppError::Stopped(request)) => {
+            info!(worker, %cluster, %address, %request, "Job stopped");
+            session.emit_event(stopped_event(request)).await;
+        }
+        Err(e) => {
+            session.emit_event(SyncStatus::Error(e.to_string())).await;
+            error!(
+                "Error occcured while sending event to channel: {}",
+                e.to_string()
+            );
+        }
+    }
+
+    if let Err(err) = state.db.delete_job(&id).await {
+        error!(%err, "Error occurred while deleting the finished job");
+    }
+
+    let removed = state.remove_session(cluster, &address);
+    info!("Session removed: {}", removed);
+}
+
+fn stopped_event(request: StopRequest) -> SyncStatus {
+    match request {
+        StopRequest::Cancel => SyncStatus::Cancelled,
+        StopRequest::Pause => SyncStatus::Paused,
+    }
+}
+
+// Cancel or pause the job of the address
+// A pending job is dropped from the queue right away while a running one
+// is asked to stop and leaves the address paused/cancelled at its checkpoint
+pub async fn stop_job(
+    state: &AppState,
+    cluster: Cluster,
+    address: &str,
+    request: StopRequest,
+) -> Result<JobOutcome, AppError> {
+    let session = state.find_session(cluster, address);
+
+    if state
+        .db
+        .delete_pending_job(&document_id(cluster, address))
+        .await?
+    {
+        // Only an address put in the Queued state by the recovery moves along with its job,
+        // any other address keeps the state of its last run
+        match state.db.get_address_indexing_state(cluster, address).await {
+            Ok(indexing_state) if indexing_state.state == IndexingState::Queued => {
+                solana::mark_stopped(state, cluster, address, request).await?;
+            }
+            Ok(_) | Err(AppError::NotFound(_)) => {}
+            Err(err) => return Err(err),
+        }
+
+        if let Some(session) = session {
+            session.emit_event(stopped_event(request)).await;
+        }
+        state.remove_session(cluster, address);
+        info!(%cluster, %address, %request, "Pending job dropped from the queue");
+        report_queue_positions(state).await;
+
+        return Ok(JobOutcome::Dequeued);
+    }
+
+    match session {
+        Some(session) if session.started.load(Ordering::Acquire) => {
+            session.request_stop(request);
+            info!(%cluster, %address, %request, "Running job asked to stop");
+            Ok(JobOutcome::StopRequested)
+        }
+        _ => Err(AppError::NotFound(
+            "No job found for the address".to_string(),
+        )),
+    }
+}
+
+// Queue the paused run of the address again, it resumes from its checkpoint
+pub async fn resume_job(
+    state: &AppState,
+    cluster: Cluster,
+    address: String,
+) -> Result<JobOutcome, AppError> {
+    let indexing_state = state
+        .db
+        .get_address_indexing_state(cluster, &address)
+        .await?;
+    if indexing_state.state != IndexingState::Paused {
+        return Err(AppError::BadRequest(format!(
+            "Only a paused job can be resumed, the address is {}",
+            indexing_state.state
+        )));
+    }
+    // The run is paused in the DB but its job may still be winding down
+    if state.find_session(cluster, &address).is_some() {
+        return Err(AppError::BadRequest(
+            "The job of the address is still stopping".to_string(),
+        ));
+    }
+
+    // A run with an until signature is a sync of an already indexed address
+    let kind = match &indexing_state.checkpoint {
+        Some(checkpoint) if checkpoint.until_signature.is_some() => JobKind::Refresh,
+        _ => JobKind::Index,
+    };
+
+    state
+        .db
+        .update_address_indexing_state(
+            cluster,
+            &address,
+            UpdateAddressIndexingState {
+                state: IndexingState::Queued,
+                checkpoint: indexing_state.checkpoint,
+                last_error: indexing_state.last_error,
+                updated_at: Timestamp::now(),
+            },
+        )
+        .await?;
+    enqueue(
+        state,
+        cluster,
+        address,
+        kind,
+        JobPriority::Interactive,
+        None,
+    )
+    .await?;
+
+    Ok(JobOutcome::Queued)
+}
+
+// Queue a repair of the stored history of the address
+pub async fn repair_job(
+    state: &AppState,
+    cluster: Cluster,
+    address: String,
+) -> Result<JobOutcome, AppError> {
+    if !state.db.check_account_exists(cluster, &address).await {
+        return Err(AppError::NotFound("Account Not Found".to_string()));
+    }
+    if state.find_session(cluster, &address).is_some() {
+        return Err(AppError::BadRequest(
+            "The address already has a job".to_string(),
+        ));
+    }
+
+    enqueue(
+        state,
+        cluster,
+        address,
+        JobKind::Repair,
+        JobPriority::Interactive,
+        None,
+    )
+    .await?;
+
+    Ok(JobOutcome::Queued)
+}
+
+// Send the current queue position to the clients of every pending job
+async fn report_queue_positions(state: &AppState) {
+    let jobs = match state.db.get_pending_jobs().await {
+        Ok(jobs) => jobs,
+        Err(err) => {
+            error!(%err, "Error occurred while getting the pending jobs");
+            return;
+        }
+    };
+
+    let pending = jobs.len();
+    for (index, job) in jobs.into_iter().enumerate() {
+        // Clone the session out of the map so its shard isn't locked while sending
+        let Some(session) = state.find_session(job.cluster, &job.address) else {
+            continue;
+        };
+
+        let position = QueuePosition {
+            position: index + 1,
+            pending,
+        };
+        match serde_json::to_string(&position) {
+            Ok(data) => session.emit_queue_position(data).await,
+            Err(err) => error!("Error occurred while serializing the queue position: {err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+    use crate::testing::{FakeCluster, Hold, state_with};
+
+    async fn enqueue_index(state: &AppState, address: &str, priority: JobPriority) {
+        enqueue(
+            state,
+            Cluster::Devnet,
+            address.to_string(),
+            JobKind::Index,
+            priority,
+            None,
+        )
+        .await
+        .unwrap();
+        // Jobs of the same priority are told apart by the millisecond they were enqueued at
+        tokio::time::sleep(Duration::from_millis(2)).await;
+    }
+
+    async fn indexing_state(state: &AppState, address: &str) -> IndexingState {
+        state
+            .db
+            .get_address_indexing_state(Cluster::Devnet, address)
+            .await
+            .unwrap()
+            .state
+    }
+
+    #[tokio::test]
+    async fn jobs_are_claimed_by_priority_then_in_order() {
+        let state = state_with(FakeCluster::new(0));
+        enqueue_index(&state, "background", JobPriority::Background).await;
+        enqueue_index(&state, "first", JobPriority::Interactive).await;
+        enqueue_index(&state, "second", JobPriority::Interactive).await;
+        // The address already has a job, so it isn't queued twice
+        enqueue_index(&state, "first", JobPriority::Interactive).await;
+
+        let mut claimed = vec![];
+        while let Some(job) = state.db.claim_next_job().await.unwrap() {
+            claimed.push(job.address);
+        }
+        assert_eq!(claimed, ["first", "second", "background"]);
+    }
+
+    #[tokio::test]
+    async fn pending_jobs_are_dropped_from_the_queue() {
+        let state = state_with(FakeCluster::new(0));
+        enqueue_index(&state, "address", JobPriority::Interactive).await;
+
+        let outcome = stop_job(&state, Cluster::Devnet, "address", StopRequest::Cancel)
+            .await
+            .unwrap();
+
+        assert!(matches!(outcome, JobOutcome::Dequeued));
+        assert!(state.db.get_pending_jobs().await.unwrap().is_empty());
+        assert!(state.find_session(Cluster::Devnet, "address").is_none());
+        // Nothing is left to stop
+        let stopped = stop_job(&state, Cluster::Devnet, "address", StopRequest::Cancel).await;
+        assert!(matches!(stopped, Err(AppError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn running_jobs_pause_at_their_checkpoint_and_resume_from_it() {
+        let mut cluster = FakeCluster::new(45);
+        let hold = Arc::new(Hold {
+            signature: cluster.history[30].signature.clone(),
+            ..Default::default()
+        });
+        cluster.hold = Some(hold.clone());
+        let state = state_with(cluster);
+        let address = Pubkey::new_unique().to_string();
+        enqueue_index(&state, &address, JobPriority::Interactive).await;
+
+        // Pause the job while it fetches the transactions past its first batch
+        let job = state.db.claim_next_job().await.unwrap().unwrap();
+        let running = tokio::spawn({
+            let state = state.clone();
+            async move { run_job(&state, 0, job).await }
+        });
+        hold.reached.notified().await;
+        let outcome = stop_job(&state, Cluster::Devnet, &address, StopRequest::Pause)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, JobOutcome::StopRequested));
+        hold.release.notify_one();
+        running.await.unwrap();
+
+        let record = state
+            .db
+            .get_address_indexing_state(Cluster::Devnet, &address)
+            .await
+            .unwrap();
+        assert_eq!(record.state, IndexingState::Paused);
+        assert!(
+            record
+                .checkpoint
+                .is_some_and(|checkpoint| checkpoint.batch >= 1)
+        );
+        assert!(state.find_session(Cluster::Devnet, &address).is_none());
+
+        // The resumed job picks the run up from its checkpoint
+        let outcome = resume_job(&state, Cluster::Devnet, address.clone())
+            .await
+            .unwrap();
+        assert!(matches!(outcome, JobOutcome::Queued));
+        assert_eq!(
+            indexing_state(&state, &address).await,
+            IndexingState::Queued
+        );
+        let job = state.db.claim_next_job().await.unwrap().unwrap();
+        assert_eq!(job.kind, JobKind::Index);
+
+        hold.release.notify_one();
+        run_job(&state, 0, job).await;
+        assert_eq!(indexing_state(&state, &address).await, IndexingState::Idle);
+        let stored = state
+            .db
+            .get_transactions_count(Cluster::Devnet, &address)
+            .await
+            .unwrap();
+        assert_eq!(stored, 45);
+
+        // Only a paused run is resumed
+        let resumed = resume_job(&state, Cluster::Devnet, address).await;
+        assert!(matches!(resumed, Err(AppError::BadRequest(_))));
+    }
+}
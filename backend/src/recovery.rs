@@ -0,0 +1,266 @@
+use tracing::{error, info, warn};
+
+use crate::{
+    app_state::AppState,
+    config::RecoveryPolicy,
+    error::AppError,
+    models::{
+        AddressIndexingState, IndexingState, JobKind, JobPriority, Timestamp,
+        UpdateAddressIndexingState,
+    },
+    queue, solana,
+};
+
+// Sessions only live in memory, so after a crash or redeploy the addresses that are still
+// queued, indexing or syncing in the DB have no run behind them anymore.
+// On startup every such address is either queued again or marked as failed,
+// depending on the recovery policy.
+// This assumes a single backend per DB, another running backend would lose its live runs.
+pub async fn recover_interrupted_runs(state: &AppState) -> Result<(), AppError> {
+    let policy = state.config.recovery_policy;
+    let interrupted = state.db.get_unfinished_address_states().await?;
+
+    let (mut requeued, mut failed, mut skipped, mut errors) = (0, 0, 0, 0);
+    for indexing_state in interrupted {
+        let cluster = indexing_state.cluster;
+        let address = indexing_state.address.clone();
+
+        // Leave the addresses of the clusters this deployment doesn't index alone
+        if state.rpc_for(cluster).is_err() {
+            warn!(%cluster, %address, "Skipped the interrupted run of a disabled cluster");
+            skipped += 1;
+            continue;
+        }
+
+        let result = match policy {
+            RecoveryPolicy::Requeue => requeue(state, indexing_state).await,
+            RecoveryPolicy::Fail => {
+                let err = AppError::Internal("The run was interrupted by a restart".to_string());
+                solana::mark_failed(state, cluster, &address, &err).await
+            }
+        };
+
+        match result {
+            Ok(()) if policy == RecoveryPolicy::Requeue => requeued += 1,
+            Ok(()) => failed += 1,
+            Err(err) => {
+                error!(%cluster, %address, %err, "Error occurred while recovering the interrupted run");
+                errors += 1;
+            }
+        }
+    }
+
+    info!(
+        ?policy,
+        requeued, failed, skipped, errors, "Recovery of the interrupted runs completed"
+    );
+
+    Ok(())
+}
+
+// Queue the run of the address again as a background job
+async fn requeue(state: &AppState, indexing_state: AddressIndexingState) -> Result<(), AppError> {
+    let AddressIndexingState {
+        cluster,
+        address,
+        state: previous_state,
+        checkpoint,
+        last_error,
+        ..
+    } = indexing_state;
+
+    // An interrupted sync is picked up by the refresher, everything else by the indexer
+    let is_sync = previous_state == IndexingState::Syncing
+        || checkpoint
+            .as_ref()
+            .is_some_and(|checkpoint| checkpoint.until_signature.is_some());
+    let kind = if is_sync {
+        JobKind::Refresh
+    } else {
+        JobKind::Index
+    };
+
+    if previous_state != IndexingState::Queued {
+        state
+            .db
+            .update_address_indexing_state(
+                cluster,
+                &address,
+                UpdateAddressIndexingState {
+                    state: IndexingState::Queued,
+                    checkpoint,
+                    last_error,
+                    updated_at: Timestamp::now(),
+                },
+            )
+            .await?;
+    }
+
+    info!(%cluster, %address, ?kind, "Requeued the interrupted run");
+    queue::enqueue(state, cluster, address, kind, JobPriority::Background, None).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        cluster::Cluster,
+        config::Config,
+        models::{IndexingBounds, IndexingCheckpoint, document_id},
+        testing::{FakeCluster, state_with},
+    };
+
+    fn state_with_policy(recovery_policy: RecoveryPolicy) -> AppState {
+        let mut state = state_with(FakeCluster::new(0));
+        state.config = Arc::new(Config {
+            recovery_policy,
+            ..Config::from_env()
+        });
+        state
+    }
+
+    async fn interrupt(
+        state: &AppState,
+        cluster: Cluster,
+        address: &str,
+        indexing_state: IndexingState,
+        checkpoint: Option<IndexingCheckpoint>,
+    ) {
+        state
+            .db
+            .insert_address_indexing_state(AddressIndexingState {
+                id: document_id(cluster, address),
+                cluster,
+                address: address.to_string(),
+                state: indexing_state,
+                checkpoint,
+                bounds: IndexingBounds::default(),
+                last_error: None,
+                started_at: Some(Timestamp::now()),
+                finished_at: None,
+                created_at: Timestamp::now(),
+                updated_at: Timestamp::now(),
+            })
+            .await
+            .unwrap();
+    }
+
+    fn checkpoint(until_signature: Option<&str>) -> Option<IndexingCheckpoint> {
+        Some(IndexingCheckpoint {
+            before_signature: Some("before".to_string()),
+            until_signature: until_signature.map(str::to_string),
+            batch: 2,
+            ..Default::default()
+        })
+    }
+
+    async fn record(state: &AppState, cluster: Cluster, address: &str) -> AddressIndexingState {
+        state
+            .db
+            .get_address_indexing_state(cluster, address)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn requeued_runs_resume_with_the_job_that_was_running() {
+        let state = state_with_policy(RecoveryPolicy::Requeue);
+        let devnet = Cluster::Devnet;
+        interrupt(
+            &state,
+            devnet,
+            "indexing",
+            IndexingState::Indexing,
+            checkpoint(None),
+        )
+        .await;
+        interrupt(&state, devnet, "syncing", IndexingState::Syncing, None).await;
+        let extending = checkpoint(Some("until"));
+        interrupt(
+            &state,
+            devnet,
+            "extending",
+            IndexingState::Indexing,
+            extending,
+        )
+        .await;
+        interrupt(&state, devnet, "queued", IndexingState::Queued, None).await;
+        // This deployment doesn't index mainnet
+        interrupt(
+            &state,
+            Cluster::Mainnet,
+            "mainnet",
+            IndexingState::Indexing,
+            None,
+        )
+        .await;
+
+        recover_interrupted_runs(&state).await.unwrap();
+
+        let mut jobs: Vec<_> = state
+            .db
+            .get_pending_jobs()
+            .await
+            .unwrap()
+            .into_iter()
+            .inspect(|job| assert_eq!(job.priority, JobPriority::Background.rank()))
+            .map(|job| (job.address, job.kind))
+            .collect();
+        jobs.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            jobs,
+            [
+                ("extending".to_string(), JobKind::Refresh),
+                ("indexing".to_string(), JobKind::Index),
+                ("queued".to_string(), JobKind::Index),
+                ("syncing".to_string(), JobKind::Refresh),
+            ]
+        );
+        for address in ["indexing", "syncing", "extending", "queued"] {
+            assert_eq!(
+                record(&state, devnet, address).await.state,
+                IndexingState::Queued
+            );
+        }
+        // The checkpoint is kept for the resumed run
+        let indexing = record(&state, devnet, "indexing").await;
+        assert!(
+            indexing
+                .checkpoint
+                .is_some_and(|checkpoint| checkpoint.batch == 2)
+        );
+        let mainnet = record(&state, Cluster::Mainnet, "mainnet").await;
+        assert_eq!(mainnet.state, IndexingState::Indexing);
+    }
+
+    #[tokio::test]
+    async fn failed_runs_keep_what_they_indexed_as_partial() {
+        let state = state_with_policy(RecoveryPolicy::Fail);
+        let devnet = Cluster::Devnet;
+        interrupt(
+            &state,
+            devnet,
+            "indexing",
+            IndexingState::Indexing,
+            checkpoint(None),
+        )
+        .await;
+        interrupt(&state, devnet, "queued", IndexingState::Queued, None).await;
+
+        recover_interrupted_runs(&state).await.unwrap();
+
+        assert!(state.db.get_pending_jobs().await.unwrap().is_empty());
+        let indexing = record(&state, devnet, "indexing").await;
+        assert_eq!(indexing.state, IndexingState::Partial);
+        let queued = record(&state, devnet, "queued").await;
+        assert_eq!(queued.state, IndexingState::Failed);
+        for record in [indexing, queued] {
+            let kind = record.last_error.map(|err| err.kind);
+            assert_eq!(kind.as_deref(), Some("internal"));
+        }
+    }
+}
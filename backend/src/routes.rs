@@ -1,4 +1,7 @@
-use axum::{Router, routing::get};
+use axum::{
+    Router, middleware,
+    routing::{get, post},
+};
 use tower::ServiceBuilder;
 use tower_http::{
     request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
@@ -6,7 +9,7 @@ use tower_http::{
 };
 use tracing::Level;
 
-use crate::{app_state::AppState, cors::setup_cors_layer, handlers::*};
+use crate::{app_state::AppState, auth::require_admin_token, cors::setup_cors_layer, handlers::*};
 
 pub fn create_router(state: AppState) -> Router {
     // Setup the cors layer and add it to the router
@@ -30,26 +33,62 @@ pub fn create_router(state: AppState) -> Router {
         // Layer that handles the CORS
         .layer(cors_layer);
 
+    // The admin routes require the admin token
+    let admin_routes = Router::new()
+        .nest("/api/admin/jobs", job_routes())
+        .nest("/api/admin/{cluster}/jobs", job_routes())
+        .nest("/api/admin/watches", watch_routes())
+        .nest("/api/admin/{cluster}/watches", watch_routes())
+        .route_layer(middleware::from_fn_with_state(
+            state.config.clone(),
+            require_admin_token,
+        ));
+
     // Setup a router consisting of the routes with the Connection pool as State accessible to all the handlers
+    // The account routes are served on /api/{cluster}/accounts/... for a specific cluster
+    // and on /api/accounts/... for the default cluster
+    Router::new()
+        .nest("/api/accounts", account_routes())
+        .nest("/api/{cluster}/accounts", account_routes())
+        .merge(admin_routes)
+        // Application state
+        .with_state(state)
+        // Add the layer / middleware at the end
+        .layer(combined_layer)
+}
+
+fn account_routes() -> Router<AppState> {
     Router::new()
         // Remaining API routes
-        .route("/api/accounts/{address}/status", get(account_status))
+        .route("/{address}/status", get(account_status))
         // SSE (Server Sent Event) route for indexing
-        .route("/api/accounts/{address}/index/sse", get(indexer_sse))
-        .route("/api/accounts/{address}/indexer/stats", get(indexer_stats))
-        .route("/api/accounts/{address}", get(account_data))
-        .route(
-            "/api/accounts/{address}/signatures",
-            get(transaction_signatures),
-        )
-        .route("/api/accounts/{address}/transactions", get(transactions))
+        .route("/{address}/index/sse", get(indexer_sse))
+        .route("/{address}/indexer/stats", get(indexer_stats))
+        .route("/{address}", get(account_data))
+        .route("/{address}/signatures", get(transaction_signatures))
+        .route("/{address}/transactions", get(transactions))
         .route(
-            "/api/accounts/{address}/transactions/{signature}",
+            "/{address}/transactions/{signature}",
             get(transaction_from_signature),
         )
-        .route("/api/accounts/{address}/refresh/sse", get(refresh_sse))
-        // Application state
-        .with_state(state)
-        // Add the layer / middleware at the end
-        .layer(combined_layer)
+        .route("/{address}/refresh/sse", get(refresh_sse))
+        // SSE route following the live ingestion of a watched address
+        .route("/{address}/live/sse", get(live_sse))
+}
+
+// Admin routes controlling the indexing job of an address
+// The integrity report scans the whole stored history, so it is kept to the admins
+// along with the repair job that fixes what it finds
+fn job_routes() -> Router<AppState> {
+    Router::new()
+        .route("/{address}/cancel", post(cancel_job))
+        .route("/{address}/pause", post(pause_job))
+        .route("/{address}/resume", post(resume_job))
+        .route("/{address}/integrity", get(integrity_report))
+        .route("/{address}/repair", post(repair_job))
+}
+
+// Admin routes controlling the live ingestion of an address
+fn watch_routes() -> Router<AppState> {
+    Router::new().route("/{address}", post(watch_address).delete(unwatch_address))
 }
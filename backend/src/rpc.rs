@@ -0,0 +1,445 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, reqwest::StatusCode},
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_config::RpcTransactionConfig,
+    rpc_custom_error::{
+        JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE,
+        JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET,
+        JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED, JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+    },
+    rpc_request::RpcError,
+    rpc_response::RpcConfirmedTransactionStatusWithSignature,
+};
+use solana_commitment_config::CommitmentConfig;
+use solana_sdk::{account::Account, pubkey::Pubkey, signature::Signature};
+use solana_transaction_status::{EncodedConfirmedTransactionWithStatusMeta, TransactionStatus};
+use tracing::{error, info, warn};
+
+use crate::{
+    app_state::AddressSession,
+    config::{Config, RpcEndpointConfig},
+    error::AppError,
+    message::SyncStatus,
+    rpc::pool::RpcPool,
+};
+
+pub mod limiter;
+pub mod pool;
+pub mod sender;
+
+// JSON-RPC internal error code that some providers return for overloaded nodes
+const JSON_RPC_INTERNAL_ERROR: i64 = -32603;
+// Waits for the rate limiter shorter than this are not worth reporting to the clients
+const NOTIFY_WAIT_THRESHOLD: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ErrorClass {
+    // The endpoint is throttling us (HTTP 429)
+    RateLimited,
+    // Timeouts, connection and server errors that are worth retrying
+    Transient,
+    // Errors that will fail the same way however often they are retried
+    Permanent,
+}
+
+// Tell the transient RPC errors apart from the permanent ones
+fn classify(err: &ClientError) -> ErrorClass {
+    match err.kind() {
+        ClientErrorKind::Reqwest(err) => match err.status() {
+            Some(StatusCode::TOO_MANY_REQUESTS) => ErrorClass::RateLimited,
+            Some(status) if status.is_server_error() => ErrorClass::Transient,
+            Some(_) => ErrorClass::Permanent,
+            // No status means the request timed out or never reached the endpoint
+            None => ErrorClass::Transient,
+        },
+        ClientErrorKind::Io(_) => ErrorClass::Transient,
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => match *code {
+            429 => ErrorClass::RateLimited,
+            JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+            | JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE
+            | JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET
+            | JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED
+            | JSON_RPC_INTERNAL_ERROR => ErrorClass::Transient,
+            _ => ErrorClass::Permanent,
+        },
+        _ => ErrorClass::Permanent,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    // Exponential backoff with jitter
+    // The delay doubles for every attempt (capped at max_delay) and a random
+    // point in its upper half is picked so concurrent retries don't line up
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        let millis = exponential.as_millis() as u64;
+
+        Duration::from_millis(rand::random_range(millis / 2..=millis))
+    }
+}
+
+// Sent to the clients of the session whenever an RPC call is held back
+#[derive(Debug, serde::Serialize)]
+struct RpcWait {
+    reason: &'static str,
+    wait_ms: u64,
+    attempt: u32,
+}
+
+async fn notify_wait(
+    session: Option<&AddressSession>,
+    reason: &'static str,
+    wait: Duration,
+    attempt: u32,
+) {
+    let Some(session) = session else {
+        return;
+    };
+
+    let wait = RpcWait {
+        reason,
+        wait_ms: wait.as_millis() as u64,
+        attempt,
+    };
+    match serde_json::to_string(&wait) {
+        Ok(data) => session.emit_event(SyncStatus::RateLimited(data)).await,
+        Err(err) => error!("Error occurred while serializing the RPC wait: {err}"),
+    }
+}
+
+// Sleep for the wait unless the job of the session is asked to stop in the meantime
+async fn wait_or_stop(session: Option<&AddressSession>, wait: Duration) -> Result<(), AppError> {
+    let Some(session) = session else {
+        tokio::time::sleep(wait).await;
+        return Ok(());
+    };
+
+    tokio::select! {
+        _ = tokio::time::sleep(wait) => Ok(()),
+        _ = session.cancel.cancelled() => session.check_stop(),
+    }
+}
+
+// The shared rate-limiting layer around a pool of Solana RPC endpoints.
+// Every RPC call is routed to a healthy endpoint and waits for a token of its token bucket.
+// Failed calls fail over to another endpoint or are retried with exponential backoff
+// when the error is transient.
+pub struct SolanaRpc {
+    pool: RpcPool,
+    retry: RetryPolicy,
+}
+
+impl SolanaRpc {
+    pub fn new(endpoints: &[RpcEndpointConfig], config: &Config) -> Self {
+        SolanaRpc {
+            pool: RpcPool::new(endpoints, config),
+            retry: RetryPolicy {
+                max_retries: config.rpc_max_retries,
+                base_delay: config.rpc_retry_base_delay,
+                max_delay: config.rpc_retry_max_delay,
+            },
+        }
+    }
+
+    // Run an RPC request through the rate limiter and retry it on transient errors
+    // The waits are reported to the clients of the session (if any) as rate-limited events
+    // and a job of the session that is asked to stop gives up before its next attempt
+    pub async fn call<T, F, Fut>(
+        &self,
+        session: Option<&AddressSession>,
+        request: F,
+    ) -> Result<T, AppError>
+    where
+        F: Fn(Arc<RpcClient>) -> Fut,
+        Fut: Future<Output = Result<T, ClientError>>,
+    {
+        let mut attempt = 0;
+        // Endpoints this request already failed on
+        let mut tried: Vec<usize> = vec![];
+
+        loop {
+            if let Some(session) = session {
+                session.check_stop()?;
+            }
+
+            let index = self.pool.select(&tried);
+            let endpoint = self.pool.endpoint(index);
+
+            // Wait for the turn of this request in the token bucket of the endpoint
+            let wait = endpoint.limiter.reserve();
+            if !wait.is_zero() {
+                if wait >= NOTIFY_WAIT_THRESHOLD {
+                    notify_wait(session, "rpc rate limit", wait, attempt).await;
+                }
+                wait_or_stop(session, wait).await?;
+            }
+
+            let err = match request(endpoint.client.clone()).await {
+                Ok(response) => {
+                    self.pool.record_success(index);
+                    return Ok(response);
+                }
+                Err(err) => err,
+            };
+
+            let class = classify(&err);
+            if class == ErrorClass::Transient {
+                self.pool.record_failure(index);
+            }
+            if class == ErrorClass::Permanent {
+                return Err(err.into());
+            }
+            // The retries ran out on an error that a later attempt may well get past
+            if attempt >= self.retry.max_retries {
+                return Err(AppError::Unavailable(err.to_string()));
+            }
+            attempt += 1;
+            if !tried.contains(&index) {
+                tried.push(index);
+            }
+
+            // A rate limited endpoint is held back for every caller, either for
+            // the Retry-After it asked for or for the backoff of this attempt
+            if class == ErrorClass::RateLimited && endpoint.limiter.blocked_for().is_none() {
+                endpoint.limiter.block_for(self.retry.backoff(attempt));
+            }
+
+            // Fail over right away when another healthy endpoint is left to try
+            if self.pool.has_untried(&tried) {
+                warn!(url = %endpoint.url, %err, ?class, attempt, "Failing over the RPC request");
+                continue;
+            }
+
+            let (reason, delay) = match class {
+                ErrorClass::RateLimited => (
+                    "rpc endpoint throttled the requests",
+                    endpoint
+                        .limiter
+                        .blocked_for()
+                        .unwrap_or_else(|| self.retry.backoff(attempt)),
+                ),
+                _ => ("rpc request failed", self.retry.backoff(attempt)),
+            };
+
+            warn!(url = %endpoint.url, %err, ?class, attempt, ?delay, "Retrying the RPC request");
+            notify_wait(session, reason, delay, attempt).await;
+            wait_or_stop(session, delay).await?;
+        }
+    }
+
+    // Periodically check the slots of the endpoints so the failing or lagging ones are ejected
+    // A single endpoint has nothing to fail over to, so it is not checked
+    pub fn spawn_health_checks(self: &Arc<Self>, interval: Duration) {
+        if self.pool.len() < 2 {
+            return;
+        }
+
+        let rpc = self.clone();
+        tokio::spawn(async move {
+            info!("RPC endpoint health checks started");
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                rpc.pool.check_health().await;
+            }
+        });
+    }
+}
+
+// The RPC calls the indexer makes to a cluster
+// Kept behind a trait so the indexing flows can run against a fake cluster in the tests
+#[async_trait]
+pub trait SolanaApi: Send + Sync {
+    // The account at the address or None when there is no account,
+    // i.e. the address was closed or never funded
+    async fn get_account(
+        &self,
+        session: Option<&AddressSession>,
+        pubkey: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> Result<Option<Account>, AppError>;
+
+    async fn get_signatures_for_address_with_config(
+        &self,
+        session: Option<&AddressSession>,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, AppError>;
+
+    async fn get_transaction_with_config(
+        &self,
+        session: Option<&AddressSession>,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> Result<EncodedConfirmedTransactionWithStatusMeta, AppError>;
+
+    // Statuses of up to 256 signatures, searched in the whole transaction history
+    // A signature unknown to the cluster has no status
+    async fn get_signature_statuses(
+        &self,
+        session: Option<&AddressSession>,
+        signatures: &[Signature],
+    ) -> Result<Vec<Option<TransactionStatus>>, AppError>;
+}
+
+#[async_trait]
+impl SolanaApi for SolanaRpc {
+    async fn get_account(
+        &self,
+        session: Option<&AddressSession>,
+        pubkey: &Pubkey,
+        commitment: CommitmentConfig,
+    ) -> Result<Option<Account>, AppError> {
+        self.call(session, |rpc| async move {
+            rpc.get_account_with_commitment(pubkey, commitment)
+                .await
+                .map(|response| response.value)
+        })
+        .await
+    }
+
+    async fn get_signatures_for_address_with_config(
+        &self,
+        session: Option<&AddressSession>,
+        address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, AppError> {
+        // The config is not Clone so rebuild it for every attempt
+        let GetConfirmedSignaturesForAddress2Config {
+            before,
+            until,
+            limit,
+            commitment,
+        } = config;
+
+        self.call(session, |rpc| async move {
+            rpc.get_signatures_for_address_with_config(
+                address,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until,
+                    limit,
+                    commitment,
+                },
+            )
+            .await
+        })
+        .await
+    }
+
+    async fn get_transaction_with_config(
+        &self,
+        session: Option<&AddressSession>,
+        signature: &Signature,
+        config: RpcTransactionConfig,
+    ) -> Result<EncodedConfirmedTransactionWithStatusMeta, AppError> {
+        self.call(session, |rpc| async move {
+            rpc.get_transaction_with_config(signature, config).await
+        })
+        .await
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        session: Option<&AddressSession>,
+        signatures: &[Signature],
+    ) -> Result<Vec<Option<TransactionStatus>>, AppError> {
+        self.call(session, |rpc| async move {
+            rpc.get_signature_statuses_with_history(signatures)
+                .await
+                .map(|response| response.value)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_client::rpc_request::RpcResponseErrorData;
+
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+        }
+    }
+
+    fn rpc_error(code: i64) -> ClientError {
+        RpcError::RpcResponseError {
+            code,
+            message: String::new(),
+            data: RpcResponseErrorData::Empty,
+        }
+        .into()
+    }
+
+    #[test]
+    fn backoff_doubles_per_attempt() {
+        let policy = policy();
+
+        for (attempt, millis) in [(1, 100), (2, 200), (3, 400), (4, 800)] {
+            let delay = policy.backoff(attempt);
+            assert!(delay >= Duration::from_millis(millis / 2));
+            assert!(delay <= Duration::from_millis(millis));
+        }
+    }
+
+    #[test]
+    fn backoff_is_capped_at_max_delay() {
+        let policy = policy();
+
+        for attempt in [5, 10, 40] {
+            let delay = policy.backoff(attempt);
+            assert!(delay >= Duration::from_millis(500));
+            assert!(delay <= Duration::from_millis(1000));
+        }
+    }
+
+    #[test]
+    fn classify_rate_limits() {
+        assert_eq!(classify(&rpc_error(429)), ErrorClass::RateLimited);
+    }
+
+    #[test]
+    fn classify_transient_errors() {
+        let io = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+        assert_eq!(classify(&io.into()), ErrorClass::Transient);
+
+        for code in [
+            JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+            JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE,
+            JSON_RPC_INTERNAL_ERROR,
+        ] {
+            assert_eq!(classify(&rpc_error(code)), ErrorClass::Transient);
+        }
+    }
+
+    #[test]
+    fn classify_permanent_errors() {
+        // Invalid params
+        assert_eq!(classify(&rpc_error(-32602)), ErrorClass::Permanent);
+        assert_eq!(
+            classify(&RpcError::ParseError("signature".to_string()).into()),
+            ErrorClass::Permanent
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+// A token bucket that limits the requests sent to a single RPC endpoint.
+// Tokens are refilled continuously at the configured rate up to the burst size.
+// Every request reserves a token up front, so once the bucket runs dry the tokens go
+// negative and each caller is told how long it has to wait for its turn.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+    // Set when the endpoint asks us to back off, e.g. the Retry-After of a 429 response
+    blocked_until: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        RateLimiter {
+            rate: requests_per_second,
+            burst: burst as f64,
+            bucket: Mutex::new(Bucket {
+                tokens: burst as f64,
+                refilled_at: Instant::now(),
+                blocked_until: None,
+            }),
+        }
+    }
+
+    // Reserve a token and return how long the caller has to wait before sending the request
+    pub fn reserve(&self) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+
+        // Refill the tokens for the time passed since the last reservation
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.refilled_at = now;
+
+        bucket.tokens -= 1.0;
+        let mut wait = if bucket.tokens < 0.0 {
+            Duration::from_secs_f64(-bucket.tokens / self.rate)
+        } else {
+            Duration::ZERO
+        };
+
+        if let Some(blocked_until) = bucket.blocked_until {
+            wait = wait.max(blocked_until.saturating_duration_since(now));
+        }
+
+        wait
+    }
+
+    // Hold back every request to the endpoint for the given duration
+    pub fn block_for(&self, duration: Duration) {
+        let until = Instant::now() + duration;
+        let mut bucket = self.bucket.lock().unwrap_or_else(PoisonError::into_inner);
+        bucket.blocked_until = Some(bucket.blocked_until.map_or(until, |b| b.max(until)));
+    }
+
+    // The time left until the endpoint accepts requests again, if it is blocked
+    pub fn blocked_for(&self) -> Option<Duration> {
+        let bucket = self.bucket.lock().unwrap_or_else(PoisonError::into_inner);
+        bucket
+            .blocked_until
+            .map(|until| until.saturating_duration_since(Instant::now()))
+            .filter(|remaining| !remaining.is_zero())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserve_is_free_within_the_burst() {
+        let limiter = RateLimiter::new(10.0, 3);
+
+        for _ in 0..3 {
+            assert_eq!(limiter.reserve(), Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn reserve_queues_the_callers_past_the_burst() {
+        let limiter = RateLimiter::new(10.0, 1);
+        assert_eq!(limiter.reserve(), Duration::ZERO);
+
+        // Every token past the burst is 100ms further out at 10 requests per second
+        let second = limiter.reserve();
+        let third = limiter.reserve();
+        assert!(second > Duration::from_millis(90) && second <= Duration::from_millis(100));
+        assert!(third > Duration::from_millis(190) && third <= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn reserve_waits_out_a_block() {
+        let limiter = RateLimiter::new(10.0, 5);
+        limiter.block_for(Duration::from_secs(2));
+
+        let wait = limiter.reserve();
+        assert!(wait > Duration::from_millis(1900) && wait <= Duration::from_secs(2));
+        assert!(limiter.blocked_for().is_some());
+    }
+
+    #[test]
+    fn block_for_keeps_the_longest_block() {
+        let limiter = RateLimiter::new(10.0, 5);
+        limiter.block_for(Duration::from_secs(2));
+        limiter.block_for(Duration::from_millis(100));
+
+        assert!(limiter.blocked_for().unwrap() > Duration::from_millis(1900));
+    }
+}
@@ -0,0 +1,312 @@
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use futures::future::join_all;
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_client::RpcClientConfig};
+use tracing::{info, warn};
+
+use crate::{
+    config::{Config, RpcEndpointConfig},
+    rpc::{limiter::RateLimiter, sender::RateLimitedSender},
+};
+
+#[derive(Debug, Default)]
+struct EndpointHealth {
+    consecutive_failures: u32,
+    // The endpoint gets no requests until this instant unless every endpoint is ejected
+    ejected_until: Option<Instant>,
+}
+
+// A single RPC endpoint of the pool with its own client, rate limiter and health
+pub struct RpcEndpoint {
+    pub url: String,
+    pub weight: u32,
+    pub client: Arc<RpcClient>,
+    pub limiter: Arc<RateLimiter>,
+    health: Mutex<EndpointHealth>,
+}
+
+impl RpcEndpoint {
+    fn new(endpoint: &RpcEndpointConfig, config: &Config) -> Self {
+        let limiter = Arc::new(RateLimiter::new(
+            endpoint
+                .requests_per_second
+                .unwrap_or(config.rpc_requests_per_second),
+            config.rpc_burst,
+        ));
+        let sender = RateLimitedSender::new(
+            endpoint.url.clone(),
+            endpoint.auth_header.as_ref(),
+            limiter.clone(),
+        );
+
+        RpcEndpoint {
+            url: endpoint.url.clone(),
+            weight: endpoint.weight.max(1),
+            client: Arc::new(RpcClient::new_sender(sender, RpcClientConfig::default())),
+            limiter,
+            health: Mutex::new(EndpointHealth::default()),
+        }
+    }
+
+    fn is_healthy(&self, now: Instant) -> bool {
+        let health = self.health.lock().unwrap_or_else(PoisonError::into_inner);
+        health.ejected_until.is_none_or(|until| until <= now)
+    }
+
+    fn eject(&self, duration: Duration, reason: &str) {
+        let mut health = self.health.lock().unwrap_or_else(PoisonError::into_inner);
+        health.ejected_until = Some(Instant::now() + duration);
+        health.consecutive_failures = 0;
+        warn!(url = %self.url, ?duration, reason, "RPC endpoint ejected from the pool");
+    }
+
+    fn reinstate(&self) {
+        let mut health = self.health.lock().unwrap_or_else(PoisonError::into_inner);
+        if health.ejected_until.take().is_some() {
+            info!(url = %self.url, "RPC endpoint is healthy again");
+        }
+        health.consecutive_failures = 0;
+    }
+}
+
+// A weighted pool of RPC endpoints
+// Requests are spread over the healthy endpoints by their weight and an endpoint
+// is ejected for a while after repeated failures or when it lags behind in slots
+pub struct RpcPool {
+    endpoints: Vec<RpcEndpoint>,
+    eject_after_failures: u32,
+    eject_duration: Duration,
+    max_slot_lag: u64,
+}
+
+impl RpcPool {
+    pub fn new(endpoints: &[RpcEndpointConfig], config: &Config) -> Self {
+        assert!(
+            !endpoints.is_empty(),
+            "At least one RPC endpoint is required"
+        );
+
+        RpcPool {
+            endpoints: endpoints
+                .iter()
+                .map(|endpoint| RpcEndpoint::new(endpoint, config))
+                .collect(),
+            eject_after_failures: config.rpc_eject_after_failures.max(1),
+            eject_duration: config.rpc_eject_duration,
+            max_slot_lag: config.rpc_max_slot_lag,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.endpoints.is_empty()
+    }
+
+    pub fn endpoint(&self, index: usize) -> &RpcEndpoint {
+        &self.endpoints[index]
+    }
+
+    // Pick an endpoint by weight among the healthy ones not tried yet by the request.
+    // Falls back to the tried healthy endpoints and then to every endpoint,
+    // so a request is still sent when the whole pool is ejected.
+    pub fn select(&self, tried: &[usize]) -> usize {
+        let now = Instant::now();
+        let healthy: Vec<usize> = (0..self.endpoints.len())
+            .filter(|&index| self.endpoints[index].is_healthy(now))
+            .collect();
+
+        let untried: Vec<usize> = healthy
+            .iter()
+            .copied()
+            .filter(|index| !tried.contains(index))
+            .collect();
+
+        let candidates = if !untried.is_empty() {
+            untried
+        } else if !healthy.is_empty() {
+            healthy
+        } else {
+            (0..self.endpoints.len()).collect()
+        };
+
+        let total_weight: u32 = candidates
+            .iter()
+            .map(|&index| self.endpoints[index].weight)
+            .sum();
+        let mut pick = rand::random_range(0..total_weight);
+
+        for &index in &candidates {
+            let weight = self.endpoints[index].weight;
+            if pick < weight {
+                return index;
+            }
+            pick -= weight;
+        }
+
+        candidates[candidates.len() - 1]
+    }
+
+    // Whether the request can fail over to a healthy endpoint it has not tried yet
+    pub fn has_untried(&self, tried: &[usize]) -> bool {
+        let now = Instant::now();
+        (0..self.endpoints.len())
+            .any(|index| !tried.contains(&index) && self.endpoints[index].is_healthy(now))
+    }
+
+    pub fn record_success(&self, index: usize) {
+        let endpoint = &self.endpoints[index];
+        let mut health = endpoint
+            .health
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        health.consecutive_failures = 0;
+    }
+
+    pub fn record_failure(&self, index: usize) {
+        let endpoint = &self.endpoints[index];
+        let failures = {
+            let mut health = endpoint
+                .health
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            health.consecutive_failures += 1;
+            health.consecutive_failures
+        };
+
+        if failures >= self.eject_after_failures {
+            endpoint.eject(
+                self.eject_duration,
+                &format!("{failures} consecutive failures"),
+            );
+        }
+    }
+
+    // Ask every endpoint for its slot and eject the ones that fail
+    // or lag too far behind the highest slot seen in the pool
+    pub async fn check_health(&self) {
+        let slots = join_all(self.endpoints.iter().map(|endpoint| async move {
+            tokio::time::sleep(endpoint.limiter.reserve()).await;
+            endpoint.client.get_slot().await
+        }))
+        .await;
+
+        let highest_slot = slots.iter().filter_map(|slot| slot.as_ref().ok()).max();
+
+        for (index, slot) in slots.iter().enumerate() {
+            let endpoint = &self.endpoints[index];
+            match slot {
+                Ok(slot) => {
+                    let lag = highest_slot.map_or(0, |highest| highest.saturating_sub(*slot));
+                    if lag > self.max_slot_lag {
+                        endpoint.eject(self.eject_duration, &format!("{lag} slots behind"));
+                    } else {
+                        endpoint.reinstate();
+                    }
+                }
+                Err(err) => {
+                    warn!(url = %endpoint.url, %err, "RPC endpoint health check failed");
+                    self.record_failure(index);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(weights: &[u32]) -> RpcPool {
+        let endpoints: Vec<RpcEndpointConfig> = weights
+            .iter()
+            .enumerate()
+            .map(|(i, &weight)| RpcEndpointConfig {
+                weight,
+                ..RpcEndpointConfig::from_url(&format!("http://rpc-{i}.test"))
+            })
+            .collect();
+
+        RpcPool::new(&endpoints, &Config::from_env())
+    }
+
+    fn eject(pool: &RpcPool, index: usize) {
+        for _ in 0..pool.eject_after_failures {
+            pool.record_failure(index);
+        }
+    }
+
+    #[test]
+    fn select_spreads_requests_by_weight() {
+        let pool = pool(&[1, 3]);
+
+        let picks = (0..4000).filter(|_| pool.select(&[]) == 1).count();
+        assert!((2700..3300).contains(&picks), "{picks} picks of 4000");
+    }
+
+    #[test]
+    fn select_prefers_untried_endpoints() {
+        let pool = pool(&[1, 1, 1]);
+
+        for _ in 0..100 {
+            assert_eq!(pool.select(&[0, 2]), 1);
+        }
+        assert!(!pool.has_untried(&[0, 1, 2]));
+    }
+
+    #[test]
+    fn select_falls_back_to_tried_healthy_endpoints() {
+        let pool = pool(&[1, 1]);
+        eject(&pool, 1);
+
+        for _ in 0..100 {
+            assert_eq!(pool.select(&[0]), 0);
+        }
+    }
+
+    #[test]
+    fn select_skips_ejected_endpoints() {
+        let pool = pool(&[1, 1]);
+        eject(&pool, 0);
+
+        for _ in 0..100 {
+            assert_eq!(pool.select(&[]), 1);
+        }
+        assert!(!pool.has_untried(&[1]));
+    }
+
+    #[test]
+    fn select_uses_every_endpoint_when_all_are_ejected() {
+        let pool = pool(&[1, 1]);
+        eject(&pool, 0);
+        eject(&pool, 1);
+
+        let picked: Vec<usize> = (0..100).map(|_| pool.select(&[])).collect();
+        assert!(picked.contains(&0) && picked.contains(&1));
+    }
+
+    #[test]
+    fn failures_below_the_threshold_do_not_eject() {
+        let pool = pool(&[1, 1]);
+        for _ in 1..pool.eject_after_failures {
+            pool.record_failure(0);
+        }
+        pool.record_success(0);
+        pool.record_failure(0);
+
+        assert!(pool.endpoint(0).is_healthy(Instant::now()));
+    }
+
+    #[test]
+    fn reinstate_returns_an_ejected_endpoint() {
+        let pool = pool(&[1, 1]);
+        eject(&pool, 0);
+        assert!(!pool.endpoint(0).is_healthy(Instant::now()));
+
+        pool.endpoint(0).reinstate();
+        assert!(pool.endpoint(0).is_healthy(Instant::now()));
+    }
+}
@@ -0,0 +1,178 @@
+use std::sync::{
+    Arc, PoisonError, RwLock,
+    atomic::{AtomicU64, Ordering},
+};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use solana_client::{
+    client_error::{
+        Result as ClientResult,
+        reqwest::{
+            self, Response, StatusCode,
+            header::{CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+        },
+    },
+    rpc_request::{RpcError, RpcRequest, RpcResponseErrorData},
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use tracing::warn;
+
+use crate::{config::RpcAuthHeader, rpc::limiter::RateLimiter};
+
+// Timeout of a single HTTP request to the RPC endpoint
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+// Longest Retry-After that is honored, anything above it falls back to the backoff
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+#[derive(Deserialize)]
+struct RpcErrorObject {
+    code: i64,
+    message: String,
+}
+
+// HTTP transport of the RpcClient that works like the HttpSender of solana-client
+// except that it never retries by itself.
+// A 429 response blocks the endpoint's rate limiter for its Retry-After duration
+// and is then returned to the RPC layer which decides whether to retry it or not.
+pub struct RateLimitedSender {
+    client: reqwest::Client,
+    url: String,
+    limiter: Arc<RateLimiter>,
+    request_id: AtomicU64,
+    stats: RwLock<RpcTransportStats>,
+}
+
+impl RateLimitedSender {
+    pub fn new(
+        url: String,
+        auth_header: Option<&RpcAuthHeader>,
+        limiter: Arc<RateLimiter>,
+    ) -> Self {
+        // Send the auth header of the endpoint (if any) with every request
+        let mut headers = HeaderMap::new();
+        if let Some(auth_header) = auth_header {
+            let name = HeaderName::from_bytes(auth_header.name.as_bytes())
+                .expect("Invalid RPC auth header name");
+            let mut value =
+                HeaderValue::from_str(&auth_header.value).expect("Invalid RPC auth header value");
+            value.set_sensitive(true);
+            headers.insert(name, value);
+        }
+
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(REQUEST_TIMEOUT)
+            .pool_idle_timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("Failed to build the RPC HTTP client");
+
+        RateLimitedSender {
+            client,
+            url,
+            limiter,
+            request_id: AtomicU64::new(0),
+            stats: RwLock::new(RpcTransportStats::default()),
+        }
+    }
+
+    fn record_request(&self, started_at: Instant, rate_limited_time: Duration) {
+        let mut stats = self.stats.write().unwrap_or_else(PoisonError::into_inner);
+        stats.request_count += 1;
+        stats.elapsed_time += started_at.elapsed();
+        stats.rate_limited_time += rate_limited_time;
+    }
+}
+
+// Parse the Retry-After header of the response given in seconds
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+        .filter(|duration| *duration <= MAX_RETRY_AFTER)
+}
+
+#[async_trait]
+impl RpcSender for RateLimitedSender {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        let started_at = Instant::now();
+        let request_id = self.request_id.fetch_add(1, Ordering::Relaxed);
+        let body = request.build_request_json(request_id, params).to_string();
+
+        let response = self
+            .client
+            .post(&self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await;
+
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                self.record_request(started_at, Duration::ZERO);
+                return Err(err.into());
+            }
+        };
+
+        if !response.status().is_success() {
+            let mut rate_limited_time = Duration::ZERO;
+            if response.status() == StatusCode::TOO_MANY_REQUESTS
+                && let Some(duration) = retry_after(&response)
+            {
+                warn!(
+                    url = %self.url,
+                    ?duration,
+                    "RPC endpoint asked to retry after"
+                );
+                self.limiter.block_for(duration);
+                rate_limited_time = duration;
+            }
+            self.record_request(started_at, rate_limited_time);
+
+            return Err(response.error_for_status().unwrap_err().into());
+        }
+
+        let json = response.json::<Value>().await;
+        self.record_request(started_at, Duration::ZERO);
+        let mut json = json?;
+
+        // Map the JSON-RPC error object to an RpcResponseError the same way HttpSender does
+        if json["error"].is_object() {
+            let error =
+                serde_json::from_value::<RpcErrorObject>(json["error"].take()).map_err(|err| {
+                    RpcError::RpcRequestError(format!(
+                        "Failed to deserialize RPC error response: {err}"
+                    ))
+                })?;
+
+            return Err(RpcError::RpcResponseError {
+                code: error.code,
+                message: error.message,
+                data: RpcResponseErrorData::Empty,
+            }
+            .into());
+        }
+
+        Ok(json["result"].take())
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.stats
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn url(&self) -> String {
+        self.url.clone()
+    }
+}
@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use tracing::{error, info};
+
+use crate::{
+    app_state::AppState,
+    cluster::Cluster,
+    error::AppError,
+    models::{IndexingState, JobKind, JobPriority, RefreshSchedule, Timestamp, document_id},
+    queue,
+};
+
+// Views of an address closer together than this count as a single view
+// e.g. the requests of a single page load
+const VIEW_DEBOUNCE: Duration = Duration::from_secs(60);
+
+fn after(duration: Duration) -> Timestamp {
+    Timestamp::from_millis(Timestamp::now().timestamp_millis() + duration.as_millis() as i64)
+}
+
+// Record a view of an indexed address and adapt its refresh interval to it.
+// The interval moves halfway towards the time since the previous view (within the
+// configured limits), so an address viewed every few minutes is refreshed about as often
+// while an address nobody looks at anymore slows down to the longest interval
+pub async fn record_view(
+    state: &AppState,
+    cluster: Cluster,
+    address: &str,
+) -> Result<(), AppError> {
+    let min_interval = state.config.auto_refresh_min_interval.as_secs() as i64;
+    let max_interval = state.config.auto_refresh_max_interval.as_secs() as i64;
+    let now = Timestamp::now();
+
+    let schedule = match state.db.get_refresh_schedule(cluster, address).await? {
+        Some(schedule) => {
+            let since_last_view =
+                (now.timestamp_millis() - schedule.last_viewed_at.timestamp_millis()) / 1000;
+            if since_last_view < VIEW_DEBOUNCE.as_secs() as i64 {
+                return Ok(());
+            }
+
+            let interval =
+                ((schedule.interval_secs + since_last_view) / 2).clamp(min_interval, max_interval);
+            let next_refresh_at =
+                after(Duration::from_secs(interval as u64)).min(schedule.next_refresh_at);
+            RefreshSchedule {
+                interval_secs: interval,
+                last_viewed_at: now,
+                next_refresh_at,
+                ..schedule
+            }
+        }
+        None => RefreshSchedule {
+            id: document_id(cluster, address),
+            cluster,
+            address: address.to_string(),
+            interval_secs: max_interval,
+            last_viewed_at: now,
+            next_refresh_at: after(state.config.auto_refresh_max_interval),
+        },
+    };
+
+    state.db.save_refresh_schedule(&schedule).await
+}
+
+// Periodically queue a refresh of the addresses whose refresh is due.
+// At most auto_refresh_concurrency of these refreshes are queued or running at once and
+// an address that already has a session (a client is indexing/refreshing it) is skipped
+pub fn spawn_auto_refresh(state: &AppState) {
+    if state.config.auto_refresh_concurrency == 0 {
+        return;
+    }
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        info!("Automatic refresh started");
+        // The refreshes queued by the scheduler that have not finished yet
+        let mut in_flight: Vec<(Cluster, String)> = vec![];
+        let mut ticker = tokio::time::interval(state.config.auto_refresh_check_interval);
+        loop {
+            ticker.tick().await;
+            in_flight.retain(|(cluster, address)| state.find_session(*cluster, address).is_some());
+
+            if let Err(err) = schedule_refreshes(&state, &mut in_flight).await {
+                error!(%err, "Error occurred while scheduling the automatic refreshes");
+            }
+        }
+    });
+}
+
+async fn schedule_refreshes(
+    state: &AppState,
+    in_flight: &mut Vec<(Cluster, String)>,
+) -> Result<(), AppError> {
+    let free = state
+        .config
+        .auto_refresh_concurrency
+        .saturating_sub(in_flight.len());
+    if free == 0 {
+        return Ok(());
+    }
+
+    let clusters: Vec<Cluster> = state.rpc.keys().copied().collect();
+    let due = state
+        .db
+        .get_due_refresh_schedules(&clusters, free as i64)
+        .await?;
+
+    for schedule in due {
+        let RefreshSchedule {
+            id,
+            cluster,
+            address,
+            interval_secs,
+            ..
+        } = schedule;
+        // The next refresh is counted from now whether this one runs or not
+        state
+            .db
+            .reschedule_refresh(&id, after(Duration::from_secs(interval_secs.max(0) as u64)))
+            .await?;
+
+        // A live session is already indexing or refreshing the address
+        if state.find_session(cluster, &address).is_some() {
+            continue;
+        }
+        // Only an address whose last run finished is refreshed,
+        // a paused, cancelled or failed one is left to the clients and admins
+        match state.db.get_address_indexing_state(cluster, &address).await {
+            Ok(indexing_state) if indexing_state.state == IndexingState::Idle => {}
+            Ok(_) | Err(AppError::NotFound(_)) => continue,
+            Err(err) => return Err(err),
+        }
+
+        queue::enqueue(
+            state,
+            cluster,
+            address.clone(),
+            JobKind::Refresh,
+            JobPriority::Background,
+            None,
+        )
+        .await?;
+        info!(%cluster, %address, interval_secs, "Automatic refresh queued");
+        in_flight.push((cluster, address));
+    }
+
+    Ok(())
+}
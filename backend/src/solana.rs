@@ -1,142 +1,234 @@
+use std::pin::pin;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, atomic::Ordering};
 
-use chrono::Utc;
-use mongodb::bson::DateTime as BsonDateTime;
-use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
-use solana_sdk::{pubkey::Pubkey, signature::Signature};
-use solana_transaction_status::UiTransactionEncoding;
-use tracing::{info, instrument};
+use futures::{StreamExt, TryStreamExt, stream};
+use solana_client::{
+    rpc_client::GetConfirmedSignaturesForAddress2Config, rpc_config::RpcTransactionConfig,
+    rpc_response::RpcConfirmedTransactionStatusWithSignature,
+};
+use solana_sdk::{
+    account::Account as SolanaAccount, pubkey::Pubkey, signature::Signature,
+    transaction::TransactionVersion,
+};
+use solana_transaction_status::{
+    EncodedTransaction, EncodedTransactionWithStatusMeta, UiMessage, UiTransactionEncoding,
+    option_serializer::OptionSerializer,
+};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{error, info, instrument, warn};
 
 use crate::{
-    app_state::{AddressSession, AppState},
-    db::{
-        accounts::{
-            check_account_exists, insert_account, insert_address_indexing_state, update_account,
-            update_address_indexing_state,
-        },
-        transactions::{
-            get_latest_signature, get_signatures_count, get_transactions_count,
-            insert_transactions, insert_transactions_signatures,
-        },
-    },
+    app_state::{AddressSession, AppState, StopRequest},
+    cluster::Cluster,
+    dead_letters::next_attempt_at,
     error::AppError,
     message::SyncStatus,
     models::{
-        Account, AddressIndexingState, IndexingState, Transaction, TransactionSignature,
-        UpdateAccount, UpdateAddressIndexingState,
+        Account, AccountStatus, AddressIndexingState, DeadLetter, DeadLetterStatus, IndexingBounds,
+        IndexingCheckpoint, IndexingError, IndexingState, LoadedAddresses, SKIPPED_TRANSACTIONS,
+        Timestamp, Transaction, TransactionSignature, UpdateAccount, UpdateAddressIndexingState,
+        UpsertCounts, document_id, membership_id,
     },
 };
 
-fn bson_current_time() -> BsonDateTime {
-    BsonDateTime::from_millis(Utc::now().timestamp_millis())
-}
-
-async fn tokio_sleep(milliseconds: u64) {
-    tokio::time::sleep(std::time::Duration::from_millis(milliseconds)).await;
-}
-
+// Progress of the signatures/transactions of the address
+// along with the outcome of the last write to the DB
 #[derive(Debug, serde::Serialize)]
 struct TotalFetch {
     total: u64,
     fetched: u64,
+    #[serde(flatten)]
+    written: UpsertCounts,
 }
 
 #[instrument(skip(state, session))]
 pub async fn indexer(
     state: AppState,
     session: Arc<AddressSession>,
+    cluster: Cluster,
     address: String,
+    bounds: Option<IndexingBounds>,
 ) -> Result<(), AppError> {
     // Convert the address str to Address struct instance of Solana account
     let public_key = Pubkey::from_str(&address)?;
+    state.rpc_for(cluster)?;
 
     // Before indexing the account, check if it is already indexed
-    if check_account_exists(&state.db, &address).await {
+    if state.db.check_account_exists(cluster, &address).await {
+        // An account whose indexing was interrupted by a crash, an error or a cancel
+        // picks up the indexing from its saved checkpoint instead of refusing it
+        let indexing_state = state
+            .db
+            .get_address_indexing_state(cluster, &address)
+            .await?;
+        reject_paused(&indexing_state)?;
+        if indexing_state.state.is_resumable() {
+            info!("Resume indexing the address");
+            return resume_run(state, session, public_key, indexing_state).await;
+        }
+
+        // A request for a deeper history than the stored one extends it
+        if let Some(bounds) = bounds
+            && indexing_state.state == IndexingState::Idle
+            && bounds != indexing_state.bounds
+            && bounds.covers(&indexing_state.bounds)
+        {
+            info!(?bounds, "Extend the indexed history of the address");
+            return extend_history(state, session, public_key, indexing_state, bounds).await;
+        }
+
         return Err(AppError::BadRequest(
             "Account is already indexed".to_string(),
         ));
     }
 
+    // A job without bounds uses the ones stored by an earlier attempt or the default ones
+    let bounds = match bounds {
+        Some(bounds) => bounds,
+        None => match state.db.get_address_indexing_state(cluster, &address).await {
+            Ok(indexing_state) => indexing_state.bounds,
+            Err(AppError::NotFound(_)) => state.config.default_bounds(),
+            Err(err) => return Err(err),
+        },
+    };
+
     // Insert the indexing state of the address for tracking purposes
-    insert_address_indexing_state(
-        &state.db,
-        AddressIndexingState {
+    let inserted = state
+        .db
+        .insert_address_indexing_state(AddressIndexingState {
+            id: document_id(cluster, &address),
+            cluster,
             address: address.clone(),
             state: IndexingState::Indexing,
-            created_at: bson_current_time(),
-            updated_at: bson_current_time(),
-        },
-    )
-    .await?;
+            checkpoint: None,
+            bounds: bounds.clone(),
+            last_error: None,
+            started_at: Some(Timestamp::now()),
+            finished_at: None,
+            created_at: Timestamp::now(),
+            updated_at: Timestamp::now(),
+        })
+        .await?;
+
+    // An earlier attempt left a state behind before the account was stored
+    // (e.g. it failed or was queued again by the recovery), so the address
+    // moves on from that state only when the transition is legal
+    if !inserted {
+        state
+            .db
+            .update_address_indexing_state(
+                cluster,
+                &address,
+                UpdateAddressIndexingState {
+                    state: IndexingState::Indexing,
+                    checkpoint: None,
+                    last_error: None,
+                    updated_at: Timestamp::now(),
+                },
+            )
+            .await?;
+        state
+            .db
+            .save_indexing_bounds(cluster, &address, &bounds, Timestamp::now())
+            .await?;
+    }
 
     info!("Begin indexing the address");
     session.emit_event(SyncStatus::Indexing).await;
 
-    // Get the Solana account data of the address
-    let account = state.rpc.get_account(&public_key).await?;
-    info!(?account);
+    if let Err(err) = index_address(
+        state.clone(),
+        session,
+        cluster,
+        address.clone(),
+        public_key,
+        bounds,
+    )
+    .await
+    {
+        return Err(record_failure(&state, cluster, &address, err).await);
+    }
 
-    let account = Account {
-        address: address.clone(),
-        lamports: account.lamports as i64,
-        owner: account.owner.to_string(),
-        executable: account.executable,
-        data_length: account.data.len() as i64,
-        rent_epoch: account.rent_epoch as i64,
-        indexed_at: bson_current_time(),
-        last_updated_at: bson_current_time(),
-    };
+    Ok(())
+}
 
-    // Insert the account data into DB
-    insert_account(&state.db, &account).await?;
+// Index a new address: its account data, the first batch of its transactions
+// and then the rest of them through the sync pipeline
+async fn index_address(
+    state: AppState,
+    session: Arc<AddressSession>,
+    cluster: Cluster,
+    address: String,
+    public_key: Pubkey,
+    bounds: IndexingBounds,
+) -> Result<(), AppError> {
+    let rpc = state.rpc_for(cluster)?;
 
-    // Send the account data to the channel
-    session
-        .emit_event(SyncStatus::AccountData(serde_json::to_string(&account)?))
-        .await;
+    // Get the Solana account data of the address
+    // A closed or never funded address has no account but its history is indexed all the same
+    let account = rpc
+        .get_account(Some(&session), &public_key, state.config.commitment())
+        .await?;
+    info!(?account);
 
     // Get only the latest 20 transaction signatures
-    let signatures = state
-        .rpc
+    let mut signatures = rpc
         .get_signatures_for_address_with_config(
+            Some(&session),
             &public_key,
             GetConfirmedSignaturesForAddress2Config {
                 before: None,
                 until: None,
                 limit: Some(20),
-                commitment: None,
+                commitment: Some(state.config.commitment()),
             },
         )
         .await?;
 
-    if signatures.is_empty() {
-        return Err(AppError::Solana(
-            "No transactions found for this address".to_string(),
-        ));
-    }
+    let update = account_update(account.as_ref(), !signatures.is_empty());
+    let account = Account {
+        id: document_id(cluster, &address),
+        cluster,
+        address: address.clone(),
+        status: update.status,
+        lamports: update.lamports,
+        owner: update.owner,
+        executable: update.executable,
+        data_length: update.data_length,
+        rent_epoch: update.rent_epoch,
+        indexed_at: Timestamp::now(),
+        last_updated_at: update.last_updated_at,
+    };
 
-    let mut txn_signs: Vec<TransactionSignature> = vec![];
+    // Insert the account data into DB
+    state.db.insert_account(&account).await?;
 
-    // // Parse the actual transaction signatures to DB format
-    for sign in &signatures {
-        txn_signs.push(TransactionSignature {
-            signature: sign.signature.clone(),
-            account_address: address.clone(),
-            slot: sign.slot as i64,
-            block_time: sign.block_time,
-            confirmation_status: serde_json::from_str(&serde_json::to_string(
-                &sign.confirmation_status,
-            )?)?,
-            indexed_at: bson_current_time(),
-        });
+    // Send the account data to the channel
+    session
+        .emit_event(SyncStatus::AccountData(serde_json::to_string(&account)?))
+        .await;
+
+    // A fresh address without any transaction is indexed as it is
+    // and the next refresh picks up its first transactions
+    if signatures.is_empty() {
+        info!("No transactions found for this address");
+        return complete_run(&state, &session, cluster, &address).await;
     }
+    let reached = apply_bounds(&mut signatures, &bounds, 0);
+
+    // Parse the actual transaction signatures to DB format
+    let txn_signs = signatures
+        .iter()
+        .map(|sign| signature_record(cluster, &address, sign))
+        .collect::<Result<Vec<_>, _>>()?;
 
-    // Insert the transaction signatures into DB
-    insert_transactions_signatures(&state.db, &txn_signs).await?;
+    // Upsert the transaction signatures into DB
+    let written = state.db.upsert_transaction_signatures(&txn_signs).await?;
 
     // Get the total transaction signatures count of the account in DB
-    let sign_count = get_signatures_count(&state.db, &address).await?;
+    let sign_count = state.db.get_signatures_count(cluster, &address).await?;
 
     // Send the transaction signatures data status to the channel
     session
@@ -144,38 +236,33 @@ pub async fn indexer(
             &TotalFetch {
                 total: sign_count,
                 fetched: txn_signs.len() as u64,
+                written,
             },
         )?))
         .await;
 
-    let mut txns: Vec<Transaction> = vec![];
-    for sign in &signatures {
-        let signature = Signature::from_str(&sign.signature)?;
-
-        // Sleep again since these are individual RPC calls to get each transaction
-        tokio_sleep(100).await;
-
-        // Fetch individual transaction based on signature
-        let txn = state
-            .rpc
-            .get_transaction(&signature, UiTransactionEncoding::JsonParsed)
-            .await?;
-
-        txns.push(Transaction {
-            signature: sign.signature.clone(),
-            account_address: address.clone(),
-            slot: txn.slot as i64,
-            block_time: txn.block_time,
-            transaction: serde_json::to_value(txn.transaction)?,
-            indexed_at: bson_current_time(),
-        });
-    }
+    // Fetch the transactions with a bounded number of RPC calls in flight
+    let fetch_signs: Vec<String> = txn_signs
+        .iter()
+        .map(|sign| sign.signature.clone())
+        .collect();
+    let txns: Vec<Transaction> = stream::iter(fetch_signs)
+        .map(|signature| {
+            let state = state.clone();
+            let session = session.clone();
+            let address = address.clone();
+            async move { fetch_or_skip(&state, Some(&session), cluster, &address, &signature).await }
+        })
+        .buffered(state.config.fetch_concurrency)
+        .try_filter_map(|txn| async move { Ok(txn) })
+        .try_collect()
+        .await?;
 
-    // Insert the transactions into DB
-    insert_transactions(&state.db, &txns).await?;
+    // Upsert the transactions into DB
+    let written = state.db.upsert_transactions(&address, &txns).await?;
 
     // Get the total transactions count of the account in DB
-    let txn_count = get_transactions_count(&state.db, &address).await?;
+    let txn_count = state.db.get_transactions_count(cluster, &address).await?;
 
     // Send the transactions data status to the channel
     session
@@ -183,6 +270,7 @@ pub async fn indexer(
             &TotalFetch {
                 total: txn_count,
                 fetched: txns.len() as u64,
+                written,
             },
         )?))
         .await;
@@ -193,54 +281,562 @@ pub async fn indexer(
         txns.len()
     );
 
-    if let Some(next_signature) = signatures.last() {
-        let next_signature = next_signature.signature.clone();
+    // The first batch already reached the bounds, there's nothing older to page through
+    if reached {
+        return complete_run(&state, &session, cluster, &address).await;
+    }
+
+    let checkpoint = IndexingCheckpoint {
+        before_signature: signatures.last().map(|sign| sign.signature.clone()),
+        until_signature: None,
+        batch: 1,
+        fetched_signatures: signatures.len() as i64,
+        fetched_transactions: txns.len() as i64,
+    };
+
+    // Save the progress of the first batch before continuing with the rest
+    state
+        .db
+        .save_indexing_checkpoint(cluster, &address, &checkpoint, Timestamp::now())
+        .await?;
+
+    continue_sync(
+        state, session, cluster, address, public_key, checkpoint, bounds,
+    )
+    .await
+}
+
+// Index the history of an indexed address further back, down to the deeper bounds
+// Paging starts below the oldest stored signature so the stored history isn't fetched again
+async fn extend_history(
+    state: AppState,
+    session: Arc<AddressSession>,
+    public_key: Pubkey,
+    indexing_state: AddressIndexingState,
+    bounds: IndexingBounds,
+) -> Result<(), AppError> {
+    let AddressIndexingState {
+        cluster,
+        address,
+        last_error,
+        ..
+    } = indexing_state;
+
+    // The counts include the stored history so the count bound applies to all of it
+    let checkpoint = IndexingCheckpoint {
+        before_signature: state.db.get_oldest_signature(cluster, &address).await?,
+        until_signature: None,
+        batch: 0,
+        fetched_signatures: state.db.get_signatures_count(cluster, &address).await? as i64,
+        fetched_transactions: state.db.get_transactions_count(cluster, &address).await? as i64,
+    };
+
+    state
+        .db
+        .update_address_indexing_state(
+            cluster,
+            &address,
+            UpdateAddressIndexingState {
+                state: IndexingState::Indexing,
+                checkpoint: Some(checkpoint.clone()),
+                last_error,
+                updated_at: Timestamp::now(),
+            },
+        )
+        .await?;
+    session.emit_event(SyncStatus::Indexing).await;
+
+    // The bounds are stored with the checkpoint once the address moved to indexing,
+    // so an interrupted extension resumes with them and a rejected one leaves them as they were
+    let extension = async {
+        state
+            .db
+            .save_indexing_bounds(cluster, &address, &bounds, Timestamp::now())
+            .await?;
         continue_sync(
-            state,
+            state.clone(),
             session,
+            cluster,
             address.clone(),
             public_key,
-            Some(Signature::from_str(&next_signature)?),
-            None,
-            signatures.len(),
-            txns.len(),
+            checkpoint,
+            bounds,
+        )
+        .await
+    };
+    if let Err(err) = extension.await {
+        return Err(record_failure(&state, cluster, &address, err).await);
+    }
+
+    Ok(())
+}
+
+// The account data of the address in DB format
+// An address without an account was closed when it has a history and never funded otherwise,
+// its account data is zeroed and owned by the system program like any account without lamports
+pub fn account_update(account: Option<&SolanaAccount>, has_history: bool) -> UpdateAccount {
+    match account {
+        Some(account) => UpdateAccount {
+            status: AccountStatus::Active,
+            lamports: account.lamports as i64,
+            owner: account.owner.to_string(),
+            executable: account.executable,
+            data_length: account.data.len() as i64,
+            rent_epoch: account.rent_epoch as i64,
+            last_updated_at: Timestamp::now(),
+        },
+        None => UpdateAccount {
+            status: if has_history {
+                AccountStatus::Closed
+            } else {
+                AccountStatus::Nonexistent
+            },
+            lamports: 0,
+            owner: Pubkey::default().to_string(),
+            executable: false,
+            data_length: 0,
+            rent_epoch: 0,
+            last_updated_at: Timestamp::now(),
+        },
+    }
+}
+
+// A paused run stays paused until an admin resumes it, which queues it again
+fn reject_paused(indexing_state: &AddressIndexingState) -> Result<(), AppError> {
+    if indexing_state.state == IndexingState::Paused {
+        return Err(AppError::BadRequest(
+            "The run of the address is paused, it continues once an admin resumes it".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+// Pick up an interrupted, failed or cancelled run of the address from its saved checkpoint
+async fn resume_run(
+    state: AppState,
+    session: Arc<AddressSession>,
+    public_key: Pubkey,
+    indexing_state: AddressIndexingState,
+) -> Result<(), AppError> {
+    let AddressIndexingState {
+        cluster,
+        address,
+        checkpoint,
+        bounds,
+        last_error,
+        ..
+    } = indexing_state;
+
+    // A run with an until signature is a sync of an already indexed address
+    let (run_state, event) = match &checkpoint {
+        Some(checkpoint) if checkpoint.until_signature.is_some() => {
+            (IndexingState::Syncing, SyncStatus::Syncing)
+        }
+        _ => (IndexingState::Indexing, SyncStatus::Indexing),
+    };
+
+    state
+        .db
+        .update_address_indexing_state(
+            cluster,
+            &address,
+            UpdateAddressIndexingState {
+                state: run_state,
+                checkpoint: checkpoint.clone(),
+                last_error,
+                updated_at: Timestamp::now(),
+            },
         )
         .await?;
+    session.emit_event(event).await;
+
+    if let Err(err) = continue_sync(
+        state.clone(),
+        session,
+        cluster,
+        address.clone(),
+        public_key,
+        checkpoint.unwrap_or_default(),
+        bounds,
+    )
+    .await
+    {
+        return Err(record_failure(&state, cluster, &address, err).await);
     }
 
     Ok(())
 }
 
+// Save the error of a failed run on the address so its state tells the truth
+// A run that was asked to stop is recorded as paused or cancelled instead
+// The error itself is returned to be sent to the clients of the session
+async fn record_failure(
+    state: &AppState,
+    cluster: Cluster,
+    address: &str,
+    err: AppError,
+) -> AppError {
+    let recorded = match &err {
+        AppError::Stopped(request) => mark_stopped(state, cluster, address, *request).await,
+        _ => mark_failed(state, cluster, address, &err).await,
+    };
+    if let Err(record_err) = recorded {
+        error!(%record_err, "Error occurred while recording the failed run of the address");
+    }
+
+    err
+}
+
+// Move the address to Paused or Cancelled once its run was asked to stop
+// The checkpoint is kept so the run resumes from the last stored batch
+pub async fn mark_stopped(
+    state: &AppState,
+    cluster: Cluster,
+    address: &str,
+    request: StopRequest,
+) -> Result<(), AppError> {
+    let indexing_state = state
+        .db
+        .get_address_indexing_state(cluster, address)
+        .await?;
+    let stopped_state = match request {
+        StopRequest::Cancel => IndexingState::Cancelled,
+        StopRequest::Pause => IndexingState::Paused,
+    };
+
+    state
+        .db
+        .update_address_indexing_state(
+            cluster,
+            address,
+            UpdateAddressIndexingState {
+                state: stopped_state,
+                checkpoint: indexing_state.checkpoint,
+                last_error: indexing_state.last_error,
+                updated_at: Timestamp::now(),
+            },
+        )
+        .await
+}
+
+// Move the address to Partial or Failed along with the error that stopped its run
+// A run that already stored some batches leaves a usable but incomplete index (Partial)
+// behind while a run that stored nothing is Failed
+pub async fn mark_failed(
+    state: &AppState,
+    cluster: Cluster,
+    address: &str,
+    err: &AppError,
+) -> Result<(), AppError> {
+    let indexing_state = state
+        .db
+        .get_address_indexing_state(cluster, address)
+        .await?;
+    let failed_state = if indexing_state.checkpoint.is_some() {
+        IndexingState::Partial
+    } else {
+        IndexingState::Failed
+    };
+
+    state
+        .db
+        .update_address_indexing_state(
+            cluster,
+            address,
+            UpdateAddressIndexingState {
+                state: failed_state,
+                checkpoint: indexing_state.checkpoint,
+                last_error: Some(IndexingError {
+                    kind: err.kind().to_string(),
+                    message: err.to_string(),
+                    occurred_at: Timestamp::now(),
+                }),
+                updated_at: Timestamp::now(),
+            },
+        )
+        .await
+}
+
 #[instrument(skip_all)]
 async fn continue_sync(
     state: AppState,
     session: Arc<AddressSession>,
+    cluster: Cluster,
     address: String,
     public_key: Pubkey,
-    mut before_signature: Option<Signature>,
-    until_signature: Option<Signature>,
-    fetched_signatures: usize,
-    fetched_transactions: usize,
+    checkpoint: IndexingCheckpoint,
+    bounds: IndexingBounds,
 ) -> Result<(), AppError> {
-    let mut total_signs = fetched_signatures;
-    let mut total_txns = fetched_transactions;
-    let mut batch = 1;
+    // Signature paging, transaction fetching and DB inserts run as separate stages
+    // connected through bounded channels, so the next page of signatures is fetched
+    // while the transactions of the previous one are still being fetched and stored
+    let (batch_sender, batch_receiver) = mpsc::channel(PIPELINE_BUFFER);
+    let (chunk_sender, chunk_receiver) = mpsc::channel(PIPELINE_BUFFER);
+
+    tokio::try_join!(
+        page_signatures(
+            &state,
+            &session,
+            cluster,
+            public_key,
+            &checkpoint,
+            &bounds,
+            batch_sender
+        ),
+        fetch_transactions(
+            &state,
+            &session,
+            cluster,
+            &address,
+            batch_receiver,
+            chunk_sender
+        ),
+        write_transactions(
+            &state,
+            &session,
+            cluster,
+            &address,
+            checkpoint.clone(),
+            chunk_receiver
+        ),
+    )?;
+
+    complete_run(&state, &session, cluster, &address).await
+}
+
+// Once the indexing/syncing/refreshing is completed
+// set the address indexing state to Idle, or to Partial when the run skipped transactions
+async fn complete_run(
+    state: &AppState,
+    session: &AddressSession,
+    cluster: Cluster,
+    address: &str,
+) -> Result<(), AppError> {
+    let skipped = session.skipped.swap(0, Ordering::AcqRel);
+    let (completed_state, last_error) = if skipped > 0 {
+        let error = IndexingError {
+            kind: SKIPPED_TRANSACTIONS.to_string(),
+            message: format!(
+                "{skipped} transactions could not be fetched, they are retried in the background"
+            ),
+            occurred_at: Timestamp::now(),
+        };
+        (IndexingState::Partial, Some(error))
+    } else {
+        (IndexingState::Idle, None)
+    };
+
+    state
+        .db
+        .update_address_indexing_state(
+            cluster,
+            address,
+            UpdateAddressIndexingState {
+                state: completed_state,
+                checkpoint: None,
+                last_error,
+                updated_at: Timestamp::now(),
+            },
+        )
+        .await?;
+    info!(skipped, "Indexing is completed");
+
+    // Send the completed indexing message to the channel
+    session.emit_event(SyncStatus::Completed).await;
+
+    Ok(())
+}
+
+// A page of transaction signatures already stored in DB
+// that is handed over from the paging stage to the fetch stage
+struct SignatureBatch {
+    batch: i64,
+    signatures: Vec<TransactionSignature>,
+}
+
+// Marks the end of a signature batch in the fetch stage output
+// so the insert stage knows when the batch can be checkpointed
+#[derive(Clone)]
+struct CompletedBatch {
+    batch: i64,
+    signatures: usize,
+    before_signature: Option<String>,
+}
+
+// A sub-batch of fetched transactions that is flushed to the DB together
+struct TransactionChunk {
+    txns: Vec<Transaction>,
+    completed: Option<CompletedBatch>,
+}
+
+// Number of batches or chunks that can wait between two pipeline stages
+const PIPELINE_BUFFER: usize = 2;
+
+// Highest transaction version the indexer can parse
+// Without it the RPC rejects every v0 transaction instead of returning it
+const MAX_SUPPORTED_TRANSACTION_VERSION: u8 = 0;
+
+// Fetch a single transaction based on its signature and parse it to DB format
+pub async fn fetch_transaction(
+    state: &AppState,
+    session: Option<&AddressSession>,
+    cluster: Cluster,
+    signature: &str,
+) -> Result<Transaction, AppError> {
+    let txn = state
+        .rpc_for(cluster)?
+        .get_transaction_with_config(
+            session,
+            &Signature::from_str(signature)?,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::JsonParsed),
+                commitment: Some(state.config.commitment()),
+                max_supported_transaction_version: Some(MAX_SUPPORTED_TRANSACTION_VERSION),
+            },
+        )
+        .await?;
+
+    let loaded_addresses = loaded_addresses(&txn.transaction);
+
+    Ok(Transaction {
+        id: document_id(cluster, signature),
+        cluster,
+        signature: signature.to_string(),
+        slot: txn.slot as i64,
+        block_time: txn.block_time,
+        version: txn
+            .transaction
+            .version
+            .as_ref()
+            .map(|version| match version {
+                TransactionVersion::Legacy(_) => "legacy".to_string(),
+                TransactionVersion::Number(number) => number.to_string(),
+            }),
+        account_keys: account_keys(&txn.transaction, &loaded_addresses),
+        loaded_addresses,
+        transaction: serde_json::to_value(txn.transaction)?,
+        indexed_at: Timestamp::now(),
+    })
+}
+
+// The addresses a v0 transaction loaded from its address lookup tables
+// Legacy transactions have none
+fn loaded_addresses(txn: &EncodedTransactionWithStatusMeta) -> LoadedAddresses {
+    match txn.meta.as_ref().map(|meta| &meta.loaded_addresses) {
+        Some(OptionSerializer::Some(loaded)) => LoadedAddresses {
+            writable: loaded.writable.clone(),
+            readonly: loaded.readonly.clone(),
+        },
+        _ => LoadedAddresses::default(),
+    }
+}
+
+// The static account keys of the message followed by the loaded addresses,
+// in the same order the instructions index them
+fn account_keys(txn: &EncodedTransactionWithStatusMeta, loaded: &LoadedAddresses) -> Vec<String> {
+    let mut keys: Vec<String> = match &txn.transaction {
+        EncodedTransaction::Json(ui_txn) => match &ui_txn.message {
+            UiMessage::Parsed(message) => message
+                .account_keys
+                .iter()
+                .map(|account| account.pubkey.clone())
+                .collect(),
+            UiMessage::Raw(message) => message.account_keys.clone(),
+        },
+        _ => vec![],
+    };
+
+    // The parsed message already lists the loaded addresses after the static keys
+    for key in loaded.writable.iter().chain(&loaded.readonly) {
+        if !keys.contains(key) {
+            keys.push(key.clone());
+        }
+    }
+
+    keys
+}
+
+// Drop the signatures of a page that are beyond the bounds of the history
+// A page is ordered from the newest signature to the oldest, so every signature after
+// the first one that crosses a bound is beyond it as well.
+// Returns whether a bound was reached, i.e. there is nothing left to page
+fn apply_bounds(
+    signatures: &mut Vec<RpcConfirmedTransactionStatusWithSignature>,
+    bounds: &IndexingBounds,
+    stored: u64,
+) -> bool {
+    let mut keep = signatures
+        .iter()
+        .position(|sign| bounds.excludes(sign.slot as i64, sign.block_time))
+        .unwrap_or(signatures.len());
+    if let Some(max_signatures) = bounds.max_signatures {
+        let left = (max_signatures.max(0) as u64).saturating_sub(stored);
+        keep = keep.min(left as usize);
+    }
+
+    let reached = keep < signatures.len();
+    signatures.truncate(keep);
+    reached
+}
+
+// Paging stage of the sync pipeline
+// Pages through the transaction signatures of the address, stores every page in DB
+// and then hands it over to the fetch stage
+async fn page_signatures(
+    state: &AppState,
+    session: &AddressSession,
+    cluster: Cluster,
+    public_key: Pubkey,
+    checkpoint: &IndexingCheckpoint,
+    bounds: &IndexingBounds,
+    sender: mpsc::Sender<SignatureBatch>,
+) -> Result<(), AppError> {
+    let address = public_key.to_string();
+    let mut before_signature = checkpoint
+        .before_signature
+        .as_deref()
+        .map(Signature::from_str)
+        .transpose()?;
+    let until_signature = checkpoint
+        .until_signature
+        .as_deref()
+        .map(Signature::from_str)
+        .transpose()?;
+    let mut total_signs = checkpoint.fetched_signatures as u64;
+    let mut batch = checkpoint.batch;
+    let rpc = state.rpc_for(cluster)?;
     const BATCH_SIZE: usize = 1000;
 
+    // The bounds only limit how far back the history goes,
+    // a sync of the newer signatures stops at its until signature
+    let unbounded = IndexingBounds::default();
+    let bounds = if until_signature.is_some() {
+        &unbounded
+    } else {
+        bounds
+    };
+
     loop {
-        // Sleep for a while to avoid RPC rate limits
-        tokio_sleep(100).await;
+        // Don't ask for signatures that the count bound would drop anyway
+        if bounds
+            .max_signatures
+            .is_some_and(|max_signatures| total_signs >= max_signatures.max(0) as u64)
+        {
+            info!("Reached the bounds of the history");
+            break;
+        }
 
         // Get the next batch transaction signatures
-        let signatures = state
-            .rpc
+        let mut signatures = rpc
             .get_signatures_for_address_with_config(
+                Some(session),
                 &public_key,
                 GetConfirmedSignaturesForAddress2Config {
                     before: before_signature,
                     until: until_signature,
                     limit: Some(BATCH_SIZE),
-                    commitment: None,
+                    commitment: Some(state.config.commitment()),
                 },
             )
             .await?;
@@ -250,111 +846,240 @@ async fn continue_sync(
             break;
         }
 
+        let reached = apply_bounds(&mut signatures, bounds, total_signs);
         if let Some(last_signature) = signatures.last() {
-            before_signature = Some(Signature::from_str(&last_signature.signature.clone())?);
+            before_signature = Some(Signature::from_str(&last_signature.signature)?);
         } else {
+            info!("Reached the bounds of the history");
             break;
         }
 
-        let mut txn_signs: Vec<TransactionSignature> = vec![];
-
         // Parse the transaction signatures to DB format
-        for sign in &signatures {
-            txn_signs.push(TransactionSignature {
-                signature: sign.signature.clone(),
-                account_address: address.clone(),
-                slot: sign.slot as i64,
-                block_time: sign.block_time,
-                confirmation_status: serde_json::from_str(&serde_json::to_string(
-                    &sign.confirmation_status,
-                )?)?,
-                indexed_at: bson_current_time(),
-            });
-        }
+        let txn_signs = signatures
+            .iter()
+            .map(|sign| signature_record(cluster, &address, sign))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        // Insert the transaction signatures into DB
-        insert_transactions_signatures(&state.db, &txn_signs).await?;
+        // Upsert the transaction signatures into DB
+        let written = state.db.upsert_transaction_signatures(&txn_signs).await?;
 
-        total_signs += signatures.len();
+        total_signs += txn_signs.len() as u64;
 
         // Get the total transaction signatures count of the account in DB
-        let sign_count = get_signatures_count(&state.db, &address).await?;
+        let sign_count = state.db.get_signatures_count(cluster, &address).await?;
 
         // Send the transaction signatures data status to the channel
         session
             .emit_event(SyncStatus::TransactionSignatures(serde_json::to_string(
                 &TotalFetch {
                     total: sign_count,
-                    fetched: total_signs as u64,
+                    fetched: total_signs,
+                    written,
                 },
             )?))
             .await;
 
-        let mut txns: Vec<Transaction> = vec![];
-        for sign in &signatures {
-            let signature = Signature::from_str(&sign.signature)?;
+        batch += 1;
 
-            // Sleep again since these are individual RPC calls to get each transaction
-            tokio_sleep(100).await;
+        // Hand the batch over to the fetch stage
+        // It can only fail when a later stage has stopped with an error
+        // which is then returned by the pipeline itself
+        let batch = SignatureBatch {
+            batch,
+            signatures: txn_signs,
+        };
+        if sender.send(batch).await.is_err() {
+            break;
+        }
 
-            // Get the transaction details based on the signature
-            let txn = state
-                .rpc
-                .get_transaction(&signature, UiTransactionEncoding::JsonParsed)
-                .await?;
+        if reached {
+            info!("Reached the bounds of the history");
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+// Parse a transaction signature of the address to DB format
+pub fn signature_record(
+    cluster: Cluster,
+    address: &str,
+    sign: &RpcConfirmedTransactionStatusWithSignature,
+) -> Result<TransactionSignature, AppError> {
+    Ok(TransactionSignature {
+        id: membership_id(cluster, address, &sign.signature),
+        cluster,
+        signature: sign.signature.clone(),
+        account_address: address.to_string(),
+        slot: sign.slot as i64,
+        block_time: sign.block_time,
+        confirmation_status: serde_json::from_str(&serde_json::to_string(
+            &sign.confirmation_status,
+        )?)?,
+        indexed_at: Timestamp::now(),
+    })
+}
+
+// Fetch the transaction of a run or skip it when it can't be fetched.
+// A skipped signature is recorded as a dead letter, so the run carries on without it
+// and the transaction is retried in the background
+pub async fn fetch_or_skip(
+    state: &AppState,
+    session: Option<&AddressSession>,
+    cluster: Cluster,
+    address: &str,
+    signature: &str,
+) -> Result<Option<Transaction>, AppError> {
+    let err = match fetch_transaction(state, session, cluster, signature).await {
+        Ok(txn) => return Ok(Some(txn)),
+        // A stopped run stops as a whole instead, and so does a run whose cluster is unavailable
+        // since every transaction after this one would be skipped as well,
+        // the failed run resumes from its checkpoint once the cluster is back
+        Err(err @ (AppError::Stopped(_) | AppError::Unavailable(_))) => return Err(err),
+        Err(err) => err,
+    };
+    warn!(%signature, %err, "Skipped the transaction that could not be fetched");
+
+    let now = Timestamp::now();
+    state
+        .db
+        .record_dead_letter(&DeadLetter {
+            id: membership_id(cluster, address, signature),
+            cluster,
+            account_address: address.to_string(),
+            signature: signature.to_string(),
+            status: DeadLetterStatus::Pending,
+            attempts: 1,
+            error: IndexingError {
+                kind: err.kind().to_string(),
+                message: err.to_string(),
+                occurred_at: now,
+            },
+            next_attempt_at: next_attempt_at(state, 1),
+            created_at: now,
+            updated_at: now,
+        })
+        .await?;
+    if let Some(session) = session {
+        session.skipped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    Ok(None)
+}
+
+// Fetch stage of the sync pipeline
+// Fetches the transactions of every signature batch with a bounded number of RPC calls
+// in flight and flushes them to the insert stage in sub-batches.
+// A transaction that can't be fetched is skipped and recorded as a dead letter
+async fn fetch_transactions(
+    state: &AppState,
+    session: &AddressSession,
+    cluster: Cluster,
+    address: &str,
+    receiver: mpsc::Receiver<SignatureBatch>,
+    sender: mpsc::Sender<TransactionChunk>,
+) -> Result<(), AppError> {
+    // Flatten the signature batches into single signatures
+    // and tag the last signature of every batch with its completion marker
+    let fetches = ReceiverStream::new(receiver)
+        .flat_map(|batch| {
+            let completed = CompletedBatch {
+                batch: batch.batch,
+                signatures: batch.signatures.len(),
+                before_signature: batch.signatures.last().map(|sign| sign.signature.clone()),
+            };
+            let last = batch.signatures.len().saturating_sub(1);
+
+            stream::iter(
+                batch
+                    .signatures
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(i, sign)| (sign, (i == last).then(|| completed.clone()))),
+            )
+        })
+        .map(|(sign, completed)| async move {
+            let txn =
+                fetch_or_skip(state, Some(session), cluster, address, &sign.signature).await?;
+            Ok::<_, AppError>((txn, completed))
+        })
+        // buffered keeps the fetched transactions in the signature order
+        // so a completion marker never overtakes the transactions of its batch
+        .buffered(state.config.fetch_concurrency);
+    let mut fetches = pin!(fetches);
+
+    let mut txns: Vec<Transaction> = Vec::with_capacity(state.config.insert_batch_size);
+    while let Some(fetched) = fetches.next().await {
+        let (txn, completed) = fetched?;
+        txns.extend(txn);
 
-            txns.push(Transaction {
-                signature: sign.signature.clone(),
-                account_address: address.clone(),
-                slot: txn.slot as i64,
-                block_time: txn.block_time,
-                transaction: serde_json::to_value(txn.transaction)?,
-                indexed_at: bson_current_time(),
-            });
+        // Flush the sub-batch once it is full or the signature batch is completed
+        if txns.len() >= state.config.insert_batch_size || completed.is_some() {
+            let chunk = TransactionChunk {
+                txns: std::mem::take(&mut txns),
+                completed,
+            };
+            if sender.send(chunk).await.is_err() {
+                break;
+            }
         }
+    }
+
+    Ok(())
+}
 
-        // Insert the transactions into DB
-        insert_transactions(&state.db, &txns).await?;
+// Insert stage of the sync pipeline
+// Stores the fetched transactions in DB and saves the checkpoint
+// once every transaction of a signature batch is stored
+async fn write_transactions(
+    state: &AppState,
+    session: &AddressSession,
+    cluster: Cluster,
+    address: &str,
+    mut checkpoint: IndexingCheckpoint,
+    mut receiver: mpsc::Receiver<TransactionChunk>,
+) -> Result<(), AppError> {
+    let mut total_txns = checkpoint.fetched_transactions as u64;
+
+    while let Some(chunk) = receiver.recv().await {
+        // Upsert the transactions into DB
+        let written = state.db.upsert_transactions(address, &chunk.txns).await?;
 
-        total_txns += txns.len();
+        total_txns += chunk.txns.len() as u64;
 
         // Get the total transactions count of the account in DB
-        let txn_count = get_transactions_count(&state.db, &address).await?;
+        let txn_count = state.db.get_transactions_count(cluster, address).await?;
 
-        // Send the transaction signatures data status to the channel
+        // Send the transactions data status to the channel
         session
             .emit_event(SyncStatus::TransactionDetails(serde_json::to_string(
                 &TotalFetch {
                     total: txn_count,
-                    fetched: total_txns as u64,
+                    fetched: total_txns,
+                    written,
                 },
             )?))
             .await;
 
-        batch += 1;
-        info!(
-            "Batch-{} of signatures {} & transactions {} completed",
-            batch,
-            signatures.len(),
-            txns.len()
-        );
-    }
+        if let Some(completed) = chunk.completed {
+            info!(
+                "Batch-{} of {} signatures & transactions completed",
+                completed.batch, completed.signatures
+            );
 
-    // Once the indexing/syncing/refreshing is completed
-    // set the address indexing state to Idle
-    update_address_indexing_state(
-        &state.db,
-        &address,
-        UpdateAddressIndexingState {
-            state: IndexingState::Idle,
-            updated_at: bson_current_time(),
-        },
-    )
-    .await?;
-    info!("Indexing is completed");
-
-    // Send the completed indexing message to the channel
-    session.emit_event(SyncStatus::Completed).await;
+            // Save the paging cursor and the running counts after every batch
+            // so that the next run for this address can resume from here
+            checkpoint.before_signature = completed.before_signature;
+            checkpoint.batch = completed.batch;
+            checkpoint.fetched_signatures += completed.signatures as i64;
+            checkpoint.fetched_transactions = total_txns as i64;
+            state
+                .db
+                .save_indexing_checkpoint(cluster, address, &checkpoint, Timestamp::now())
+                .await?;
+        }
+    }
 
     Ok(())
 }
@@ -362,48 +1087,93 @@ async fn continue_sync(
 pub async fn refresher(
     state: AppState,
     session: Arc<AddressSession>,
+    cluster: Cluster,
     address: String,
 ) -> Result<(), AppError> {
     // Convert the address str to Address struct instance of Solana account
     let public_key = Pubkey::from_str(&address)?;
+    state.rpc_for(cluster)?;
 
     // You can only refresh an indexed account
-    if !check_account_exists(&state.db, &address).await {
+    if !state.db.check_account_exists(cluster, &address).await {
         return Err(AppError::BadRequest("Account is not indexed".to_string()));
     }
 
+    // A previous indexing or syncing run of this address was interrupted,
+    // so finish that run from its checkpoint. Syncing again from the latest
+    // stored signature would leave a gap below the partially synced batches
+    let indexing_state = state
+        .db
+        .get_address_indexing_state(cluster, &address)
+        .await?;
+    reject_paused(&indexing_state)?;
+    if let Some(checkpoint) = &indexing_state.checkpoint {
+        info!(?checkpoint, "Resume the interrupted run of the address");
+        return resume_run(state, session, public_key, indexing_state).await;
+    }
+
     // Set the address indexing state to Syncing
-    update_address_indexing_state(
-        &state.db,
-        &address,
-        UpdateAddressIndexingState {
-            state: IndexingState::Syncing,
-            updated_at: bson_current_time(),
-        },
-    )
-    .await?;
+    state
+        .db
+        .update_address_indexing_state(
+            cluster,
+            &address,
+            UpdateAddressIndexingState {
+                state: IndexingState::Syncing,
+                checkpoint: None,
+                last_error: indexing_state.last_error,
+                updated_at: Timestamp::now(),
+            },
+        )
+        .await?;
 
     info!("Begin syncing the address");
     session.emit_event(SyncStatus::Syncing).await;
 
-    // Get the Solana account data of the address
-    let account = state.rpc.get_account(&public_key).await?;
+    if let Err(err) = sync_address(
+        state.clone(),
+        session,
+        cluster,
+        address.clone(),
+        public_key,
+        indexing_state.bounds,
+    )
+    .await
+    {
+        return Err(record_failure(&state, cluster, &address, err).await);
+    }
+
+    Ok(())
+}
+
+// Sync an indexed address: its latest account data and every transaction
+// newer than the latest stored signature
+async fn sync_address(
+    state: AppState,
+    session: Arc<AddressSession>,
+    cluster: Cluster,
+    address: String,
+    public_key: Pubkey,
+    bounds: IndexingBounds,
+) -> Result<(), AppError> {
+    let rpc = state.rpc_for(cluster)?;
+
+    // Get the Solana account data of the address, which may have been closed since
+    let account = rpc
+        .get_account(Some(&session), &public_key, state.config.commitment())
+        .await?;
     info!(?account);
 
     // Update the account data in DB with the latest data
-    let updated = update_account(
-        &state.db,
-        &address,
-        UpdateAccount {
-            lamports: account.lamports as i64,
-            owner: account.owner.to_string(),
-            executable: account.executable,
-            data_length: account.data.len() as i64,
-            rent_epoch: account.rent_epoch as i64,
-            last_updated_at: bson_current_time(),
-        },
-    )
-    .await?;
+    let has_history = state.db.get_signatures_count(cluster, &address).await? > 0;
+    let updated = state
+        .db
+        .update_account(
+            cluster,
+            &address,
+            account_update(account.as_ref(), has_history),
+        )
+        .await?;
 
     // Send the updated account data to the channel
     session
@@ -411,20 +1181,392 @@ pub async fn refresher(
         .await;
 
     // Get the latest signature to continue the sync/refresh
-    let latest_signature = get_latest_signature(&state.db, address.clone()).await?;
+    // An address without any transaction yet pages its history from the top instead
+    let latest_signature = state
+        .db
+        .get_latest_signature(cluster, address.clone())
+        .await?;
     info!(?latest_signature);
 
+    let checkpoint = IndexingCheckpoint {
+        until_signature: latest_signature,
+        ..Default::default()
+    };
+
+    // Save the starting point so an interrupted sync still stops at the same signature
+    state
+        .db
+        .save_indexing_checkpoint(cluster, &address, &checkpoint, Timestamp::now())
+        .await?;
+
+    // A sync pages the newer signatures down to its until signature whatever the bounds,
+    // they only limit the history of an address that had none yet
     continue_sync(
-        state,
-        session,
-        address,
-        public_key,
-        None,
-        Some(Signature::from_str(&latest_signature)?),
-        0,
-        0,
+        state, session, cluster, address, public_key, checkpoint, bounds,
     )
-    .await?;
+    .await
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::testing::{FakeCluster, sharing, state_with};
+
+    // A page of signatures from the newest slot down to the oldest one
+    fn page(slots: &[(u64, Option<i64>)]) -> Vec<RpcConfirmedTransactionStatusWithSignature> {
+        slots
+            .iter()
+            .map(
+                |&(slot, block_time)| RpcConfirmedTransactionStatusWithSignature {
+                    signature: format!("signature-{slot}"),
+                    slot,
+                    err: None,
+                    memo: None,
+                    block_time,
+                    confirmation_status: None,
+                },
+            )
+            .collect()
+    }
+
+    fn slots(signatures: &[RpcConfirmedTransactionStatusWithSignature]) -> Vec<u64> {
+        signatures.iter().map(|sign| sign.slot).collect()
+    }
+
+    #[test]
+    fn unbounded_pages_are_kept_whole() {
+        let mut signatures = page(&[(30, Some(300)), (20, Some(200)), (10, Some(100))]);
+
+        assert!(!apply_bounds(
+            &mut signatures,
+            &IndexingBounds::default(),
+            0
+        ));
+        assert_eq!(slots(&signatures), [30, 20, 10]);
+    }
+
+    #[test]
+    fn min_slot_drops_the_older_signatures() {
+        let mut signatures = page(&[(30, Some(300)), (20, Some(200)), (10, Some(100))]);
+        let bounds = IndexingBounds {
+            min_slot: Some(20),
+            ..Default::default()
+        };
+
+        assert!(apply_bounds(&mut signatures, &bounds, 0));
+        assert_eq!(slots(&signatures), [30, 20]);
+    }
+
+    #[test]
+    fn since_drops_everything_after_the_first_older_signature() {
+        let mut signatures = page(&[(30, Some(300)), (20, Some(150)), (10, None)]);
+        let bounds = IndexingBounds {
+            since: Some(200),
+            ..Default::default()
+        };
+
+        assert!(apply_bounds(&mut signatures, &bounds, 0));
+        assert_eq!(slots(&signatures), [30]);
+    }
+
+    #[test]
+    fn max_signatures_counts_the_stored_ones() {
+        let bounds = IndexingBounds {
+            max_signatures: Some(5),
+            ..Default::default()
+        };
+
+        let mut signatures = page(&[(30, None), (20, None), (10, None)]);
+        assert!(apply_bounds(&mut signatures, &bounds, 3));
+        assert_eq!(slots(&signatures), [30, 20]);
+
+        let mut signatures = page(&[(30, None), (20, None)]);
+        assert!(!apply_bounds(&mut signatures, &bounds, 3));
+        assert_eq!(slots(&signatures), [30, 20]);
+
+        let mut signatures = page(&[(30, None)]);
+        assert!(apply_bounds(&mut signatures, &bounds, 5));
+        assert!(signatures.is_empty());
+    }
+
+    async fn index(state: &AppState, address: &str, bounds: Option<IndexingBounds>) {
+        let session = state.get_or_create_session(Cluster::Devnet, address);
+        indexer(
+            state.clone(),
+            session,
+            Cluster::Devnet,
+            address.to_string(),
+            bounds,
+        )
+        .await
+        .unwrap();
+    }
+
+    async fn counts(state: &AppState, address: &str) -> (u64, u64) {
+        let signatures = state
+            .db
+            .get_signatures_count(Cluster::Devnet, address)
+            .await
+            .unwrap();
+        let transactions = state
+            .db
+            .get_transactions_count(Cluster::Devnet, address)
+            .await
+            .unwrap();
+        (signatures, transactions)
+    }
+
+    async fn indexing_state(state: &AppState, address: &str) -> AddressIndexingState {
+        state
+            .db
+            .get_address_indexing_state(Cluster::Devnet, address)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn indexer_stores_the_whole_history() {
+        let state = state_with(FakeCluster::new(45));
+        let address = Pubkey::new_unique().to_string();
+
+        index(&state, &address, None).await;
+
+        assert_eq!(counts(&state, &address).await, (45, 45));
+        let record = indexing_state(&state, &address).await;
+        assert_eq!(record.state, IndexingState::Idle);
+        assert!(record.checkpoint.is_none());
+        assert!(
+            state
+                .db
+                .check_account_exists(Cluster::Devnet, &address)
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn indexer_stops_at_the_bounds() {
+        let state = state_with(FakeCluster::new(45));
+        let address = Pubkey::new_unique().to_string();
+        let bounds = IndexingBounds {
+            max_signatures: Some(30),
+            ..Default::default()
+        };
+
+        index(&state, &address, Some(bounds.clone())).await;
+
+        assert_eq!(counts(&state, &address).await, (30, 30));
+        let record = indexing_state(&state, &address).await;
+        assert_eq!(record.state, IndexingState::Idle);
+        assert_eq!(record.bounds, bounds);
+    }
+
+    #[tokio::test]
+    async fn indexer_completes_when_the_first_batch_reaches_the_bounds() {
+        let cluster = Arc::new(FakeCluster::new(45));
+        let state = sharing(cluster.clone());
+        let address = Pubkey::new_unique().to_string();
+        let bounds = IndexingBounds {
+            min_slot: Some(40),
+            ..Default::default()
+        };
+
+        index(&state, &address, Some(bounds)).await;
+
+        assert_eq!(counts(&state, &address).await, (6, 6));
+        assert_eq!(
+            indexing_state(&state, &address).await.state,
+            IndexingState::Idle
+        );
+        // The paging stops with the first page instead of asking for the next one
+        assert_eq!(cluster.pages.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn deeper_bounds_extend_the_indexed_history() {
+        let state = state_with(FakeCluster::new(45));
+        let address = Pubkey::new_unique().to_string();
+        let bounds = |max_signatures| IndexingBounds {
+            max_signatures: Some(max_signatures),
+            ..Default::default()
+        };
+
+        index(&state, &address, Some(bounds(25))).await;
+        index(&state, &address, Some(bounds(35))).await;
+
+        assert_eq!(counts(&state, &address).await, (35, 35));
+        let record = indexing_state(&state, &address).await;
+        assert_eq!(record.state, IndexingState::Idle);
+        assert_eq!(record.bounds, bounds(35));
+    }
+
+    #[tokio::test]
+    async fn indexer_skips_the_transactions_it_cannot_fetch() {
+        let mut cluster = FakeCluster::new(25);
+        let failing = cluster.history[3].signature.clone();
+        cluster.failing.push(failing);
+        let state = state_with(cluster);
+        let address = Pubkey::new_unique().to_string();
+
+        index(&state, &address, None).await;
+
+        assert_eq!(counts(&state, &address).await, (25, 24));
+        let stats = state
+            .db
+            .get_indexer_stats(Cluster::Devnet, &address)
+            .await
+            .unwrap();
+        assert_eq!(stats.pending_dead_letters, 1);
+        // The index is incomplete until the dead letter is retried
+        let record = indexing_state(&state, &address).await;
+        assert_eq!(record.state, IndexingState::Partial);
+        assert!(record.checkpoint.is_none());
+        assert_eq!(
+            record.last_error.as_ref().map(|error| error.kind.as_str()),
+            Some(SKIPPED_TRANSACTIONS)
+        );
+        assert!(!record.retry_recommendation().retry);
+    }
+
+    #[tokio::test]
+    async fn an_unavailable_cluster_fails_the_run_instead_of_skipping() {
+        let mut cluster = FakeCluster::new(25);
+        let unavailable = cluster.history[22].signature.clone();
+        cluster.unavailable.push(unavailable);
+        let state = state_with(cluster);
+        let address = Pubkey::new_unique().to_string();
+
+        let session = state.get_or_create_session(Cluster::Devnet, &address);
+        let indexed = indexer(
+            state.clone(),
+            session,
+            Cluster::Devnet,
+            address.clone(),
+            None,
+        )
+        .await;
+
+        assert!(matches!(indexed, Err(AppError::Unavailable(_))));
+        let stats = state
+            .db
+            .get_indexer_stats(Cluster::Devnet, &address)
+            .await
+            .unwrap();
+        assert_eq!(stats.pending_dead_letters, 0);
+        // The first batch was stored, so the run resumes from its checkpoint
+        let record = indexing_state(&state, &address).await;
+        assert_eq!(record.state, IndexingState::Partial);
+        assert!(record.checkpoint.is_some());
+        assert!(record.retry_recommendation().retry);
+    }
+
+    async fn move_to(state: &AppState, address: &str, next: IndexingState) {
+        state
+            .db
+            .update_address_indexing_state(
+                Cluster::Devnet,
+                address,
+                UpdateAddressIndexingState {
+                    state: next,
+                    checkpoint: None,
+                    last_error: None,
+                    updated_at: Timestamp::now(),
+                },
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn paused_addresses_wait_for_the_admin_resume() {
+        let state = state_with(FakeCluster::new(5));
+        let address = Pubkey::new_unique().to_string();
+        index(&state, &address, None).await;
+        move_to(&state, &address, IndexingState::Syncing).await;
+        move_to(&state, &address, IndexingState::Paused).await;
+
+        // Indexing or refreshing the address again is refused instead of failing the transition
+        let session = state.get_or_create_session(Cluster::Devnet, &address);
+        let indexed = indexer(
+            state.clone(),
+            session.clone(),
+            Cluster::Devnet,
+            address.clone(),
+            None,
+        )
+        .await;
+        assert!(
+            matches!(indexed, Err(AppError::BadRequest(message)) if message.contains("paused"))
+        );
+        let refreshed = refresher(state.clone(), session, Cluster::Devnet, address.clone()).await;
+        assert!(
+            matches!(refreshed, Err(AppError::BadRequest(message)) if message.contains("paused"))
+        );
+        assert_eq!(
+            indexing_state(&state, &address).await.state,
+            IndexingState::Paused
+        );
+
+        // The admin resume queues the run again, which then picks it up
+        move_to(&state, &address, IndexingState::Queued).await;
+        index(&state, &address, None).await;
+        assert_eq!(
+            indexing_state(&state, &address).await.state,
+            IndexingState::Idle
+        );
+    }
+
+    #[tokio::test]
+    async fn continue_sync_resumes_below_the_checkpoint() {
+        let cluster = FakeCluster::new(45);
+        let before_signature = cluster.history[19].signature.clone();
+        let state = state_with(cluster);
+        let address = Pubkey::new_unique().to_string();
+
+        // A run interrupted after its first batch of 20 signatures
+        state
+            .db
+            .insert_address_indexing_state(AddressIndexingState {
+                id: document_id(Cluster::Devnet, &address),
+                cluster: Cluster::Devnet,
+                address: address.clone(),
+                state: IndexingState::Indexing,
+                checkpoint: None,
+                bounds: IndexingBounds::default(),
+                last_error: None,
+                started_at: Some(Timestamp::now()),
+                finished_at: None,
+                created_at: Timestamp::now(),
+                updated_at: Timestamp::now(),
+            })
+            .await
+            .unwrap();
+        let checkpoint = IndexingCheckpoint {
+            before_signature: Some(before_signature),
+            until_signature: None,
+            batch: 1,
+            fetched_signatures: 20,
+            fetched_transactions: 20,
+        };
+
+        let session = state.get_or_create_session(Cluster::Devnet, &address);
+        continue_sync(
+            state.clone(),
+            session,
+            Cluster::Devnet,
+            address.clone(),
+            Pubkey::from_str(&address).unwrap(),
+            checkpoint,
+            IndexingBounds::default(),
+        )
+        .await
+        .unwrap();
+
+        // Only the 25 signatures below the checkpoint are paged
+        assert_eq!(counts(&state, &address).await, (25, 25));
+        let record = indexing_state(&state, &address).await;
+        assert_eq!(record.state, IndexingState::Idle);
+        assert!(record.checkpoint.is_none());
+    }
 }
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use async_trait::async_trait;
+use solana_client::{
+    rpc_client::GetConfirmedSignaturesForAddress2Config, rpc_config::RpcTransactionConfig,
+    rpc_response::RpcConfirmedTransactionStatusWithSignature,
+};
+use solana_commitment_config::CommitmentConfig;
+use solana_sdk::{account::Account as SolanaAccount, pubkey::Pubkey, signature::Signature};
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction,
+    EncodedTransactionWithStatusMeta, TransactionConfirmationStatus, TransactionStatus,
+};
+use tokio::sync::Notify;
+
+use crate::{
+    app_state::{AddressSession, AppState},
+    cluster::Cluster,
+    config::Config,
+    db::memory::MemoryStorage,
+    error::AppError,
+    rpc::SolanaApi,
+};
+
+// Holds the fetch of a transaction until the test lets it go on
+#[derive(Default)]
+pub struct Hold {
+    pub signature: String,
+    pub reached: Notify,
+    pub release: Notify,
+}
+
+// A cluster holding the history of a single address, newest signature first
+// The transactions of the failing signatures can't be fetched
+// and the RPC is unavailable by the time the unavailable ones are fetched
+// Like the RPC client, it fails the calls of a session that was asked to stop
+pub struct FakeCluster {
+    pub history: Vec<RpcConfirmedTransactionStatusWithSignature>,
+    pub failing: Vec<String>,
+    pub unavailable: Vec<String>,
+    pub hold: Option<Arc<Hold>>,
+    // The pages of signatures requested so far
+    pub pages: AtomicUsize,
+    // The statuses the cluster reports, a signature without one is unknown to it
+    pub statuses: Mutex<HashMap<String, TransactionStatus>>,
+}
+
+impl FakeCluster {
+    pub fn new(signatures: u64) -> Self {
+        let history = (0..signatures)
+            .rev()
+            .map(|slot| RpcConfirmedTransactionStatusWithSignature {
+                signature: Signature::from([slot as u8 + 1; 64]).to_string(),
+                slot: slot + 1,
+                err: None,
+                memo: None,
+                block_time: None,
+                confirmation_status: Some(TransactionConfirmationStatus::Finalized),
+            })
+            .collect();
+
+        FakeCluster {
+            history,
+            failing: vec![],
+            unavailable: vec![],
+            hold: None,
+            pages: AtomicUsize::new(0),
+            statuses: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Report the status of the signature from now on, or forget it without one
+    pub fn set_status(
+        &self,
+        signature: &str,
+        slot: u64,
+        status: Option<TransactionConfirmationStatus>,
+    ) {
+        let mut statuses = self.statuses.lock().unwrap();
+        match status {
+            Some(status) => {
+                statuses.insert(
+                    signature.to_string(),
+                    TransactionStatus {
+                        slot,
+                        confirmations: None,
+                        status: Ok(()),
+                        err: None,
+                        confirmation_status: Some(status),
+                    },
+                );
+            }
+            None => {
+                statuses.remove(signature);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl SolanaApi for FakeCluster {
+    async fn get_account(
+        &self,
+        session: Option<&AddressSession>,
+        _pubkey: &Pubkey,
+        _commitment: CommitmentConfig,
+    ) -> Result<Option<SolanaAccount>, AppError> {
+        check_stop(session)?;
+        Ok(None)
+    }
+
+    async fn get_signatures_for_address_with_config(
+        &self,
+        session: Option<&AddressSession>,
+        _address: &Pubkey,
+        config: GetConfirmedSignaturesForAddress2Config,
+    ) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, AppError> {
+        check_stop(session)?;
+        self.pages.fetch_add(1, Ordering::Relaxed);
+        let position = |signature: Option<Signature>| {
+            signature.and_then(|signature| {
+                self.history
+                    .iter()
+                    .position(|sign| sign.signature == signature.to_string())
+            })
+        };
+        let start = position(config.before).map_or(0, |i| i + 1);
+        let end = position(config.until).unwrap_or(self.history.len());
+
+        Ok(self.history[start..end.max(start)]
+            .iter()
+            .take(config.limit.unwrap_or(1000))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_transaction_with_config(
+        &self,
+        session: Option<&AddressSession>,
+        signature: &Signature,
+        _config: RpcTransactionConfig,
+    ) -> Result<EncodedConfirmedTransactionWithStatusMeta, AppError> {
+        let signature = signature.to_string();
+        if let Some(hold) = self
+            .hold
+            .as_ref()
+            .filter(|hold| hold.signature == signature)
+        {
+            hold.reached.notify_one();
+            hold.release.notified().await;
+        }
+        check_stop(session)?;
+        if self.failing.contains(&signature) {
+            return Err(AppError::Solana("Transaction not available".to_string()));
+        }
+        if self.unavailable.contains(&signature) {
+            return Err(AppError::Unavailable("Connection refused".to_string()));
+        }
+        let sign = self
+            .history
+            .iter()
+            .find(|sign| sign.signature == signature)
+            .ok_or_else(|| AppError::NotFound("Transaction not found".to_string()))?;
+
+        Ok(EncodedConfirmedTransactionWithStatusMeta {
+            slot: sign.slot,
+            transaction: EncodedTransactionWithStatusMeta {
+                transaction: EncodedTransaction::LegacyBinary(String::new()),
+                meta: None,
+                version: None,
+            },
+            block_time: sign.block_time,
+        })
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        _session: Option<&AddressSession>,
+        signatures: &[Signature],
+    ) -> Result<Vec<Option<TransactionStatus>>, AppError> {
+        let statuses = self.statuses.lock().unwrap();
+        Ok(signatures
+            .iter()
+            .map(|signature| statuses.get(&signature.to_string()).cloned())
+            .collect())
+    }
+}
+
+fn check_stop(session: Option<&AddressSession>) -> Result<(), AppError> {
+    match session {
+        Some(session) => session.check_stop(),
+        None => Ok(()),
+    }
+}
+
+// The state of a devnet served by the cluster, over an in-memory storage
+pub fn state_with(cluster: FakeCluster) -> AppState {
+    sharing(Arc::new(cluster))
+}
+
+// The state of a cluster the test keeps looking at
+pub fn sharing(cluster: Arc<FakeCluster>) -> AppState {
+    let rpc: HashMap<_, Arc<dyn SolanaApi>> =
+        HashMap::from([(Cluster::Devnet, cluster as Arc<dyn SolanaApi>)]);
+
+    AppState::new(
+        Arc::new(MemoryStorage::default()),
+        rpc,
+        Arc::new(Config::from_env()),
+    )
+}
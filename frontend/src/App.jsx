@@ -33,7 +33,9 @@ export default function App() {
     });
     const data = await res.json();
     console.log(data);
-    if (!res.ok || data.state == "indexing") {
+    // Only idle or syncing addresses are fully indexed, the index SSE
+    // picks up every other address from where its last run stopped
+    if (!res.ok || !["idle", "syncing"].includes(data.state)) {
       setIndexed(false);
     } else {
       setIndexed(true);
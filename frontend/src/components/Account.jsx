@@ -33,8 +33,20 @@ export default function Account({ address, account, setAccount, setError }) {
             <tbody>
               <tr>
                 <td>Address</td>
-                <td className="mono responsive-td">{account._id}</td>
+                <td className="mono responsive-td">{account.address}</td>
               </tr>
+              {account.status && account.status !== "active" && (
+                <tr>
+                  <td>Status</td>
+                  <td className="responsive-td">
+                    <strong>
+                      {account.status === "closed"
+                        ? "Closed account"
+                        : "No account (never funded)"}
+                    </strong>
+                  </td>
+                </tr>
+              )}
               <tr>
                 <td>Balance (SOL)</td>
                 <td className="responsive-td">
@@ -25,6 +25,11 @@ export default function IndexerStats({
     const sse = new EventSource(url);
     sseRef.current = sse;
 
+    sse.addEventListener("queued", (e) => {
+      const data = JSON.parse(e.data);
+      setState(`⏳ Queued (${data.position} of ${data.pending})`);
+    });
+
     sse.addEventListener("indexing", () => {
       setState("▶️ Running");
       setAccountFetched(false);
@@ -60,6 +65,18 @@ export default function IndexerStats({
       }
     });
 
+    sse.addEventListener("paused", () => {
+      sse.close();
+      setLoading(false);
+      setState("⏸️ Paused");
+    });
+
+    sse.addEventListener("cancelled", () => {
+      sse.close();
+      setLoading(false);
+      setState("⏹️ Cancelled");
+    });
+
     sse.addEventListener("close", () => {
       sse.close();
       setLoading(false);
@@ -85,11 +85,11 @@ export default function TransactionHistory({
           </thead>
           <tbody>
             {txns.map((txn) => (
-              <tr key={txn._id}>
+              <tr key={txn.signature}>
                 <td>
                   <div className="truncated-text">
-                    <a href="#" onClick={(e) => onSignatureClick(e, txn._id)}>
-                      {txn._id}
+                    <a href="#" onClick={(e) => onSignatureClick(e, txn.signature)}>
+                      {txn.signature}
                     </a>
                   </div>
                 </td>